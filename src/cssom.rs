@@ -1,8 +1,20 @@
 use super::dom::*;
+use parser::Span;
+use std::fmt;
 
 #[derive(Clone)]
 pub struct StyleSheet {
     pub rules: Vec<Rule>,
+    /// Comments in source order, only kept by `css_parser::parse_with_comments`.
+    pub comments: Vec<Comment>,
+}
+
+/// A `/* */` comment, kept as trivia for tools which write stylesheets back
+/// out.
+#[derive(Clone, Debug)]
+pub struct Comment {
+    pub text: String,
+    pub span: Span,
 }
 
 #[derive(Clone, Debug)]
@@ -38,7 +50,19 @@ impl Selector {
     pub fn matches(&self, element: &ElementData) -> bool {
         match &self.selector_type {
             SelectorType::SimpleSelector(simple) => {
-                if simple.tag_name.iter().any(|tag| element.tag_name != *tag) {
+                if simple
+                    .namespace
+                    .iter()
+                    .any(|url| element.namespace.url() != url)
+                {
+                    return false;
+                }
+
+                if simple
+                    .tag_name
+                    .iter()
+                    .any(|tag| !matches_tag_name(element, tag))
+                {
                     return false;
                 }
 
@@ -54,7 +78,7 @@ impl Selector {
                     return false;
                 }
 
-                return true;
+                true
             }
         }
     }
@@ -62,6 +86,9 @@ impl Selector {
 
 #[derive(Clone, Debug)]
 pub struct SimpleSelectorData {
+    /// The namespace URL from a `prefix|` or a default `@namespace`, empty
+    /// for `|name`. None, as for `*|name`, matches any namespace.
+    pub namespace: Option<String>,
     pub tag_name: Option<String>,
     pub id: Option<String>,
     pub class: Vec<String>,
@@ -71,13 +98,69 @@ pub struct SimpleSelectorData {
 pub struct Declaration {
     pub name: String,
     pub value: Value,
+    // Set by a trailing `!important`, which wins over normal declarations.
+    pub important: bool,
 }
 
+/// A declaration's value. Shown as CSS text, with strings and URLs quoted.
 #[derive(Clone, Debug)]
 pub enum Value {
-    // Keyword(String),
+    Keyword(String),
     Length(f32, Unit),
-    // ColorValue(Color),
+    Number(f32),
+    Percentage(f32),
+    String(String),
+    Url(String),
+    Color(Color),
+    /// Replaced by the element's own `color` during styling.
+    CurrentColor,
+    /// Components separated by whitespace, such as `0 auto`.
+    SpaceSeparated(Vec<Value>),
+    /// Values separated by commas, such as `"Helvetica Neue", sans-serif`.
+    CommaSeparated(Vec<Value>),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Value::Keyword(ref keyword) => write!(f, "{}", keyword),
+            Value::Length(amount, unit) => write!(f, "{}{}", amount, unit),
+            Value::Number(number) => write!(f, "{}", number),
+            Value::Percentage(number) => write!(f, "{}%", number),
+            Value::String(ref text) => write!(f, "{}", quote(text)),
+            Value::Url(ref url) => write!(f, "url({})", quote(url)),
+            Value::Color(color) => write!(f, "{}", color),
+            Value::CurrentColor => write!(f, "currentcolor"),
+            Value::SpaceSeparated(ref values) => write_separated(f, values, " "),
+            Value::CommaSeparated(ref values) => write_separated(f, values, ", "),
+        }
+    }
+}
+
+fn write_separated(f: &mut fmt::Formatter, values: &[Value], separator: &str) -> fmt::Result {
+    for (index, value) in values.iter().enumerate() {
+        if index > 0 {
+            write!(f, "{}", separator)?;
+        }
+        write!(f, "{}", value)?;
+    }
+    Ok(())
+}
+
+fn quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' => quoted.push_str("\\a "),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -85,10 +168,59 @@ pub enum Unit {
     Px,
 }
 
-// #[derive(Clone, Copy)]
-// pub struct Color {
-//     r: u8,
-//     g: u8,
-//     b: u8,
-//     a: u8,
-// }
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Unit::Px => write!(f, "px"),
+        }
+    }
+}
+
+/// An sRGB color with 8-bit channels. Shown the way CSSOM serializes it,
+/// as `rgb(r, g, b)` or `rgba(r, g, b, alpha)`.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+pub const BLACK: Color = Color {
+    r: 0,
+    g: 0,
+    b: 0,
+    a: 255,
+};
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.a == 255 {
+            return write!(f, "rgb({}, {}, {})", self.r, self.g, self.b);
+        }
+        // The shortest alpha which reads back as the same byte.
+        let alpha = f32::from(self.a) / 255.0;
+        let rounded = (alpha * 100.0).round() / 100.0;
+        let alpha = if (rounded * 255.0).round() as u8 == self.a {
+            rounded
+        } else {
+            (alpha * 1000.0).round() / 1000.0
+        };
+        write!(f, "rgba({}, {}, {}, {})", self.r, self.g, self.b, alpha)
+    }
+}
+
+impl fmt::Debug for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+// Type selectors ignore case for HTML elements only, since SVG names such as
+// `foreignObject` are case-sensitive.
+fn matches_tag_name(element: &ElementData, tag: &str) -> bool {
+    match element.namespace {
+        Namespace::Html => element.tag_name.eq_ignore_ascii_case(tag),
+        _ => element.tag_name == tag,
+    }
+}
@@ -2,28 +2,57 @@ use cssom;
 use dom;
 use styling;
 
+use parser::diagnostic::Diagnostic;
+use parser::encoding::{Confidence, Encoding};
 use parser::*;
 
-pub fn create_document(html_source: String, css_source: String) -> Document {
-    let mut warnings = vec![];
-    let dom = html_parser::parse(html_source, &mut warnings);
-    let style_sheet = css_parser::parse(css_source, &mut warnings);
+/// With `xml_compatible` set, processing instructions in the html are kept
+/// as nodes rather than read as bogus comments.
+pub fn create_document(html_bytes: &[u8], css_bytes: &[u8], xml_compatible: bool) -> Document {
+    let mut html_warnings = vec![];
+    let (html_source, encoding, encoding_confidence) =
+        encoding::decode_html(html_bytes, &mut html_warnings);
+    let (css_source, _) = encoding::decode_css(css_bytes);
+
+    let dom = if xml_compatible {
+        html_parser::parse_xml_compatible(html_source.clone(), &mut html_warnings)
+    } else {
+        html_parser::parse(html_source.clone(), &mut html_warnings)
+    };
+    let quirks_mode = match dom.node_type {
+        dom::NodeType::Document(ref document) => document.quirks_mode,
+        _ => dom::QuirksMode::NoQuirks,
+    };
+    let mut css_warnings = vec![];
+    let style_sheet = css_parser::parse(css_source.clone(), &mut css_warnings);
 
     let mut document = Document {
-        dom: dom.first().unwrap().clone(),
+        dom,
         style_sheet,
-        warnings,
+        html_source,
+        css_source,
+        html_warnings,
+        css_warnings,
+        encoding,
+        encoding_confidence,
+        quirks_mode,
     };
 
     document.on_document_changed();
 
-    return document;
+    document
 }
 
 pub struct Document {
     pub dom: dom::Node,
     pub style_sheet: cssom::StyleSheet,
-    pub warnings: Vec<String>,
+    pub html_source: String,
+    pub css_source: String,
+    pub html_warnings: Vec<Diagnostic>,
+    pub css_warnings: Vec<Diagnostic>,
+    pub encoding: Encoding,
+    pub encoding_confidence: Confidence,
+    pub quirks_mode: dom::QuirksMode,
 }
 
 impl Document {
@@ -40,14 +69,19 @@ impl Document {
         for style in &self.style_sheet.rules {
             output.push_str(format!("{:#?}\n", style).as_str());
         }
-        return output;
+        output
     }
 
     pub fn dump_warnings(&self) -> String {
         let mut output = String::new();
-        for warning in &self.warnings {
-            output += format!("{}\n", warning).as_str();
+        for warning in &self.html_warnings {
+            output += warning.render(&self.html_source, "html").as_str();
+            output += "\n";
+        }
+        for warning in &self.css_warnings {
+            output += warning.render(&self.css_source, "css").as_str();
+            output += "\n";
         }
-        return output;
+        output
     }
 }
@@ -1,15 +1,19 @@
 use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::mem;
+use std::slice;
 
 use super::cssom;
+use parser::Span;
 
 #[derive(Debug, Clone)]
 pub enum NodeType {
+    Document(DocumentData),
     Comment(String),
-    CData(String),
     DocType(DocTypeData),
     Element(ElementData),
     Text(String),
+    ProcessingInstruction(ProcessingInstructionData),
 }
 
 pub type PropertyMap = HashMap<String, cssom::Value>;
@@ -18,6 +22,7 @@ pub type PropertyMap = HashMap<String, cssom::Value>;
 pub struct Node {
     pub children: Vec<Node>,
     pub node_type: NodeType,
+    span: Span,
 }
 
 impl fmt::Display for Node {
@@ -27,14 +32,54 @@ impl fmt::Display for Node {
 }
 
 impl Node {
+    /// The source range the node was parsed from. Elements cover their start
+    /// tag through their end tag or last descendant, and elements the parser
+    /// implied have an empty span where they were inserted.
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn set_span(&mut self, span: Span) {
+        self.span = span;
+    }
+
+    /// The source ranges of an attribute's name and value, if this is an
+    /// element with that attribute.
+    pub fn attribute_span(&self, name: &str) -> Option<AttributeSpan> {
+        match self.node_type {
+            NodeType::Element(ref element) => element.attributes.span(name),
+            _ => None,
+        }
+    }
+
     fn pretty_print(&self, depth: usize) -> String {
         let tag = match self.node_type {
+            NodeType::Document(ref document) => {
+                format!("Document {{{}}}", document.quirks_mode)
+            }
             NodeType::Text(ref content) => String::from("Text {") + content.as_str() + "}",
-            NodeType::DocType(ref elem) => String::from("DocType {") + elem.version.as_str() + "}",
+            NodeType::DocType(ref doctype) => {
+                let mut output = format!("DocType {{{}", doctype.name);
+                if !doctype.public_id.is_empty() || !doctype.system_id.is_empty() {
+                    output += &format!(" {:?} {:?}", doctype.public_id, doctype.system_id);
+                }
+                if doctype.force_quirks {
+                    output += " force-quirks";
+                }
+                output + "}"
+            }
             NodeType::Comment(ref comment) => String::from("Comment {") + comment.as_str() + "}",
-            NodeType::CData(ref content) => String::from("CData {") + content.as_str() + "}",
+            NodeType::ProcessingInstruction(ref instruction) => {
+                format!(
+                    "ProcessingInstruction {{{} {}}}",
+                    instruction.target, instruction.data
+                )
+            }
             NodeType::Element(ref elem) => {
-                let mut output = elem.tag_name.clone();
+                let mut output = match elem.namespace {
+                    Namespace::Html => elem.tag_name.clone(),
+                    namespace => format!("{} {}", namespace, elem.tag_name),
+                };
                 output.push_str(" {");
                 let mut first = true;
                 for (key, value) in &elem.attributes {
@@ -44,11 +89,24 @@ impl Node {
                     output.push_str(key);
                     output.push_str(": \"");
                     output.push_str(value);
-                    output.push_str("\"");
+                    output.push('"');
+                    if let Some(span) = self.attribute_span(key) {
+                        let whole = Span {
+                            start: span.name.start,
+                            end: span.value.end,
+                        };
+                        output.push_str(&format!(" @ {}", whole));
+                    }
                     first = false;
                 }
                 output.push_str("} ");
-                output.push_str(format!("Style {:#?}", elem.style_values).as_str());
+                let mut styles: Vec<String> = elem
+                    .style_values
+                    .iter()
+                    .map(|(name, value)| format!("{}: {}", name, value))
+                    .collect();
+                styles.sort();
+                output.push_str(&format!("Style {{{}}}", styles.join("; ")));
                 output
             }
         };
@@ -61,17 +119,63 @@ impl Node {
         for child in &self.children {
             children_output += child.pretty_print(next_depth).as_str();
         }
-        return indent + tag.as_str() + "\n" + children_output.as_str();
+        indent
+            + tag.as_str()
+            + " @ "
+            + self.span.to_string().as_str()
+            + "\n"
+            + children_output.as_str()
+    }
+}
+
+/// The namespace of an element. Elements inside inline `<svg>` and `<math>`
+/// are foreign, keeping the mixed case names SVG uses, such as
+/// `foreignObject`. Their `xlink:`, `xml:` and `xmlns:` attributes keep the
+/// prefix in the attribute name.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Namespace {
+    Html,
+    Svg,
+    MathMl,
+}
+
+impl Namespace {
+    /// The namespace URL, as given in CSS `@namespace` rules.
+    pub fn url(&self) -> &'static str {
+        match *self {
+            Namespace::Html => "http://www.w3.org/1999/xhtml",
+            Namespace::Svg => "http://www.w3.org/2000/svg",
+            Namespace::MathMl => "http://www.w3.org/1998/Math/MathML",
+        }
+    }
+}
+
+impl fmt::Display for Namespace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Namespace::Html => write!(f, "html"),
+            Namespace::Svg => write!(f, "svg"),
+            Namespace::MathMl => write!(f, "math"),
+        }
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct ElementData {
+    pub namespace: Namespace,
     pub tag_name: String,
     pub attributes: AttrMap,
     pub style_values: PropertyMap,
 }
 
+/// Where an attribute's name and value were parsed from. Attributes without
+/// a value have an empty value span just after the name.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AttributeSpan {
+    pub name: Span,
+    pub value: Span,
+}
+
 impl ElementData {
     pub fn id(&self) -> Option<&String> {
         self.attributes.get("id")
@@ -85,54 +189,175 @@ impl ElementData {
     }
 }
 
+/// How closely a document follows the standards, decided by its DOCTYPE.
+/// Styling and layout emulate old browsers in the quirks modes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QuirksMode {
+    NoQuirks,
+    LimitedQuirks,
+    Quirks,
+}
+
+impl fmt::Display for QuirksMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            QuirksMode::NoQuirks => write!(f, "no-quirks"),
+            QuirksMode::LimitedQuirks => write!(f, "limited-quirks"),
+            QuirksMode::Quirks => write!(f, "quirks"),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
-pub struct TextData {
-    pub text: String,
-    pub style_values: PropertyMap,
+pub struct DocumentData {
+    pub quirks_mode: QuirksMode,
 }
 
+/// A DOCTYPE as written. Missing identifiers are empty, and `force_quirks`
+/// is set when the DOCTYPE was malformed.
 #[derive(Debug, Clone)]
 pub struct DocTypeData {
-    pub version: String,
+    pub name: String,
+    pub public_id: String,
+    pub system_id: String,
+    pub force_quirks: bool,
 }
 
-pub type AttrMap = HashMap<String, String>;
+/// A `<?target data?>` processing instruction, kept by the parser's XML
+/// compatibility mode.
+#[derive(Debug, Clone)]
+pub struct ProcessingInstructionData {
+    pub target: String,
+    pub data: String,
+}
 
-pub fn doctype(version: String) -> Node {
-    Node {
-        children: Vec::new(),
-        node_type: NodeType::DocType(DocTypeData { version: version }),
+/// An element's attributes, iterated in the order they were added, which is
+/// source order for parsed elements. Lookups by name go through an index.
+#[derive(Clone, Default)]
+pub struct AttrMap {
+    entries: Vec<(String, String, AttributeSpan)>,
+    index: HashMap<String, usize>,
+}
+
+impl AttrMap {
+    pub fn new() -> AttrMap {
+        AttrMap::default()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&String> {
+        self.index
+            .get(name)
+            .map(|&position| &self.entries[position].1)
+    }
+
+    pub fn span(&self, name: &str) -> Option<AttributeSpan> {
+        self.index
+            .get(name)
+            .map(|&position| self.entries[position].2)
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.index.contains_key(name)
+    }
+
+    /// Sets an attribute and where it was parsed from, keeping its position
+    /// if it was already present. Returns the value it replaced.
+    pub fn insert(&mut self, name: String, value: String, span: AttributeSpan) -> Option<String> {
+        match self.index.get(&name) {
+            Some(&position) => {
+                let entry = &mut self.entries[position];
+                entry.2 = span;
+                Some(mem::replace(&mut entry.1, value))
+            }
+            None => {
+                self.index.insert(name.clone(), self.entries.len());
+                self.entries.push((name, value, span));
+                None
+            }
+        }
+    }
+
+    pub fn iter(&self) -> AttrIter<'_> {
+        AttrIter {
+            entries: self.entries.iter(),
+        }
     }
 }
 
-pub fn text(data: String) -> Node {
-    Node {
-        children: Vec::new(),
-        node_type: NodeType::Text(data),
+pub struct AttrIter<'a> {
+    entries: slice::Iter<'a, (String, String, AttributeSpan)>,
+}
+
+impl<'a> Iterator for AttrIter<'a> {
+    type Item = (&'a String, &'a String);
+
+    fn next(&mut self) -> Option<(&'a String, &'a String)> {
+        self.entries.next().map(|(name, value, _)| (name, value))
     }
 }
 
-pub fn comment(comment: String) -> Node {
-    Node {
-        children: Vec::new(),
-        node_type: NodeType::Comment(comment),
+impl<'a> IntoIterator for &'a AttrMap {
+    type Item = (&'a String, &'a String);
+    type IntoIter = AttrIter<'a>;
+
+    fn into_iter(self) -> AttrIter<'a> {
+        self.iter()
     }
 }
 
-pub fn cdata(content: String) -> Node {
-    Node {
-        children: Vec::new(),
-        node_type: NodeType::CData(content),
+impl fmt::Debug for AttrMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
     }
 }
 
-pub fn element(name: String, attrs: AttrMap, children: Vec<Node>) -> Node {
+pub fn node(node_type: NodeType, children: Vec<Node>) -> Node {
     Node {
         children,
-        node_type: NodeType::Element(ElementData {
+        node_type,
+        span: Span::default(),
+    }
+}
+
+pub fn document(quirks_mode: QuirksMode, children: Vec<Node>) -> Node {
+    node(NodeType::Document(DocumentData { quirks_mode }), children)
+}
+
+pub fn doctype(name: String, public_id: String, system_id: String, force_quirks: bool) -> Node {
+    node(
+        NodeType::DocType(DocTypeData {
+            name,
+            public_id,
+            system_id,
+            force_quirks,
+        }),
+        Vec::new(),
+    )
+}
+
+pub fn text(data: String) -> Node {
+    node(NodeType::Text(data), Vec::new())
+}
+
+pub fn comment(comment: String) -> Node {
+    node(NodeType::Comment(comment), Vec::new())
+}
+
+pub fn processing_instruction(target: String, data: String) -> Node {
+    node(
+        NodeType::ProcessingInstruction(ProcessingInstructionData { target, data }),
+        Vec::new(),
+    )
+}
+
+pub fn element(name: String, attrs: AttrMap, children: Vec<Node>) -> Node {
+    node(
+        NodeType::Element(ElementData {
+            namespace: Namespace::Html,
             tag_name: name,
             attributes: attrs,
             style_values: HashMap::new(),
         }),
-    }
+        children,
+    )
 }
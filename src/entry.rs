@@ -4,6 +4,11 @@ use std::fs::File;
 use std::io::Read;
 
 use document;
+use dom::NodeType;
+use parser::css_parser;
+use parser::encoding;
+use parser::html_parser::{self, Attribute, Event};
+use serializer::{self, AttributeOrder, SerializeOptions};
 
 fn print_usage(program: &str, opts: Options) {
     let brief = format!("Usage: {} [options]", program);
@@ -21,6 +26,39 @@ pub fn entry() {
     let mut opts = Options::new();
     opts.optopt("h", "html", "set the html file to parse", "[FILE]");
     opts.optopt("c", "css", "set the css file to parse", "[FILE]");
+    opts.optflag(
+        "s",
+        "stream",
+        "parse the html file in chunks, printing elements as they close",
+    );
+    opts.optflag(
+        "t",
+        "tokens",
+        "print the tags and text of the html file without building a tree",
+    );
+    opts.optopt(
+        "",
+        "context",
+        "parse the html file as the contents of this element",
+        "[TAG]",
+    );
+    opts.optflag(
+        "",
+        "css-comments",
+        "print the comments of the css file with their positions",
+    );
+    opts.optflag("", "serialize", "print the html file back out as markup");
+    opts.optflag("", "pretty", "indent the markup printed by --serialize");
+    opts.optflag(
+        "",
+        "sort-attributes",
+        "sort attributes by name in the markup printed by --serialize",
+    );
+    opts.optflag(
+        "",
+        "xml",
+        "keep processing instructions such as <?xml ...?> instead of reading them as comments",
+    );
     opts.optflag("", "help", "print this help menu");
 
     let args: Vec<String> = env::args().collect();
@@ -38,6 +76,14 @@ pub fn entry() {
         return;
     }
 
+    if matches.opt_present("css-comments") {
+        match matches.opt_str("c") {
+            Some(css_file_path) => print_css_comments(css_file_path),
+            None => show_error("Missing css file path"),
+        }
+        return;
+    }
+
     let html_file_path = match matches.opt_str("h") {
         Some(p) => p,
         None => {
@@ -45,6 +91,31 @@ pub fn entry() {
             return;
         }
     };
+    let xml_compatible = matches.opt_present("xml");
+    if matches.opt_present("stream") {
+        stream_html(html_file_path);
+        return;
+    }
+    if matches.opt_present("tokens") {
+        print_events(html_file_path, xml_compatible);
+        return;
+    }
+    if let Some(context_tag) = matches.opt_str("context") {
+        print_fragment(html_file_path, &context_tag);
+        return;
+    }
+    if matches.opt_present("serialize") {
+        let options = SerializeOptions {
+            pretty: matches.opt_present("pretty"),
+            attribute_order: if matches.opt_present("sort-attributes") {
+                AttributeOrder::Alphabetical
+            } else {
+                AttributeOrder::Source
+            },
+        };
+        print_markup(html_file_path, &options, xml_compatible);
+        return;
+    }
     let css_file_path = match matches.opt_str("c") {
         Some(p) => p,
         None => {
@@ -56,18 +127,140 @@ pub fn entry() {
     let html = read_source(html_file_path);
     let css = read_source(css_file_path);
 
-    let document = document::create_document(html, css);
+    let document = document::create_document(&html, &css, xml_compatible);
+    println!(
+        "Encoding: {} ({})",
+        document.encoding, document.encoding_confidence
+    );
+    println!("Mode: {}", document.quirks_mode);
     println!("DOM Tree:\n{}", document.dump_dom_tree());
     println!("CSS:\n{}", document.dump_styles());
     println!("Warnings:\n{}", document.dump_warnings());
 }
 
-fn read_source(file_path: String) -> String {
+fn read_source(file_path: String) -> Vec<u8> {
     println!("File path: {}", file_path);
-    let mut buffer = String::new();
+    let mut buffer = vec![];
     File::open(file_path)
         .unwrap()
-        .read_to_string(&mut buffer)
+        .read_to_end(&mut buffer)
         .unwrap();
     buffer
 }
+
+fn stream_html(file_path: String) {
+    println!("File path: {}", file_path);
+    let mut file = File::open(file_path).unwrap();
+    let mut parser = html_parser::create_streaming();
+    let mut buffer = [0; 8192];
+    loop {
+        let length = file.read(&mut buffer).unwrap();
+        if length == 0 {
+            parser.finish();
+        } else {
+            parser.feed(&buffer[..length]);
+        }
+        for id in parser.take_completed() {
+            let node = parser.node(id);
+            if let NodeType::Element(ref element) = *node.node_type() {
+                println!(
+                    "Closed {} with {} children @ {}",
+                    element.tag_name,
+                    node.children().len(),
+                    node.span()
+                );
+            }
+        }
+        if length == 0 {
+            break;
+        }
+    }
+    if let Some((encoding, confidence)) = parser.encoding() {
+        println!("Encoding: {} ({})", encoding, confidence);
+    }
+    let mut warnings = vec![];
+    parser.into_document(&mut warnings);
+    println!("Warnings:");
+    for warning in &warnings {
+        println!("{}", warning);
+    }
+}
+
+fn print_events(file_path: String, xml_compatible: bool) {
+    let mut warnings = vec![];
+    let (html, _, _) = encoding::decode_html(&read_source(file_path), &mut warnings);
+    let mut events = html_parser::events(html);
+    events.set_xml_compatible(xml_compatible);
+    for (event, span) in events.by_ref() {
+        match event {
+            Event::StartTag(tag) => {
+                let attributes: Vec<String> =
+                    tag.attributes.iter().map(describe_attribute).collect();
+                println!("{} <{}> {}", span, tag.name, attributes.join(" "));
+            }
+            Event::EndTag(tag) => println!("{} </{}>", span, tag.name),
+            Event::Text(text) => println!("{} text {:?}", span, text),
+            Event::Comment(text) => println!("{} comment {:?}", span, text),
+            Event::Doctype(doctype) => println!("{} doctype {:?}", span, doctype.name),
+            Event::CData(text) => println!("{} cdata {:?}", span, text),
+            Event::ProcessingInstruction(instruction) => {
+                println!("{} <?{}> {:?}", span, instruction.target, instruction.data)
+            }
+        }
+    }
+    println!("Warnings:");
+    for warning in &warnings {
+        println!("{}", warning);
+    }
+    for error in events.take_errors() {
+        println!("{}", error);
+    }
+}
+
+fn describe_attribute(attribute: &Attribute) -> String {
+    format!("{}={:?}", attribute.name, attribute.value)
+}
+
+fn print_markup(file_path: String, options: &SerializeOptions, xml_compatible: bool) {
+    let mut warnings = vec![];
+    let (html, _, _) = encoding::decode_html(&read_source(file_path), &mut warnings);
+    let dom = if xml_compatible {
+        html_parser::parse_xml_compatible(html, &mut warnings)
+    } else {
+        html_parser::parse(html, &mut warnings)
+    };
+    print!("{}", serializer::serialize(&dom, options));
+}
+
+fn print_css_comments(file_path: String) {
+    let (css, _) = encoding::decode_css(&read_source(file_path));
+    let mut warnings = vec![];
+    let style_sheet = css_parser::parse_with_comments(css, &mut warnings);
+    for comment in &style_sheet.comments {
+        println!("{} {:?}", comment.span, comment.text);
+    }
+    println!("Warnings:");
+    for warning in &warnings {
+        println!("{}", warning);
+    }
+}
+
+fn print_fragment(file_path: String, context_tag: &str) {
+    let mut warnings = vec![];
+    let (html, _, _) = encoding::decode_html(&read_source(file_path), &mut warnings);
+    let nodes = match html_parser::parse_fragment(html, context_tag, &mut warnings) {
+        Ok(nodes) => nodes,
+        Err(error) => {
+            show_error(&error.message);
+            return;
+        }
+    };
+    println!("Fragment:");
+    for node in &nodes {
+        print!("{}", node);
+    }
+    println!("Warnings:");
+    for warning in &warnings {
+        println!("{}", warning);
+    }
+}
@@ -1,11 +1,18 @@
 extern crate getopts;
+#[cfg(test)]
+extern crate json;
 
 mod cssom;
 mod document;
 mod dom;
 mod entry;
 mod parser;
+mod serializer;
+#[cfg(test)]
+mod serializer_test;
 mod styling;
+#[cfg(test)]
+mod styling_test;
 
 fn main() {
     entry::entry();
@@ -1,168 +1,684 @@
 use cssom::*;
-use parser::parser;
+use parser::css_color;
+use parser::css_tokenizer::{self, HashType, Token};
+use parser::diagnostic::{self, Diagnostic};
+use parser::Span;
+use std::collections::HashMap;
 
-pub fn parse<'a>(css_text: String, warnings: &'a mut Vec<String>) -> StyleSheet {
+pub fn parse(css_text: String, warnings: &mut Vec<Diagnostic>) -> StyleSheet {
+    let tokens = css_tokenizer::tokenize(css_text, warnings);
+    parse_tokens(tokens, warnings)
+}
+
+/// Parses a stylesheet and keeps its comments, which `parse` drops.
+pub fn parse_with_comments(css_text: String, warnings: &mut Vec<Diagnostic>) -> StyleSheet {
+    let (tokens, comments) = css_tokenizer::tokenize_with_comments(css_text, warnings);
+    let mut style_sheet = parse_tokens(tokens, warnings);
+    style_sheet.comments = comments;
+    style_sheet
+}
+
+fn parse_tokens(tokens: Vec<(Token, Span)>, warnings: &mut Vec<Diagnostic>) -> StyleSheet {
     let mut parser = CSSParser {
-        parser: parser::create(css_text),
+        tokens,
+        position: 0,
         warnings,
+        namespaces: HashMap::new(),
+        default_namespace: None,
     };
-    return StyleSheet {
+    StyleSheet {
         rules: parser.parse_rules(),
-    };
+        comments: vec![],
+    }
 }
 
 struct CSSParser<'a> {
-    parser: parser::Parser,
-    warnings: &'a mut Vec<String>,
+    // Always ends with `Token::Eof`, which is never consumed.
+    tokens: Vec<(Token, Span)>,
+    position: usize,
+    warnings: &'a mut Vec<Diagnostic>,
+    // Declared by `@namespace` rules, by prefix.
+    namespaces: HashMap<String, String>,
+    default_namespace: Option<String>,
 }
 
 impl<'a> CSSParser<'a> {
     fn parse_rules(&mut self) -> Vec<Rule> {
         let mut rules = vec![];
-        while !self.parser.eof() {
-            self.parser.consume_whitespace();
-            rules.push(self.consume_rule());
-            self.parser.consume_whitespace();
+        // `@namespace` rules may only come after `@charset` and `@import`.
+        let mut namespaces_allowed = true;
+        loop {
+            // CDO and CDC are allowed around rules, for stylesheets embedded
+            // in HTML comments.
+            while matches!(
+                self.next_token(),
+                Token::Whitespace | Token::Cdo | Token::Cdc
+            ) {
+                self.consume_token();
+            }
+            match self.next_token().clone() {
+                Token::Eof => return rules,
+                Token::AtKeyword(ref name) if name.eq_ignore_ascii_case("namespace") => {
+                    self.consume_namespace_rule(namespaces_allowed)
+                }
+                Token::AtKeyword(name) => {
+                    if !name.eq_ignore_ascii_case("charset") && !name.eq_ignore_ascii_case("import")
+                    {
+                        namespaces_allowed = false;
+                    }
+                    self.consume_at_rule();
+                }
+                _ => {
+                    namespaces_allowed = false;
+                    if let Some(rule) = self.consume_rule() {
+                        rules.push(rule);
+                    }
+                }
+            }
         }
-        rules
     }
 
-    fn consume_rule(&mut self) -> Rule {
-        let selectors = self.consume_selectors();
-        let declarations = self.consume_declarations();
-        Rule {
-            selectors,
-            declarations,
+    // No at-rules are supported yet, so they are dropped up to their `;` or
+    // the end of their block.
+    fn consume_at_rule(&mut self) {
+        let span = self.next_span();
+        let message = format!("Dropping unsupported {}", describe_token(self.next_token()));
+        self.consume_token();
+        self.skip_at_rule();
+        self.warnings
+            .push(diagnostic::warning("unsupported-at-rule", span, &message));
+    }
+
+    // `@namespace svg url(http://www.w3.org/2000/svg);`, or without the
+    // prefix for the default namespace.
+    fn consume_namespace_rule(&mut self, allowed: bool) {
+        let span = self.next_span();
+        self.consume_token();
+        self.consume_whitespace();
+        let prefix = match self.next_token().clone() {
+            Token::Ident(prefix) => {
+                self.consume_token();
+                self.consume_whitespace();
+                Some(prefix)
+            }
+            _ => None,
+        };
+        let url = match self.next_token().clone() {
+            Token::String(url) | Token::Url(url) => {
+                self.consume_token();
+                Some(url)
+            }
+            Token::Function(ref name) if name.eq_ignore_ascii_case("url") => {
+                self.consume_token();
+                match self.consume_function_arguments().as_deref() {
+                    Some([Token::String(url)]) => Some(url.clone()),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        self.consume_whitespace();
+        match url {
+            _ if !allowed => {
+                self.skip_at_rule();
+                self.warnings.push(diagnostic::warning(
+                    "invalid-at-rule",
+                    span,
+                    "Dropping @namespace rule after other rules",
+                ));
+            }
+            Some(url) if matches!(self.next_token(), Token::Semicolon | Token::Eof) => {
+                self.consume_token();
+                match prefix {
+                    Some(prefix) => {
+                        self.namespaces.insert(prefix, url);
+                    }
+                    None => self.default_namespace = Some(url),
+                }
+            }
+            _ => {
+                self.skip_at_rule();
+                self.warnings.push(diagnostic::warning(
+                    "invalid-at-rule",
+                    span,
+                    "Dropping invalid @namespace rule",
+                ));
+            }
         }
     }
 
-    fn consume_selectors(&mut self) -> Vec<Selector> {
+    // Skips the rest of an at-rule, up to its `;` or the end of its block.
+    fn skip_at_rule(&mut self) {
+        loop {
+            match self.next_token() {
+                Token::Semicolon => {
+                    self.consume_token();
+                    break;
+                }
+                Token::OpenCurly => {
+                    self.consume_component_value();
+                    break;
+                }
+                // The end of an enclosing block.
+                Token::CloseCurly | Token::Eof => break,
+                _ => self.consume_component_value(),
+            }
+        }
+    }
+
+    fn consume_rule(&mut self) -> Option<Rule> {
+        let start = self.next_span();
+        match self.consume_selectors() {
+            // The block is skipped whole, nested blocks included.
+            None => {
+                self.consume_component_value();
+                None
+            }
+            Some(_) if *self.next_token() == Token::Eof => {
+                let span = Span {
+                    start: start.start,
+                    end: self.next_span().end,
+                };
+                self.warnings.push(diagnostic::warning(
+                    "eof-in-rule",
+                    span,
+                    "Dropping rule without a declaration block at the end of the stylesheet",
+                ));
+                None
+            }
+            Some(selectors) => Some(Rule {
+                selectors,
+                declarations: self.consume_declarations(),
+            }),
+        }
+    }
+
+    // Returns None when the selector list is invalid, which drops the rule.
+    fn consume_selectors(&mut self) -> Option<Vec<Selector>> {
         let mut selectors = vec![];
         loop {
-            self.parser.consume_whitespace();
-            selectors.push(Selector {
-                selector_type: SelectorType::SimpleSelector(self.consume_simple_selector()),
-            });
-            self.parser.consume_whitespace();
-            match self.parser.next_char() {
-                ',' => {
-                    self.parser.consume_char();
+            self.consume_whitespace();
+            let selector = self.consume_simple_selector();
+            self.consume_whitespace();
+            match (selector, self.next_token()) {
+                (Some(selector), &Token::Comma) => {
+                    self.consume_token();
+                    selectors.push(selector);
+                }
+                (Some(selector), &Token::OpenCurly) | (Some(selector), &Token::Eof) => {
+                    selectors.push(selector);
+                    break;
+                }
+                (_, token) => {
+                    let message = format!("Unexpected {} in selector", describe_token(token));
+                    let span = self.next_span();
+                    self.warnings
+                        .push(diagnostic::warning("invalid-selector", span, &message));
+                    while !matches!(self.next_token(), Token::OpenCurly | Token::Eof) {
+                        self.consume_component_value();
+                    }
+                    return None;
                 }
-                '{' => break,
-                _ => self.warnings.push(format!(
-                    "Consuming selectors: @{} - {}",
-                    self.parser.position(),
-                    self.parser.next_char()
-                )),
             }
         }
-        selectors.sort_by(|a, b| a.specificity().cmp(&b.specificity()));
-        selectors
+        selectors.sort_by_key(|selector| selector.specificity());
+        Some(selectors)
     }
 
-    fn consume_simple_selector(&mut self) -> SimpleSelectorData {
+    // A type or universal selector followed by any number of ID and class
+    // selectors, such as `p#intro.note`. The type or universal selector may
+    // have a namespace prefix, as in `svg|rect`, `*|rect` or `|rect`.
+    fn consume_simple_selector(&mut self) -> Option<Selector> {
         let mut selector = SimpleSelectorData {
+            namespace: self.default_namespace.clone(),
             tag_name: None,
             id: None,
             class: vec![],
         };
-        match self.parser.next_char() {
-            '#' => {
-                self.parser.consume_char();
-                selector.id = Some(self.consume_identifier());
+        let type_follows = |token: &Token| matches!(token, Token::Ident(_) | Token::Delim('*'));
+        match (self.next_token().clone(), self.peek_token(1).clone()) {
+            (Token::Ident(prefix), Token::Delim('|')) if type_follows(self.peek_token(2)) => {
+                // An undeclared prefix makes the selector invalid.
+                selector.namespace = Some(self.namespaces.get(&prefix)?.clone());
+                self.consume_token();
+                self.consume_token();
             }
-            '.' => {
-                self.parser.consume_char();
-                selector.class.push(self.consume_identifier());
+            (Token::Delim('*'), Token::Delim('|')) if type_follows(self.peek_token(2)) => {
+                selector.namespace = None;
+                self.consume_token();
+                self.consume_token();
             }
-            _ => {
-                selector.tag_name = Some(self.consume_identifier());
+            (Token::Delim('|'), ref next) if type_follows(next) => {
+                selector.namespace = Some(String::new());
+                self.consume_token();
             }
-        };
-        selector
+            _ => {}
+        }
+        let mut empty = true;
+        match self.next_token().clone() {
+            Token::Ident(name) => {
+                self.consume_token();
+                selector.tag_name = Some(name);
+                empty = false;
+            }
+            Token::Delim('*') => {
+                self.consume_token();
+                empty = false;
+            }
+            _ => {}
+        }
+        loop {
+            match self.next_token().clone() {
+                Token::Hash(id, HashType::Id) => {
+                    self.consume_token();
+                    selector.id = Some(id);
+                }
+                Token::Delim('.') => match self.peek_token(1).clone() {
+                    Token::Ident(class) => {
+                        self.consume_token();
+                        self.consume_token();
+                        selector.class.push(class);
+                    }
+                    _ => return None,
+                },
+                _ => break,
+            }
+            empty = false;
+        }
+        if empty {
+            return None;
+        }
+        Some(Selector {
+            selector_type: SelectorType::SimpleSelector(selector),
+        })
     }
 
+    // Called at the `{` of a rule's block.
     fn consume_declarations(&mut self) -> Vec<Declaration> {
         let mut declarations = vec![];
-        self.parser.consume_expected_text("{").unwrap();
-        while self.parser.next_char() != '}' {
-            self.parser.consume_whitespace();
+        self.consume_token();
+        loop {
+            while matches!(self.next_token(), Token::Whitespace | Token::Semicolon) {
+                self.consume_token();
+            }
+            match self.next_token() {
+                Token::CloseCurly => {
+                    self.consume_token();
+                    break;
+                }
+                Token::Eof => {
+                    let span = self.next_span();
+                    self.warnings.push(diagnostic::warning(
+                        "eof-in-rule",
+                        span,
+                        "Stylesheet ended inside a declaration block",
+                    ));
+                    break;
+                }
+                Token::AtKeyword(_) => {
+                    self.consume_at_rule();
+                    continue;
+                }
+                _ => {}
+            }
             match self.consume_declaration() {
                 Ok(declaration) => {
                     declarations.push(declaration);
                 }
-                Err(e) => {
-                    self.parser.consume_until_including(';');
-                    self.warnings.push(format!("Skipping declaration - {}", e));
+                Err(diagnostic) => {
+                    while !matches!(
+                        self.next_token(),
+                        Token::Semicolon | Token::CloseCurly | Token::Eof
+                    ) {
+                        self.consume_component_value();
+                    }
+                    self.warnings.push(diagnostic);
                 }
             }
-            self.parser.consume_whitespace();
         }
-        self.parser.consume_expected_text("}").unwrap();
         declarations
     }
 
-    fn consume_declaration(&mut self) -> Result<Declaration, String> {
-        let name = self.consume_identifier();
-        self.parser.consume_whitespace();
-        self.parser.consume_expected_text(":").unwrap();
-        self.parser.consume_whitespace();
-        match self.consume_value() {
-            Ok(value) => {
-                self.parser.consume_whitespace();
-                self.parser.consume_expected_text(";").unwrap();
-                Ok(Declaration { name, value })
+    fn consume_declaration(&mut self) -> Result<Declaration, Diagnostic> {
+        let name = match self.next_token().clone() {
+            Token::Ident(name) => name,
+            token => {
+                return Err(self.invalid_declaration(format!(
+                    "Expected a property name, found {}",
+                    describe_token(&token)
+                )))
             }
-            Err(e) => Err(e),
+        };
+        self.consume_token();
+        self.consume_whitespace();
+        if *self.next_token() != Token::Colon {
+            return Err(self.invalid_declaration(format!(
+                "Expected ':' after '{}', found {}",
+                name,
+                describe_token(self.next_token())
+            )));
+        }
+        self.consume_token();
+        self.consume_whitespace();
+        let value = if is_color_property(&name) {
+            self.consume_colors(&name)?
+        } else {
+            self.consume_value()?
+        };
+        self.consume_whitespace();
+        let important = self.consume_important();
+        match self.next_token() {
+            Token::Semicolon | Token::CloseCurly | Token::Eof => Ok(Declaration {
+                name,
+                value,
+                important,
+            }),
+            token => Err(self.invalid_declaration(format!(
+                "Unexpected {} after the value of '{}'",
+                describe_token(token),
+                name
+            ))),
         }
     }
 
-    fn consume_value(&mut self) -> Result<Value, String> {
-        match self.parser.next_char() {
-            '0'..='9' => self.consume_length(),
-            _ => Err(format!(
-                "ERROR@{} - Consuming value - Invalid character: '{}'",
-                self.parser.position(),
-                self.parser.next_char()
+    // A comma separated list of space separated components, such as
+    // `"Helvetica Neue", sans-serif` or `0 auto`. Lists of one value are
+    // left unwrapped.
+    fn consume_value(&mut self) -> Result<Value, Diagnostic> {
+        let mut values = vec![];
+        loop {
+            values.push(self.consume_components()?);
+            if *self.next_token() != Token::Comma {
+                break;
+            }
+            self.consume_token();
+            self.consume_whitespace();
+        }
+        match values.len() {
+            1 => Ok(values.remove(0)),
+            _ => Ok(Value::CommaSeparated(values)),
+        }
+    }
+
+    fn consume_components(&mut self) -> Result<Value, Diagnostic> {
+        let mut components = vec![self.consume_component()?];
+        loop {
+            // Components are only split at whitespace, so `1.2.3px` is not
+            // read as `1.2 .3px`.
+            let separated = *self.next_token() == Token::Whitespace;
+            self.consume_whitespace();
+            match self.next_token() {
+                Token::Comma | Token::Semicolon | Token::CloseCurly | Token::Eof => break,
+                Token::Delim('!') => break,
+                token if !separated => {
+                    return Err(self.invalid_declaration(format!(
+                        "Skipping declaration with {} directly after a value",
+                        describe_token(token)
+                    )))
+                }
+                _ => components.push(self.consume_component()?),
+            }
+        }
+        match components.len() {
+            1 => Ok(components.remove(0)),
+            _ => Ok(Value::SpaceSeparated(components)),
+        }
+    }
+
+    fn consume_component(&mut self) -> Result<Value, Diagnostic> {
+        let value = match self.next_token().clone() {
+            Token::Dimension(amount, unit) => Value::Length(amount, self.consume_unit(&unit)?),
+            Token::Number(number) => Value::Number(number),
+            Token::Percentage(number) => Value::Percentage(number),
+            Token::Ident(keyword) => Value::Keyword(keyword),
+            Token::String(text) => Value::String(text),
+            Token::Url(url) => Value::Url(url),
+            Token::Function(ref name) if name.eq_ignore_ascii_case("url") => {
+                return self.consume_quoted_url();
+            }
+            Token::Hash(..) => return self.consume_color(),
+            Token::Function(ref name) if css_color::is_color_function(name) => {
+                return self.consume_color();
+            }
+            token => {
+                return Err(self.invalid_declaration(format!(
+                    "Skipping declaration with invalid value starting with {}",
+                    describe_token(&token)
+                )))
+            }
+        };
+        self.consume_token();
+        Ok(value)
+    }
+
+    // `url("a.png")`, which the tokenizer leaves as a function.
+    fn consume_quoted_url(&mut self) -> Result<Value, Diagnostic> {
+        let span = self.next_span();
+        self.consume_token();
+        match self.consume_function_arguments().as_deref() {
+            Some([Token::String(url)]) => Ok(Value::Url(url.clone())),
+            _ => Err(diagnostic::warning(
+                "invalid-declaration",
+                span,
+                "Skipping declaration with invalid url",
             )),
         }
     }
 
-    fn consume_length(&mut self) -> Result<Value, String> {
-        let amount = self.consume_float();
+    // A color property's value. `border-color` takes one to four colors, one
+    // per side.
+    fn consume_colors(&mut self, name: &str) -> Result<Value, Diagnostic> {
+        if let Token::Ident(keyword) = self.next_token().clone() {
+            if is_css_wide_keyword(&keyword) {
+                self.consume_token();
+                return Ok(Value::Keyword(keyword));
+            }
+        }
+        let mut colors = vec![self.consume_color()?];
+        if name.eq_ignore_ascii_case("border-color") {
+            while colors.len() < 4
+                && *self.next_token() == Token::Whitespace
+                && !matches!(
+                    self.peek_token(1),
+                    Token::Semicolon | Token::CloseCurly | Token::Eof | Token::Delim('!')
+                )
+            {
+                self.consume_whitespace();
+                colors.push(self.consume_color()?);
+            }
+        }
+        match colors.len() {
+            1 => Ok(colors.remove(0)),
+            _ => Ok(Value::SpaceSeparated(colors)),
+        }
+    }
+
+    fn consume_color(&mut self) -> Result<Value, Diagnostic> {
+        let span = self.next_span();
+        let token = self.next_token().clone();
+        self.consume_token();
+        let color = match token {
+            Token::Hash(ref digits, _) => css_color::parse_hex(digits),
+            Token::Ident(ref name) if name.eq_ignore_ascii_case("currentcolor") => {
+                return Ok(Value::CurrentColor);
+            }
+            Token::Ident(ref name) => css_color::named_color(name),
+            Token::Function(ref name) => {
+                let arguments = self.consume_function_arguments();
+                arguments.and_then(|arguments| css_color::parse_color_function(name, &arguments))
+            }
+            _ => None,
+        };
+        color.map(Value::Color).ok_or_else(|| {
+            let span = Span {
+                start: span.start,
+                end: self.tokens[self.position - 1].1.end,
+            };
+            let message = format!(
+                "Skipping declaration with invalid color {}",
+                describe_token(&token)
+            );
+            diagnostic::warning("invalid-declaration", span, &message)
+        })
+    }
+
+    // Called after a function token. Returns the arguments without
+    // whitespace, or None on a nested block, which is left for recovery to
+    // skip.
+    fn consume_function_arguments(&mut self) -> Option<Vec<Token>> {
+        let mut arguments = vec![];
+        loop {
+            match self.next_token() {
+                Token::CloseParen => {
+                    self.consume_token();
+                    return Some(arguments);
+                }
+                Token::Eof => return Some(arguments),
+                Token::Whitespace => {}
+                token if closing_token(token).is_some() => return None,
+                token => arguments.push(token.clone()),
+            }
+            self.consume_token();
+        }
+    }
 
-        match self.consume_unit() {
-            Ok(unit) => Ok(Value::Length(amount, unit)),
-            Err(e) => Err(e),
+    // `!important`, with whitespace allowed after the `!`.
+    fn consume_important(&mut self) -> bool {
+        if *self.next_token() != Token::Delim('!') {
+            return false;
         }
+        let mut offset = 1;
+        while *self.peek_token(offset) == Token::Whitespace {
+            offset += 1;
+        }
+        match self.peek_token(offset) {
+            Token::Ident(name) if name.eq_ignore_ascii_case("important") => {}
+            _ => return false,
+        }
+        for _ in 0..=offset {
+            self.consume_token();
+        }
+        self.consume_whitespace();
+        true
     }
 
-    fn consume_float(&mut self) -> f32 {
-        let text = self.parser.consume_while(|c| match c {
-            '0'..='9' | '.' => true,
-            _ => false,
-        });
-        text.parse().unwrap()
+    fn consume_unit(&self, unit: &str) -> Result<Unit, Diagnostic> {
+        if unit.eq_ignore_ascii_case("px") {
+            Ok(Unit::Px)
+        } else {
+            Err(self
+                .invalid_declaration(format!("Skipping declaration with unknown unit '{}'", unit)))
+        }
     }
 
-    fn consume_unit(&mut self) -> Result<Unit, String> {
-        match self.parser.next_char().to_ascii_lowercase() {
-            'p' => {
-                self.parser.consume_expected_text("px").unwrap();
-                Ok(Unit::Px)
+    // Consumes a token, or a whole block or function up to its matching
+    // closing token, so that recovery never stops inside brackets.
+    fn consume_component_value(&mut self) {
+        let mut closing = vec![];
+        loop {
+            let next = self.next_token();
+            if *next == Token::Eof {
+                return;
+            }
+            if closing.last() == Some(next) {
+                closing.pop();
+            } else if let Some(token) = closing_token(next) {
+                closing.push(token);
+            }
+            self.consume_token();
+            if closing.is_empty() {
+                return;
             }
-            _ => Err(format!(
-                "ERROR@{} - Consuming unit found: '{}'",
-                self.parser.position(),
-                self.parser.next_char()
-            )),
         }
     }
 
-    fn consume_identifier(&mut self) -> String {
-        self.parser.consume_while(|c| match c {
-            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => true,
-            _ => false,
-        })
+    fn next_token(&self) -> &Token {
+        self.peek_token(0)
+    }
+
+    fn peek_token(&self, offset: usize) -> &Token {
+        let index = (self.position + offset).min(self.tokens.len() - 1);
+        &self.tokens[index].0
+    }
+
+    fn next_span(&self) -> Span {
+        self.tokens[self.position].1
+    }
+
+    fn consume_token(&mut self) {
+        if self.position < self.tokens.len() - 1 {
+            self.position += 1;
+        }
+    }
+
+    fn consume_whitespace(&mut self) {
+        while *self.next_token() == Token::Whitespace {
+            self.consume_token();
+        }
+    }
+
+    fn invalid_declaration(&self, message: String) -> Diagnostic {
+        diagnostic::warning("invalid-declaration", self.next_span(), &message)
+    }
+}
+
+const COLOR_PROPERTIES: &[&str] = &[
+    "color",
+    "background-color",
+    "border-color",
+    "border-top-color",
+    "border-right-color",
+    "border-bottom-color",
+    "border-left-color",
+    "outline-color",
+];
+
+fn is_color_property(name: &str) -> bool {
+    COLOR_PROPERTIES
+        .iter()
+        .any(|property| property.eq_ignore_ascii_case(name))
+}
+
+// Keywords every property accepts.
+fn is_css_wide_keyword(name: &str) -> bool {
+    ["inherit", "initial", "unset"]
+        .iter()
+        .any(|keyword| keyword.eq_ignore_ascii_case(name))
+}
+
+fn closing_token(token: &Token) -> Option<Token> {
+    match token {
+        Token::OpenCurly => Some(Token::CloseCurly),
+        Token::OpenSquare => Some(Token::CloseSquare),
+        Token::OpenParen | Token::Function(_) => Some(Token::CloseParen),
+        _ => None,
+    }
+}
+
+fn describe_token(token: &Token) -> String {
+    match token {
+        Token::Ident(name) => format!("'{}'", name),
+        Token::Function(name) => format!("'{}('", name),
+        Token::AtKeyword(name) => format!("'@{}'", name),
+        Token::Hash(name, _) => format!("'#{}'", name),
+        Token::String(_) | Token::BadString => String::from("a string"),
+        Token::Url(_) | Token::BadUrl => String::from("a url"),
+        Token::Delim(c) => format!("'{}'", c),
+        Token::Number(number) => format!("'{}'", number),
+        Token::Percentage(number) => format!("'{}%'", number),
+        Token::Dimension(number, unit) => format!("'{}{}'", number, unit),
+        Token::Whitespace => String::from("whitespace"),
+        Token::Cdo => String::from("'<!--'"),
+        Token::Cdc => String::from("'-->'"),
+        Token::Colon => String::from("':'"),
+        Token::Semicolon => String::from("';'"),
+        Token::Comma => String::from("','"),
+        Token::OpenSquare => String::from("'['"),
+        Token::CloseSquare => String::from("']'"),
+        Token::OpenParen => String::from("'('"),
+        Token::CloseParen => String::from("')'"),
+        Token::OpenCurly => String::from("'{'"),
+        Token::CloseCurly => String::from("'}'"),
+        Token::Eof => String::from("end of stylesheet"),
     }
 }
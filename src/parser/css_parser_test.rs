@@ -1,5 +1,8 @@
 use cssom::*;
+use dom::*;
 use parser::css_parser::*;
+use parser::diagnostic::{Diagnostic, Severity};
+use parser::html_parser;
 use std::fs::read_dir;
 use std::fs::File;
 use std::io::prelude::*;
@@ -8,14 +11,14 @@ use std::path::{Path, PathBuf};
 #[test]
 fn it_parses_element_selector_with_attribute() {
     let style_sheet = parse_css("div { height: 100px; }".to_string());
-    let rule: &Rule = style_sheet.rules.get(0).unwrap();
-    let selector: &Selector = rule.selectors.get(0).unwrap();
+    let rule: &Rule = style_sheet.rules.first().unwrap();
+    let selector: &Selector = rule.selectors.first().unwrap();
     match &selector.selector_type {
         SelectorType::SimpleSelector(s) => {
             assert_eq!(s.tag_name.clone().unwrap(), String::from("div"));
         }
     };
-    let declaration: &Declaration = rule.declarations.get(0).unwrap();
+    let declaration: &Declaration = rule.declarations.first().unwrap();
     assert_eq!(declaration.name, String::from("height"));
     match declaration.value {
         Value::Length(amount, unit) => {
@@ -25,20 +28,21 @@ fn it_parses_element_selector_with_attribute() {
             };
             assert_eq!(unit_result, "px")
         }
+        _ => panic!("Wrong value type"),
     };
 }
 
 #[test]
 fn it_parses_multiple_attributes() {
     let style_sheet = parse_css("span { height: 100px; width: 50px; }".to_string());
-    let rule: &Rule = style_sheet.rules.get(0).unwrap();
-    let selector: &Selector = rule.selectors.get(0).unwrap();
+    let rule: &Rule = style_sheet.rules.first().unwrap();
+    let selector: &Selector = rule.selectors.first().unwrap();
     match &selector.selector_type {
         SelectorType::SimpleSelector(s) => {
             assert_eq!(s.tag_name.clone().unwrap(), String::from("span"));
         }
     }
-    let height_declaration: &Declaration = rule.declarations.get(0).unwrap();
+    let height_declaration: &Declaration = rule.declarations.first().unwrap();
     assert_eq!(height_declaration.name, String::from("height"));
     match height_declaration.value {
         Value::Length(amount, unit) => {
@@ -48,6 +52,7 @@ fn it_parses_multiple_attributes() {
             };
             assert_eq!(unit_result, "px")
         }
+        _ => panic!("Wrong value type"),
     }
 
     let width_declaration: &Declaration = rule.declarations.get(1).unwrap();
@@ -60,8 +65,433 @@ fn it_parses_multiple_attributes() {
             };
             assert_eq!(unit_result, "px")
         }
+        _ => panic!("Wrong value type"),
     }
 }
+
+#[test]
+fn it_matches_type_selectors_case_insensitively() {
+    let style_sheet = parse_css("DIV { height: 1px; }".to_string());
+    let selector: &Selector = style_sheet.rules[0].selectors.first().unwrap();
+    let document = html_parser::parse("<Div ID=a></DIV><p>".to_string(), &mut vec![]);
+    let body = &document.children[0].children[1];
+    assert_eq!(body.children.len(), 2);
+    match &body.children[0].node_type {
+        NodeType::Element(element) => {
+            assert_eq!(element.tag_name, "div");
+            assert_eq!(element.id(), Some(&String::from("a")));
+            assert!(selector.matches(element));
+        }
+        _ => panic!("Wrong node type"),
+    }
+}
+
+#[test]
+fn it_parses_mixed_case_type_selectors() {
+    let style_sheet = parse_css("sEcTiOn { height: 1px; }".to_string());
+    let selector: &Selector = style_sheet.rules[0].selectors.first().unwrap();
+    match &selector.selector_type {
+        SelectorType::SimpleSelector(s) => {
+            assert!(s.tag_name.as_ref().unwrap().eq_ignore_ascii_case("section"));
+        }
+    };
+    let document = html_parser::parse(
+        "<SECTION></SECTION><Section></section><sections></sections>".to_string(),
+        &mut vec![],
+    );
+    let body = &document.children[0].children[1];
+    let matched: Vec<bool> = body
+        .children
+        .iter()
+        .map(|child| match &child.node_type {
+            NodeType::Element(element) => selector.matches(element),
+            _ => panic!("Wrong node type"),
+        })
+        .collect();
+    assert_eq!(matched, [true, true, false]);
+}
+
+#[test]
+fn it_matches_foreign_type_selectors_case_sensitively() {
+    let style_sheet =
+        parse_css("foreignObject { height: 1px; } foreignobject { height: 1px; }".to_string());
+    let document = html_parser::parse("<svg><foreignObject>".to_string(), &mut vec![]);
+    let svg = &document.children[0].children[1].children[0];
+    match &svg.children[0].node_type {
+        NodeType::Element(element) => {
+            assert_eq!(element.namespace, Namespace::Svg);
+            let matching: Vec<bool> = style_sheet
+                .rules
+                .iter()
+                .map(|rule| rule.selectors[0].matches(element))
+                .collect();
+            assert_eq!(matching, vec![true, false]);
+        }
+        _ => panic!("Wrong node type"),
+    }
+}
+
+#[test]
+fn it_parses_compound_and_universal_selectors() {
+    let style_sheet = parse_css("p#intro.note.wide, * { width: 1PX }".to_string());
+    let selectors = &style_sheet.rules[0].selectors;
+    let specificities: Vec<Specificity> = selectors.iter().map(|s| s.specificity()).collect();
+    assert_eq!(specificities, vec![(0, 0, 0), (1, 2, 1)]);
+    match &selectors[1].selector_type {
+        SelectorType::SimpleSelector(s) => {
+            assert_eq!(s.tag_name, Some(String::from("p")));
+            assert_eq!(s.id, Some(String::from("intro")));
+            assert_eq!(s.class, vec![String::from("note"), String::from("wide")]);
+        }
+    }
+    assert_eq!(style_sheet.rules[0].declarations.len(), 1);
+}
+
+#[test]
+fn it_reads_escapes_and_skips_comments_between_tokens() {
+    let style_sheet =
+        parse_css(r"/* a */ .\31 st/**/{ /* b */ height /* c */: 2px /* d */ }".to_string());
+    match &style_sheet.rules[0].selectors[0].selector_type {
+        SelectorType::SimpleSelector(s) => assert_eq!(s.class, vec![String::from("1st")]),
+    }
+    assert_eq!(style_sheet.rules[0].declarations[0].name, "height");
+}
+
+#[test]
+fn it_drops_rules_with_invalid_selectors() {
+    let mut warnings = vec![];
+    let style_sheet = parse(
+        "a > b { width: 1px } i { width: 2px }".to_string(),
+        &mut warnings,
+    );
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].code, "invalid-selector");
+    assert_eq!(warnings[0].severity, Severity::Warning);
+    assert_eq!(warnings[0].span.start.offset(), 2);
+    assert_eq!(style_sheet.rules.len(), 1);
+}
+
+#[test]
+fn it_skips_comments_wherever_whitespace_is_allowed() {
+    let style_sheet = parse_css(
+        "/**/a/* x */,/* y */b/**/{/* z */width/**/:/**/1px/**/;/* w */}/* v */".to_string(),
+    );
+    assert_eq!(style_sheet.rules[0].selectors.len(), 2);
+    assert_eq!(style_sheet.rules[0].declarations[0].name, "width");
+    assert!(style_sheet.comments.is_empty());
+}
+
+#[test]
+fn it_keeps_comments_as_trivia() {
+    let mut warnings = vec![];
+    let style_sheet = parse_with_comments(
+        "/* header */\na { width: 1px; } /* open".to_string(),
+        &mut warnings,
+    );
+    let comments: Vec<(&str, usize, usize)> = style_sheet
+        .comments
+        .iter()
+        .map(|c| (c.text.as_str(), c.span.start.offset(), c.span.end.offset()))
+        .collect();
+    assert_eq!(comments, vec![(" header ", 0, 12), (" open", 31, 38)]);
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].code, "eof-in-comment");
+    assert_eq!(style_sheet.rules[0].declarations.len(), 1);
+}
+
+#[test]
+fn it_ends_blocks_at_an_unterminated_comment() {
+    let (style_sheet, warnings) = parse_with_warnings("a { width: 1px /* } b { }");
+    let codes: Vec<&str> = warnings.iter().map(|w| w.code).collect();
+    assert_eq!(codes, vec!["eof-in-comment", "eof-in-rule"]);
+    assert_eq!(style_sheet.rules.len(), 1);
+    assert_eq!(style_sheet.rules[0].declarations.len(), 1);
+}
+
+#[test]
+fn it_parses_keywords_strings_urls_and_component_lists() {
+    let style_sheet = parse_css(
+        "p { display: block; font-family: \"Helvetica Neue\", sans-serif; \
+         background: url(a.png) no-repeat; margin: 0 auto; \
+         background-image: url( \"b c.png\" ), URL('d\\\"e'); \
+         line-height: 1.5; width: 50%; padding: 1px 2% 3 4PX; border: 1px solid #00f }"
+            .to_string(),
+    );
+    let values: Vec<String> = style_sheet.rules[0]
+        .declarations
+        .iter()
+        .map(|declaration| format!("{}: {}", declaration.name, declaration.value))
+        .collect();
+    assert_eq!(
+        values,
+        vec![
+            "display: block",
+            "font-family: \"Helvetica Neue\", sans-serif",
+            "background: url(\"a.png\") no-repeat",
+            "margin: 0 auto",
+            "background-image: url(\"b c.png\"), url(\"d\\\"e\")",
+            "line-height: 1.5",
+            "width: 50%",
+            "padding: 1px 2% 3 4px",
+            "border: 1px solid rgb(0, 0, 255)",
+        ]
+    );
+    let declarations = &style_sheet.rules[0].declarations;
+    match declarations[1].value {
+        Value::CommaSeparated(ref families) => match families[0] {
+            Value::String(ref family) => assert_eq!(family, "Helvetica Neue"),
+            _ => panic!("Wrong value type"),
+        },
+        _ => panic!("Wrong value type"),
+    }
+    match declarations[3].value {
+        Value::SpaceSeparated(ref components) => {
+            assert!(matches!(components[0], Value::Number(n) if n == 0.0));
+            assert!(matches!(components[1], Value::Keyword(ref k) if k == "auto"));
+        }
+        _ => panic!("Wrong value type"),
+    }
+}
+
+#[test]
+fn it_rejects_invalid_component_values() {
+    let (style_sheet, warnings) = parse_with_warnings(
+        "p { width: 1em; font-family: a,; background: url(\"a\" x) red; width: calc(1px) 2px; \
+         margin: 0 ! important; margin: 0.1.2; padding: 0 !importan; height: 2px }",
+    );
+    assert_eq!(warnings.len(), 6, "{:?}", warnings);
+    assert!(warnings.iter().all(|w| w.code == "invalid-declaration"));
+    let names: Vec<&str> = style_sheet.rules[0]
+        .declarations
+        .iter()
+        .map(|d| d.name.as_str())
+        .collect();
+    assert_eq!(names, vec!["margin", "height"]);
+}
+
+#[test]
+fn it_keeps_important_declarations() {
+    let style_sheet = parse_css(
+        "p { color: red !important; width: 1px ! IMPORTANT ; display: block!important; \
+         height: 2px }"
+            .to_string(),
+    );
+    let declarations: Vec<(String, bool)> = style_sheet.rules[0]
+        .declarations
+        .iter()
+        .map(|d| (format!("{}: {}", d.name, d.value), d.important))
+        .collect();
+    assert_eq!(
+        declarations,
+        vec![
+            (String::from("color: rgb(255, 0, 0)"), true),
+            (String::from("width: 1px"), true),
+            (String::from("display: block"), true),
+            (String::from("height: 2px"), false),
+        ]
+    );
+}
+
+#[test]
+fn it_parses_colors() {
+    let style_sheet = parse_css(
+        "a { color: #f00; color: #0F08; color: #12345678; color: #abcdef; \
+         color: Red; color: transparent; color: rebeccapurple; \
+         color: rgb(255, 0, 0); color: rgba(100%, 50%, 0%, 0.5); color: rgb(0 128 255 / 25%); \
+         color: rgb(none 1 2); color: RGBA(300 -1 0); \
+         color: hsl(120, 100%, 50%); color: hsla(0.5turn 100 25 / .2); color: hsl(90deg 0% 100%) }"
+            .to_string(),
+    );
+    let colors: Vec<String> = style_sheet.rules[0]
+        .declarations
+        .iter()
+        .map(|declaration| match declaration.value {
+            Value::Color(color) => color.to_string(),
+            _ => panic!("Wrong value type"),
+        })
+        .collect();
+    assert_eq!(
+        colors,
+        vec![
+            "rgb(255, 0, 0)",
+            "rgba(0, 255, 0, 0.533)",
+            "rgba(18, 52, 86, 0.47)",
+            "rgb(171, 205, 239)",
+            "rgb(255, 0, 0)",
+            "rgba(0, 0, 0, 0)",
+            "rgb(102, 51, 153)",
+            "rgb(255, 0, 0)",
+            "rgba(255, 128, 0, 0.5)",
+            "rgba(0, 128, 255, 0.25)",
+            "rgb(0, 1, 2)",
+            "rgb(255, 0, 0)",
+            "rgb(0, 255, 0)",
+            "rgba(0, 128, 128, 0.2)",
+            "rgb(255, 255, 255)",
+        ]
+    );
+}
+
+#[test]
+fn it_rejects_invalid_colors() {
+    let (style_sheet, warnings) = parse_with_warnings(
+        "a { color: #ff; color: #ggg; color: blurple; color: rgb(1, 2 3); \
+         color: rgb(1, 2%, 3); color: hsl(1, 2, 3); color: rgb(none, 0, 0); \
+         color: rgb(1 2); color: lab(1 2 3); color: rgb(calc(1) 2 3); color: 1px; \
+         background-color: currentColor }",
+    );
+    assert_eq!(warnings.len(), 11, "{:?}", warnings);
+    assert!(warnings.iter().all(|w| w.code == "invalid-declaration"));
+    assert_eq!(
+        (
+            warnings[0].span.start.offset(),
+            warnings[0].span.end.offset()
+        ),
+        (11, 14)
+    );
+    assert_eq!(
+        (
+            warnings[3].span.start.offset(),
+            warnings[3].span.end.offset()
+        ),
+        (52, 63)
+    );
+    let declarations = &style_sheet.rules[0].declarations;
+    assert_eq!(declarations.len(), 1);
+    assert!(matches!(declarations[0].value, Value::CurrentColor));
+}
+
+#[test]
+fn it_parses_css_wide_keywords_and_border_color_lists() {
+    let (style_sheet, warnings) = parse_with_warnings(
+        "a { color: inherit; background-color: INITIAL; outline-color: unset; \
+         border-color: red blue; border-color: red #0f0 currentColor blue !important; \
+         border-color: red red red red red; color: red blue; border-color: red inherit }",
+    );
+    let values: Vec<String> = style_sheet.rules[0]
+        .declarations
+        .iter()
+        .map(|declaration| format!("{}: {}", declaration.name, declaration.value))
+        .collect();
+    assert_eq!(
+        values,
+        vec![
+            "color: inherit",
+            "background-color: INITIAL",
+            "outline-color: unset",
+            "border-color: rgb(255, 0, 0) rgb(0, 0, 255)",
+            "border-color: rgb(255, 0, 0) rgb(0, 255, 0) currentcolor rgb(0, 0, 255)",
+        ]
+    );
+    assert_eq!(warnings.len(), 3, "{:?}", warnings);
+}
+
+#[test]
+fn it_drops_bad_declarations_at_the_right_nesting_level() {
+    let (style_sheet, warnings) = parse_with_warnings(
+        "a { width 1px; height: 1.2.3px; width: f(;}); ; b { c: 1px; } height: 2px }",
+    );
+    let codes: Vec<&str> = warnings.iter().map(|w| w.code).collect();
+    assert_eq!(codes, vec!["invalid-declaration"; 4]);
+    assert_eq!(warnings[0].span.start.offset(), 10);
+    assert_eq!(warnings[1].span.start.offset(), 26);
+    assert_eq!(style_sheet.rules.len(), 1);
+    assert!(style_sheet.rules[0].declarations.is_empty());
+
+    let (style_sheet, warnings) = parse_with_warnings("a { x: [}]; width: 1px } i { height: 2px }");
+    assert_eq!(warnings.len(), 1);
+    let names: Vec<&str> = style_sheet
+        .rules
+        .iter()
+        .flat_map(|rule| rule.declarations.iter().map(|d| d.name.as_str()))
+        .collect();
+    assert_eq!(names, vec!["width", "height"]);
+}
+
+#[test]
+fn it_drops_bad_rules_up_to_their_matching_brace() {
+    let (style_sheet, warnings) =
+        parse_with_warnings("a! { b { } width: 1px } @media print { i { } } @import 'x'; i { }");
+    let codes: Vec<&str> = warnings.iter().map(|w| w.code).collect();
+    assert_eq!(
+        codes,
+        vec![
+            "invalid-selector",
+            "unsupported-at-rule",
+            "unsupported-at-rule"
+        ]
+    );
+    assert_eq!(style_sheet.rules.len(), 1);
+}
+
+#[test]
+fn it_recovers_from_every_truncation() {
+    let source = "a.b, #c { width: 10px; height: f([{\"x\"}]) } @media x { i { } } p { }";
+    for (end, _) in source.char_indices() {
+        let (style_sheet, warnings) = parse_with_warnings(&source[..end]);
+        assert!(style_sheet.rules.len() <= 2);
+        assert!(end == 0 || !warnings.is_empty() || style_sheet.rules.len() == 2);
+    }
+    let (style_sheet, warnings) = parse_with_warnings("a { width: 1px");
+    assert_eq!(warnings[0].code, "eof-in-rule");
+    assert_eq!(style_sheet.rules[0].declarations.len(), 1);
+    let (style_sheet, warnings) = parse_with_warnings("a { width: 1px } p");
+    assert_eq!(warnings[0].code, "eof-in-rule");
+    assert_eq!(style_sheet.rules.len(), 1);
+}
+
+#[test]
+fn it_matches_namespace_prefixes() {
+    let (style_sheet, warnings) = parse_with_warnings(
+        "@namespace s url(http://www.w3.org/2000/svg); \
+         @namespace h \"http://www.w3.org/1999/xhtml\"; \
+         s|rect {} *|rect {} |rect {} h|rect {} rect {} s|* {} x|rect {} \
+         @namespace x \"x\";",
+    );
+    let codes: Vec<&str> = warnings.iter().map(|w| w.code).collect();
+    assert_eq!(codes, vec!["invalid-selector", "invalid-at-rule"]);
+    let document = html_parser::parse("<svg><rect/></svg><rect>".to_string(), &mut vec![]);
+    let body = &document.children[0].children[1];
+    let matching = |node: &Node| match &node.node_type {
+        NodeType::Element(element) => style_sheet
+            .rules
+            .iter()
+            .map(|rule| rule.selectors[0].matches(element))
+            .collect::<Vec<bool>>(),
+        _ => panic!("Wrong node type"),
+    };
+    assert_eq!(
+        matching(&body.children[0].children[0]),
+        vec![true, true, false, false, true, true]
+    );
+    assert_eq!(
+        matching(&body.children[1]),
+        vec![false, true, false, true, true, false]
+    );
+}
+
+#[test]
+fn it_applies_the_default_namespace() {
+    let style_sheet =
+        parse_css("@namespace url(http://www.w3.org/2000/svg); rect {} .a {} *|*.a {}".to_string());
+    let document = html_parser::parse("<p class=a><svg class=a><rect/>".to_string(), &mut vec![]);
+    let paragraph = &document.children[0].children[1].children[0];
+    let matching = |node: &Node| match &node.node_type {
+        NodeType::Element(element) => style_sheet
+            .rules
+            .iter()
+            .map(|rule| rule.selectors[0].matches(element))
+            .collect::<Vec<bool>>(),
+        _ => panic!("Wrong node type"),
+    };
+    assert_eq!(matching(paragraph), vec![false, false, true]);
+    assert_eq!(matching(&paragraph.children[0]), vec![false, true, true]);
+    assert_eq!(
+        matching(&paragraph.children[0].children[0]),
+        vec![true, false, false]
+    );
+}
+
 #[test]
 fn it_parse_example_stylesheets() {
     let p = Path::new("examples").to_path_buf();
@@ -103,7 +533,13 @@ fn get_files_in_dir(dir: PathBuf) -> Vec<PathBuf> {
             }
         }
     }
-    return paths;
+    paths
+}
+
+fn parse_with_warnings(style: &str) -> (StyleSheet, Vec<Diagnostic>) {
+    let mut warnings = vec![];
+    let style_sheet = parse(String::from(style), &mut warnings);
+    (style_sheet, warnings)
 }
 
 fn parse_css(style: String) -> StyleSheet {
@@ -113,5 +549,5 @@ fn parse_css(style: String) -> StyleSheet {
         println!("Warn: {}", warn)
     }
     assert_eq!(warnings.len(), 0, "No warnings expected");
-    return style_sheet.clone();
+    style_sheet
 }
@@ -0,0 +1,484 @@
+use cssom::Comment;
+use parser::diagnostic::{self, Diagnostic};
+use parser::parser::{self, ParserPosition, Span};
+
+/// A token of the CSS Syntax Level 3 tokenizer. Comments produce no token.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Ident(String),
+    Function(String),
+    AtKeyword(String),
+    Hash(String, HashType),
+    String(String),
+    BadString,
+    Url(String),
+    BadUrl,
+    Delim(char),
+    Number(f32),
+    Percentage(f32),
+    Dimension(f32, String),
+    Whitespace,
+    Cdo,
+    Cdc,
+    Colon,
+    Semicolon,
+    Comma,
+    OpenSquare,
+    CloseSquare,
+    OpenParen,
+    CloseParen,
+    OpenCurly,
+    CloseCurly,
+    Eof,
+}
+
+/// Whether a hash token's name could be an identifier, which is what ID
+/// selectors need.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HashType {
+    Id,
+    Unrestricted,
+}
+
+/// Splits a stylesheet into tokens, ending with a single `Eof`. Parse errors
+/// are pushed onto `warnings`, since the tokenizer always recovers.
+pub fn tokenize(css: String, warnings: &mut Vec<Diagnostic>) -> Vec<(Token, Span)> {
+    tokenize_with_comments(css, warnings).0
+}
+
+/// Like `tokenize`, but also returns the comments, whose text leaves out the
+/// delimiters. An unterminated comment runs to the end.
+pub fn tokenize_with_comments(
+    css: String,
+    warnings: &mut Vec<Diagnostic>,
+) -> (Vec<(Token, Span)>, Vec<Comment>) {
+    let mut tokenizer = Tokenizer {
+        parser: parser::create(css),
+        warnings,
+        comments: vec![],
+    };
+    let mut tokens = vec![];
+    loop {
+        let (token, span) = tokenizer.consume_token();
+        let eof = token == Token::Eof;
+        tokens.push((token, span));
+        if eof {
+            return (tokens, tokenizer.comments);
+        }
+    }
+}
+
+struct Tokenizer<'a> {
+    parser: parser::Parser,
+    warnings: &'a mut Vec<Diagnostic>,
+    comments: Vec<Comment>,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn consume_token(&mut self) -> (Token, Span) {
+        self.consume_comments();
+        let start = self.parser.position();
+        let token = match self.peek(0) {
+            None => Token::Eof,
+            Some(c) if is_whitespace(c) => {
+                self.consume_whitespace();
+                Token::Whitespace
+            }
+            Some(c) if c == '"' || c == '\'' => {
+                self.consume();
+                self.consume_string(c, start)
+            }
+            Some('#') => {
+                self.consume();
+                if self.peek(0).is_some_and(is_ident_char) || self.next_is_valid_escape(0) {
+                    let hash_type = if self.next_starts_ident(0) {
+                        HashType::Id
+                    } else {
+                        HashType::Unrestricted
+                    };
+                    Token::Hash(self.consume_ident_sequence(), hash_type)
+                } else {
+                    Token::Delim('#')
+                }
+            }
+            Some('+') | Some('.') if self.next_starts_number(0) => self.consume_numeric(),
+            Some('-') => {
+                if self.next_starts_number(0) {
+                    self.consume_numeric()
+                } else if self.parser.starts_with("-->") {
+                    self.skip(3);
+                    Token::Cdc
+                } else if self.next_starts_ident(0) {
+                    self.consume_ident_like(start)
+                } else {
+                    self.consume();
+                    Token::Delim('-')
+                }
+            }
+            Some('<') if self.parser.starts_with("<!--") => {
+                self.skip(4);
+                Token::Cdo
+            }
+            Some('@') => {
+                self.consume();
+                if self.next_starts_ident(0) {
+                    Token::AtKeyword(self.consume_ident_sequence())
+                } else {
+                    Token::Delim('@')
+                }
+            }
+            Some('\\') => {
+                if self.next_is_valid_escape(0) {
+                    self.consume_ident_like(start)
+                } else {
+                    self.consume();
+                    self.warn("invalid-escape", start, "Backslash before a line break");
+                    Token::Delim('\\')
+                }
+            }
+            Some(c) if c.is_ascii_digit() => self.consume_numeric(),
+            Some(c) if is_ident_start(c) => self.consume_ident_like(start),
+            Some(c) => {
+                self.consume();
+                match c {
+                    '(' => Token::OpenParen,
+                    ')' => Token::CloseParen,
+                    '[' => Token::OpenSquare,
+                    ']' => Token::CloseSquare,
+                    '{' => Token::OpenCurly,
+                    '}' => Token::CloseCurly,
+                    ',' => Token::Comma,
+                    ':' => Token::Colon,
+                    ';' => Token::Semicolon,
+                    c => Token::Delim(c),
+                }
+            }
+        };
+        let span = Span {
+            start,
+            end: self.parser.position(),
+        };
+        (token, span)
+    }
+
+    fn consume_comments(&mut self) {
+        while self.parser.starts_with("/*") {
+            let start = self.parser.position();
+            self.skip(2);
+            let mut text = String::new();
+            loop {
+                if self.parser.starts_with("*/") {
+                    self.skip(2);
+                    break;
+                }
+                match self.consume() {
+                    Some(c) => text.push(c),
+                    None => {
+                        self.warn("eof-in-comment", start, "Unterminated comment");
+                        break;
+                    }
+                }
+            }
+            let span = Span {
+                start,
+                end: self.parser.position(),
+            };
+            self.comments.push(Comment { text, span });
+        }
+    }
+
+    // Called after the opening quote.
+    fn consume_string(&mut self, ending: char, start: ParserPosition) -> Token {
+        let mut value = String::new();
+        loop {
+            match self.peek(0) {
+                None => {
+                    self.warn("eof-in-string", start, "Unterminated string");
+                    return Token::String(value);
+                }
+                Some(c) if c == ending => {
+                    self.consume();
+                    return Token::String(value);
+                }
+                Some(c) if is_newline(c) => {
+                    self.warn("newline-in-string", start, "Line break in string");
+                    return Token::BadString;
+                }
+                Some('\\') => match self.peek(1) {
+                    None => {
+                        self.consume();
+                    }
+                    Some(c) if is_newline(c) => {
+                        self.consume();
+                        self.consume_newline();
+                    }
+                    Some(_) => {
+                        self.consume();
+                        value.push(self.consume_escape());
+                    }
+                },
+                Some(_) => value.push(self.consume_code_point()),
+            }
+        }
+    }
+
+    fn consume_numeric(&mut self) -> Token {
+        let number = self.consume_number();
+        if self.next_starts_ident(0) {
+            Token::Dimension(number, self.consume_ident_sequence())
+        } else if self.peek(0) == Some('%') {
+            self.consume();
+            Token::Percentage(number)
+        } else {
+            Token::Number(number)
+        }
+    }
+
+    fn consume_number(&mut self) -> f32 {
+        let mut text = String::new();
+        if let Some(sign) = self.peek(0).filter(|&c| c == '+' || c == '-') {
+            self.consume();
+            text.push(sign);
+        }
+        self.consume_digits(&mut text);
+        if self.peek(0) == Some('.') && self.peek(1).is_some_and(|c| c.is_ascii_digit()) {
+            self.consume();
+            text.push('.');
+            self.consume_digits(&mut text);
+        }
+        let exponent_digit = match self.peek(1) {
+            Some('+') | Some('-') => 2,
+            _ => 1,
+        };
+        if matches!(self.peek(0), Some('e') | Some('E'))
+            && self
+                .peek(exponent_digit)
+                .is_some_and(|c| c.is_ascii_digit())
+        {
+            for _ in 0..exponent_digit {
+                text.push(self.consume_code_point());
+            }
+            self.consume_digits(&mut text);
+        }
+        // The text always has digits, so it is a valid float.
+        text.parse().unwrap_or_default()
+    }
+
+    fn consume_digits(&mut self, text: &mut String) {
+        while let Some(c) = self.peek(0).filter(|c| c.is_ascii_digit()) {
+            self.consume();
+            text.push(c);
+        }
+    }
+
+    fn consume_ident_like(&mut self, start: ParserPosition) -> Token {
+        let name = self.consume_ident_sequence();
+        if self.peek(0) != Some('(') {
+            return Token::Ident(name);
+        }
+        self.consume();
+        if !name.eq_ignore_ascii_case("url") {
+            return Token::Function(name);
+        }
+        while self.peek(0).is_some_and(is_whitespace) && self.peek(1).is_some_and(is_whitespace) {
+            self.consume();
+        }
+        let quoted = |c: Option<char>| c == Some('"') || c == Some('\'');
+        if quoted(self.peek(0)) || (self.peek(0).is_some_and(is_whitespace) && quoted(self.peek(1)))
+        {
+            return Token::Function(name);
+        }
+        self.consume_url(start)
+    }
+
+    // Called after `url(`, for URLs which are not quoted.
+    fn consume_url(&mut self, start: ParserPosition) -> Token {
+        let mut value = String::new();
+        self.consume_whitespace();
+        loop {
+            match self.peek(0) {
+                Some(')') => {
+                    self.consume();
+                    return Token::Url(value);
+                }
+                None => {
+                    self.warn("eof-in-url", start, "Unterminated url");
+                    return Token::Url(value);
+                }
+                Some(c) if is_whitespace(c) => {
+                    self.consume_whitespace();
+                    match self.peek(0) {
+                        Some(')') | None => continue,
+                        Some(_) => return self.consume_bad_url(start),
+                    }
+                }
+                Some(c) if c == '"' || c == '\'' || c == '(' || is_non_printable(c) => {
+                    return self.consume_bad_url(start);
+                }
+                Some('\\') => {
+                    if !self.next_is_valid_escape(0) {
+                        return self.consume_bad_url(start);
+                    }
+                    self.consume();
+                    value.push(self.consume_escape());
+                }
+                Some(_) => value.push(self.consume_code_point()),
+            }
+        }
+    }
+
+    fn consume_bad_url(&mut self, start: ParserPosition) -> Token {
+        self.warn("bad-url", start, "Unexpected character in url");
+        loop {
+            if self.next_is_valid_escape(0) {
+                self.consume();
+                self.consume_escape();
+                continue;
+            }
+            match self.consume() {
+                Some(')') | None => return Token::BadUrl,
+                Some(_) => {}
+            }
+        }
+    }
+
+    fn consume_ident_sequence(&mut self) -> String {
+        let mut name = String::new();
+        loop {
+            match self.peek(0) {
+                Some(c) if is_ident_char(c) => name.push(self.consume_code_point()),
+                Some('\\') if self.next_is_valid_escape(0) => {
+                    self.consume();
+                    name.push(self.consume_escape());
+                }
+                _ => return name,
+            }
+        }
+    }
+
+    // Called after the backslash.
+    fn consume_escape(&mut self) -> char {
+        let start = self.parser.position();
+        let c = match self.consume() {
+            Some(c) => c,
+            None => {
+                self.warn("eof-in-escape", start, "Unterminated escape");
+                return '\u{FFFD}';
+            }
+        };
+        let mut hex = match c.to_digit(16) {
+            Some(digit) => digit,
+            None => return if c == '\0' { '\u{FFFD}' } else { c },
+        };
+        for _ in 0..5 {
+            match self.peek(0).and_then(|c| c.to_digit(16)) {
+                Some(digit) => {
+                    self.consume();
+                    hex = hex * 16 + digit;
+                }
+                None => break,
+            }
+        }
+        if self.peek(0).is_some_and(is_whitespace) {
+            self.consume_newline();
+        }
+        match char::from_u32(hex) {
+            Some(c) if c != '\0' => c,
+            _ => '\u{FFFD}',
+        }
+    }
+
+    fn next_is_valid_escape(&self, offset: usize) -> bool {
+        self.peek(offset) == Some('\\') && self.peek(offset + 1).is_some_and(|c| !is_newline(c))
+    }
+
+    fn next_starts_ident(&self, offset: usize) -> bool {
+        match self.peek(offset) {
+            Some('-') => {
+                self.peek(offset + 1)
+                    .is_some_and(|c| is_ident_start(c) || c == '-')
+                    || self.next_is_valid_escape(offset + 1)
+            }
+            Some('\\') => self.next_is_valid_escape(offset),
+            Some(c) => is_ident_start(c),
+            None => false,
+        }
+    }
+
+    fn next_starts_number(&self, offset: usize) -> bool {
+        let is_digit = |c: Option<char>| c.is_some_and(|c| c.is_ascii_digit());
+        match self.peek(offset) {
+            Some('+') | Some('-') => {
+                is_digit(self.peek(offset + 1))
+                    || (self.peek(offset + 1) == Some('.') && is_digit(self.peek(offset + 2)))
+            }
+            Some('.') => is_digit(self.peek(offset + 1)),
+            c => is_digit(c),
+        }
+    }
+
+    fn peek(&self, offset: usize) -> Option<char> {
+        self.parser.remaining().chars().nth(offset)
+    }
+
+    fn consume(&mut self) -> Option<char> {
+        if self.parser.eof() {
+            None
+        } else {
+            Some(self.parser.consume_char())
+        }
+    }
+
+    // NULL is read as U+FFFD, as the spec's input preprocessing does.
+    fn consume_code_point(&mut self) -> char {
+        match self.consume() {
+            Some('\0') | None => '\u{FFFD}',
+            Some(c) => c,
+        }
+    }
+
+    fn skip(&mut self, length: usize) {
+        self.parser.consume_next_n_chars(length);
+    }
+
+    fn consume_whitespace(&mut self) {
+        while self.peek(0).is_some_and(is_whitespace) {
+            self.consume();
+        }
+    }
+
+    // A CR LF pair counts as one newline.
+    fn consume_newline(&mut self) {
+        if self.consume() == Some('\r') && self.peek(0) == Some('\n') {
+            self.consume();
+        }
+    }
+
+    fn warn(&mut self, code: &'static str, start: ParserPosition, message: &str) {
+        let span = Span {
+            start,
+            end: self.parser.position(),
+        };
+        self.warnings.push(diagnostic::warning(code, span, message));
+    }
+}
+
+fn is_newline(c: char) -> bool {
+    matches!(c, '\n' | '\r' | '\x0C')
+}
+
+fn is_whitespace(c: char) -> bool {
+    is_newline(c) || c == ' ' || c == '\t'
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || !c.is_ascii() || c == '\0'
+}
+
+fn is_ident_char(c: char) -> bool {
+    is_ident_start(c) || c.is_ascii_digit() || c == '-'
+}
+
+fn is_non_printable(c: char) -> bool {
+    matches!(c, '\x01'..='\x08' | '\x0B' | '\x0E'..='\x1F' | '\x7F')
+}
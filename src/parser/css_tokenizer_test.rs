@@ -0,0 +1,168 @@
+use parser::css_tokenizer::*;
+use parser::diagnostic::Diagnostic;
+
+#[test]
+fn it_tokenizes_a_rule() {
+    assert_eq!(
+        tokens("p.a{width:10px}"),
+        vec![
+            Token::Ident(String::from("p")),
+            Token::Delim('.'),
+            Token::Ident(String::from("a")),
+            Token::OpenCurly,
+            Token::Ident(String::from("width")),
+            Token::Colon,
+            Token::Dimension(10.0, String::from("px")),
+            Token::CloseCurly,
+            Token::Eof,
+        ]
+    );
+}
+
+#[test]
+fn it_tokenizes_numbers() {
+    assert_eq!(
+        tokens("1 +.5 -2e3 50% 1.2.3px 4e"),
+        vec![
+            Token::Number(1.0),
+            Token::Whitespace,
+            Token::Number(0.5),
+            Token::Whitespace,
+            Token::Number(-2000.0),
+            Token::Whitespace,
+            Token::Percentage(50.0),
+            Token::Whitespace,
+            Token::Number(1.2),
+            Token::Dimension(0.3, String::from("px")),
+            Token::Whitespace,
+            Token::Dimension(4.0, String::from("e")),
+            Token::Eof,
+        ]
+    );
+}
+
+#[test]
+fn it_tokenizes_hashes_at_keywords_and_functions() {
+    assert_eq!(
+        tokens("#id #1a @media rgb( -x --y - "),
+        vec![
+            Token::Hash(String::from("id"), HashType::Id),
+            Token::Whitespace,
+            Token::Hash(String::from("1a"), HashType::Unrestricted),
+            Token::Whitespace,
+            Token::AtKeyword(String::from("media")),
+            Token::Whitespace,
+            Token::Function(String::from("rgb")),
+            Token::Whitespace,
+            Token::Ident(String::from("-x")),
+            Token::Whitespace,
+            Token::Ident(String::from("--y")),
+            Token::Whitespace,
+            Token::Delim('-'),
+            Token::Whitespace,
+            Token::Eof,
+        ]
+    );
+}
+
+#[test]
+fn it_tokenizes_strings_and_escapes() {
+    assert_eq!(
+        tokens(
+            r#""a\"b" 'c\
+d' \31 0 a\-b "e""#
+        ),
+        vec![
+            Token::String(String::from("a\"b")),
+            Token::Whitespace,
+            Token::String(String::from("cd")),
+            Token::Whitespace,
+            Token::Ident(String::from("10")),
+            Token::Whitespace,
+            Token::Ident(String::from("a-b")),
+            Token::Whitespace,
+            Token::String(String::from("e")),
+            Token::Eof,
+        ]
+    );
+    let (tokens, warnings) = tokenize_with_warnings("'a\nb'");
+    assert_eq!(tokens[0], Token::BadString);
+    assert_eq!(warnings[0].code, "newline-in-string");
+}
+
+#[test]
+fn it_tokenizes_urls() {
+    let (tokens, warnings) = tokenize_with_warnings("url( a.png ) URL(\"b.png\") url(c d)");
+    assert_eq!(
+        tokens,
+        vec![
+            Token::Url(String::from("a.png")),
+            Token::Whitespace,
+            Token::Function(String::from("URL")),
+            Token::String(String::from("b.png")),
+            Token::CloseParen,
+            Token::Whitespace,
+            Token::BadUrl,
+            Token::Eof,
+        ]
+    );
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].code, "bad-url");
+}
+
+#[test]
+fn it_skips_comments_and_keeps_cdo_and_cdc() {
+    assert_eq!(
+        tokens("<!--/* x */a/**/b -->"),
+        vec![
+            Token::Cdo,
+            Token::Ident(String::from("a")),
+            Token::Ident(String::from("b")),
+            Token::Whitespace,
+            Token::Cdc,
+            Token::Eof,
+        ]
+    );
+}
+
+#[test]
+fn it_gives_tokens_spans() {
+    let mut warnings = vec![];
+    let tokens = tokenize(String::from("a {\n  b: 1em }"), &mut warnings);
+    let spans: Vec<(usize, usize)> = tokens
+        .iter()
+        .map(|(_, span)| (span.start.offset(), span.end.offset()))
+        .collect();
+    assert_eq!(
+        spans,
+        vec![
+            (0, 1),
+            (1, 2),
+            (2, 3),
+            (3, 6),
+            (6, 7),
+            (7, 8),
+            (8, 9),
+            (9, 12),
+            (12, 13),
+            (13, 14),
+            (14, 14),
+        ]
+    );
+    assert_eq!(tokens[4].1.start.line(), 2);
+}
+
+fn tokens(css: &str) -> Vec<Token> {
+    let (tokens, warnings) = tokenize_with_warnings(css);
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    tokens
+}
+
+fn tokenize_with_warnings(css: &str) -> (Vec<Token>, Vec<Diagnostic>) {
+    let mut warnings = vec![];
+    let tokens = tokenize(String::from(css), &mut warnings)
+        .into_iter()
+        .map(|(token, _)| token)
+        .collect();
+    (tokens, warnings)
+}
@@ -0,0 +1,117 @@
+use parser::Span;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A problem found while parsing. HTML codes use the WHATWG parse error
+/// names where the spec defines one; CSS codes name the kind of construct
+/// which was dropped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub span: Span,
+    pub message: String,
+}
+
+pub fn error(code: &'static str, span: Span, message: &str) -> Diagnostic {
+    Diagnostic {
+        severity: Severity::Error,
+        code,
+        span,
+        message: String::from(message),
+    }
+}
+
+pub fn warning(code: &'static str, span: Span, message: &str) -> Diagnostic {
+    Diagnostic {
+        severity: Severity::Warning,
+        code,
+        span,
+        message: String::from(message),
+    }
+}
+
+/// Turns an error code such as `eof-in-tag` into a sentence, for the many
+/// tokenizer errors whose name says everything there is to say.
+pub fn describe_code(code: &str) -> String {
+    let words: Vec<&str> = code
+        .split('-')
+        .map(|word| match word {
+            "eof" => "end of file",
+            "cdata" => "CDATA",
+            "html" => "HTML",
+            word => word,
+        })
+        .collect();
+    let sentence = words.join(" ");
+    let mut chars = sentence.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => sentence,
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}[{}] {}: {}",
+            self.severity, self.code, self.span, self.message
+        )
+    }
+}
+
+impl Diagnostic {
+    /// Renders the diagnostic with the offending source line and carets under
+    /// the span, in the style of rustc. Spans over several lines are
+    /// underlined to the end of their first line.
+    pub fn render(&self, source: &str, origin: &str) -> String {
+        let start = self.span.start;
+        let line_start = start.offset() - start.column();
+        let line = source[line_start..].lines().next().unwrap_or("");
+        let end = if self.span.end.line() == start.line() {
+            self.span.end.offset() - line_start
+        } else {
+            line.len()
+        };
+        // A span starting at a line break sits just past the end of the line.
+        let column = start.column().min(line.len());
+        let end = end.min(line.len()).max(column);
+        let padding = line[..column].chars().count();
+        let carets = line[column..end].chars().count().max(1);
+
+        let number = start.line().to_string();
+        let gutter = " ".repeat(number.len());
+        let mut output = format!("{}[{}]: {}\n", self.severity, self.code, self.message);
+        output += &format!(
+            "{}--> {}:{}:{}\n",
+            gutter,
+            origin,
+            start.line(),
+            padding + 1
+        );
+        output += &format!("{} |\n", gutter);
+        output += &format!("{} | {}\n", number, line.replace('\t', " "));
+        output += &format!(
+            "{} | {}{}\n",
+            gutter,
+            " ".repeat(padding),
+            "^".repeat(carets)
+        );
+        output
+    }
+}
@@ -0,0 +1,108 @@
+use document;
+use parser::css_parser;
+use parser::diagnostic::*;
+use parser::html_parser;
+
+#[test]
+fn it_describes_error_codes() {
+    assert_eq!(describe_code("eof-in-tag"), "End of file in tag");
+    assert_eq!(
+        describe_code("cdata-in-html-content"),
+        "CDATA in HTML content"
+    );
+}
+
+#[test]
+fn it_reports_html_errors_with_codes_and_spans() {
+    let warnings = parse_html("<!DOCTYPE html><p a=1 a=2></p>");
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].severity, Severity::Error);
+    assert_eq!(warnings[0].code, "duplicate-attribute");
+    assert_eq!(warnings[0].span.start.offset(), 22);
+    assert_eq!(warnings[0].span.end.offset(), 23);
+}
+
+#[test]
+fn it_points_duplicate_attributes_at_the_kept_one() {
+    let document = document::create_document(b"<!DOCTYPE html><p>\n<b A=1 a=2>x</b>", b"", false);
+    assert_eq!(document.html_warnings.len(), 1);
+    assert_eq!(
+        document.html_warnings[0].message,
+        "Duplicate attribute 'a', keeping the one at 2:3-2:4"
+    );
+    let bold = &document.dom.children[1].children[1].children[0].children[1];
+    assert_eq!(bold.attribute_span("a").unwrap().name.start.offset(), 22);
+
+    // The kept attribute is named as written, before foreign content adjusts
+    // its case.
+    let warnings = parse_html("<svg viewBox='0 0 1 1' VIEWBOX=''>");
+    let duplicates: Vec<&str> = warnings
+        .iter()
+        .filter(|warning| warning.code == "duplicate-attribute")
+        .map(|warning| warning.message.as_str())
+        .collect();
+    assert_eq!(
+        duplicates,
+        ["Duplicate attribute 'viewbox', keeping the one at 1:5-1:12"]
+    );
+}
+
+#[test]
+fn it_reports_tree_construction_errors_at_the_token() {
+    let warnings = parse_html("<!DOCTYPE html>\n<p></div>");
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].code, "unexpected-end-tag");
+    assert_eq!(warnings[0].span.start.line(), 2);
+    assert_eq!(warnings[0].span.start.column(), 3);
+    assert_eq!(warnings[0].span.end.column(), 9);
+}
+
+#[test]
+fn it_reports_css_warnings() {
+    let mut warnings = vec![];
+    css_parser::parse(String::from("div { width: 10em; }"), &mut warnings);
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].severity, Severity::Warning);
+    assert_eq!(warnings[0].code, "invalid-declaration");
+    assert_eq!(warnings[0].span.start.offset(), 13);
+}
+
+#[test]
+fn it_renders_a_snippet_with_carets() {
+    let source = "<!DOCTYPE html>\n<p>\n  <b></i></b>";
+    let warnings = parse_html(source);
+    assert_eq!(
+        warnings[0].render(source, "index.html"),
+        "error[unexpected-end-tag]: Unexpected end tag\n \
+         --> index.html:3:6\n  \
+         |\n\
+         3 |   <b></i></b>\n  \
+         |      ^^^^\n"
+    );
+}
+
+#[test]
+fn it_renders_empty_and_multiline_spans() {
+    let warnings = parse_html("<!DOCTYPE html><p");
+    assert_eq!(warnings[0].code, "eof-in-tag");
+    assert_eq!(
+        warnings[0].render("<!DOCTYPE html><p", "html"),
+        "error[eof-in-tag]: End of file in tag\n \
+         --> html:1:18\n  \
+         |\n\
+         1 | <!DOCTYPE html><p\n  \
+         |                  ^\n"
+    );
+
+    let source = "<!DOCTYPE html><p></div\n>";
+    let warnings = parse_html(source);
+    assert!(warnings[0]
+        .render(source, "html")
+        .ends_with("1 | <!DOCTYPE html><p></div\n  |                   ^^^^^\n"));
+}
+
+fn parse_html(source: &str) -> Vec<Diagnostic> {
+    let mut warnings = vec![];
+    html_parser::parse(String::from(source), &mut warnings);
+    warnings
+}
@@ -0,0 +1,921 @@
+use parser::diagnostic::{self, Diagnostic};
+use parser::entities;
+use parser::parser::{ParserPosition, Span};
+use std::char;
+use std::fmt;
+use std::mem;
+use std::ops::Range;
+
+/// The character encodings input bytes can be decoded from, named as in the
+/// WHATWG Encoding standard.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Windows1252,
+    Windows1254,
+    Windows874,
+    Iso8859(u8),
+}
+
+/// How sure we are of an encoding. A byte order mark makes it certain; a
+/// `<meta>` declaration or a guess is only tentative.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Confidence {
+    Certain,
+    Tentative,
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Encoding::Utf8 => write!(f, "UTF-8"),
+            Encoding::Utf16Le => write!(f, "UTF-16LE"),
+            Encoding::Utf16Be => write!(f, "UTF-16BE"),
+            Encoding::Windows1252 => write!(f, "windows-1252"),
+            Encoding::Windows1254 => write!(f, "windows-1254"),
+            Encoding::Windows874 => write!(f, "windows-874"),
+            Encoding::Iso8859(part) => write!(f, "ISO-8859-{}", part),
+        }
+    }
+}
+
+impl fmt::Display for Confidence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Confidence::Certain => write!(f, "certain"),
+            Confidence::Tentative => write!(f, "tentative"),
+        }
+    }
+}
+
+impl Encoding {
+    /// Looks up an encoding label such as `latin1` or `utf8`, ignoring case
+    /// and surrounding whitespace.
+    pub fn for_label(label: &str) -> Option<Encoding> {
+        let label = label.trim_matches(is_space_char).to_ascii_lowercase();
+        let encoding = match label.as_str() {
+            "unicode-1-1-utf-8" | "unicode11utf8" | "unicode20utf8" | "utf-8" | "utf8"
+            | "x-unicode20utf8" => Encoding::Utf8,
+            "csunicode" | "iso-10646-ucs-2" | "ucs-2" | "unicode" | "unicodefeff" | "utf-16"
+            | "utf-16le" => Encoding::Utf16Le,
+            "unicodefffe" | "utf-16be" => Encoding::Utf16Be,
+            // Latin-1 and ASCII labels mean windows-1252 on the web.
+            "ansi_x3.4-1968" | "ascii" | "cp1252" | "cp819" | "csisolatin1" | "ibm819"
+            | "iso-8859-1" | "iso-ir-100" | "iso8859-1" | "iso88591" | "iso_8859-1"
+            | "iso_8859-1:1987" | "l1" | "latin1" | "us-ascii" | "windows-1252" | "x-cp1252"
+            | "x-user-defined" => Encoding::Windows1252,
+            // So do the Latin-5 and Thai ones for their Windows supersets.
+            "cp1254" | "csisolatin5" | "iso-ir-148" | "l5" | "latin5" | "windows-1254" => {
+                Encoding::Windows1254
+            }
+            "iso-8859-9" | "iso8859-9" | "iso88599" | "iso_8859-9" | "iso_8859-9:1989"
+            | "x-cp1254" => Encoding::Windows1254,
+            "dos-874" | "iso-8859-11" | "iso8859-11" | "iso885911" | "tis-620" | "windows-874" => {
+                Encoding::Windows874
+            }
+            "csisolatin2" | "iso-8859-2" | "iso-ir-101" | "iso8859-2" | "iso88592"
+            | "iso_8859-2" | "iso_8859-2:1987" | "l2" | "latin2" => Encoding::Iso8859(2),
+            "csisolatin3" | "iso-8859-3" | "iso-ir-109" | "iso8859-3" | "iso88593"
+            | "iso_8859-3" | "iso_8859-3:1988" | "l3" | "latin3" => Encoding::Iso8859(3),
+            "csisolatin4" | "iso-8859-4" | "iso-ir-110" | "iso8859-4" | "iso88594"
+            | "iso_8859-4" | "iso_8859-4:1988" | "l4" | "latin4" => Encoding::Iso8859(4),
+            "csisolatincyrillic" | "cyrillic" | "iso-8859-5" | "iso-ir-144" | "iso8859-5"
+            | "iso88595" | "iso_8859-5" | "iso_8859-5:1988" => Encoding::Iso8859(5),
+            "arabic" | "asmo-708" | "csiso88596e" | "csiso88596i" | "csisolatinarabic"
+            | "ecma-114" | "iso-8859-6" | "iso-8859-6-e" | "iso-8859-6-i" | "iso-ir-127"
+            | "iso8859-6" | "iso88596" | "iso_8859-6" | "iso_8859-6:1987" => Encoding::Iso8859(6),
+            "csisolatingreek" | "ecma-118" | "elot_928" | "greek" | "greek8" | "iso-8859-7"
+            | "iso-ir-126" | "iso8859-7" | "iso88597" | "iso_8859-7" | "iso_8859-7:1987"
+            | "sun_eu_greek" => Encoding::Iso8859(7),
+            "csiso88598e" | "csisolatinhebrew" | "hebrew" | "iso-8859-8" | "iso-8859-8-e"
+            | "iso-ir-138" | "iso8859-8" | "iso88598" | "iso_8859-8" | "iso_8859-8:1988"
+            | "visual" => Encoding::Iso8859(8),
+            // ISO-8859-8-I only differs in how the text is laid out, which
+            // doesn't matter for decoding.
+            "csiso88598i" | "iso-8859-8-i" | "logical" => Encoding::Iso8859(8),
+            "csisolatin6" | "iso-8859-10" | "iso-ir-157" | "iso8859-10" | "iso885910" | "l6"
+            | "latin6" => Encoding::Iso8859(10),
+            "iso-8859-13" | "iso8859-13" | "iso885913" => Encoding::Iso8859(13),
+            "iso-8859-14" | "iso8859-14" | "iso885914" => Encoding::Iso8859(14),
+            "csisolatin9" | "iso-8859-15" | "iso8859-15" | "iso885915" | "iso_8859-15" | "l9" => {
+                Encoding::Iso8859(15)
+            }
+            "iso-8859-16" => Encoding::Iso8859(16),
+            _ => return None,
+        };
+        Some(encoding)
+    }
+
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        match *self {
+            Encoding::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+            Encoding::Utf16Le => decode_utf16(bytes, u16::from_le_bytes),
+            Encoding::Utf16Be => decode_utf16(bytes, u16::from_be_bytes),
+            Encoding::Windows1252 => bytes
+                .iter()
+                .map(|&byte| match byte {
+                    0x80..=0x9F => {
+                        let code = entities::windows_1252_replacement(u32::from(byte));
+                        char::from_u32(code.unwrap_or(u32::from(byte))).unwrap()
+                    }
+                    _ => char::from(byte),
+                })
+                .collect(),
+            // windows-1254 is windows-1252 with the Turkish letters of
+            // ISO-8859-9, apart from two C1 controls which are kept.
+            Encoding::Windows1254 => bytes
+                .iter()
+                .map(|&byte| match byte {
+                    0x8E | 0x9E => char::from(byte),
+                    0x80..=0x9F => {
+                        let code = entities::windows_1252_replacement(u32::from(byte));
+                        char::from_u32(code.unwrap_or(u32::from(byte))).unwrap()
+                    }
+                    0xA0..=0xFF => ISO_8859_9[usize::from(byte - 0xA0)],
+                    _ => char::from(byte),
+                })
+                .collect(),
+            // windows-874 is ISO-8859-11 with a few of windows-1252's
+            // punctuation marks.
+            Encoding::Windows874 => bytes
+                .iter()
+                .map(|&byte| match byte {
+                    0x80 | 0x85 | 0x91..=0x97 => {
+                        let code = entities::windows_1252_replacement(u32::from(byte));
+                        char::from_u32(code.unwrap_or(u32::from(byte))).unwrap()
+                    }
+                    0xA0..=0xFF => ISO_8859_11[usize::from(byte - 0xA0)],
+                    _ => char::from(byte),
+                })
+                .collect(),
+            Encoding::Iso8859(part) => {
+                let table = iso_8859_table(part);
+                bytes
+                    .iter()
+                    .map(|&byte| match byte {
+                        0xA0..=0xFF => table[usize::from(byte - 0xA0)],
+                        _ => char::from(byte),
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// The name of the encoding a label stands for, when it is one the Encoding
+/// standard knows but which can't be decoded here, such as `shift_jis`.
+pub fn unsupported_label(label: &str) -> Option<&'static str> {
+    let label = label.trim_matches(is_space_char).to_ascii_lowercase();
+    UNSUPPORTED_LABELS
+        .iter()
+        .find(|&&(_, labels)| labels.contains(&label.as_str()))
+        .map(|&(name, _)| name)
+}
+
+const UNSUPPORTED_LABELS: &[(&str, &[&str])] = &[
+    ("IBM866", &["866", "cp866", "csibm866", "ibm866"]),
+    ("KOI8-R", &["cskoi8r", "koi", "koi8", "koi8-r", "koi8_r"]),
+    ("KOI8-U", &["koi8-ru", "koi8-u"]),
+    (
+        "macintosh",
+        &["csmacintosh", "mac", "macintosh", "x-mac-roman"],
+    ),
+    ("windows-1250", &["cp1250", "windows-1250", "x-cp1250"]),
+    ("windows-1251", &["cp1251", "windows-1251", "x-cp1251"]),
+    ("windows-1253", &["cp1253", "windows-1253", "x-cp1253"]),
+    ("windows-1255", &["cp1255", "windows-1255", "x-cp1255"]),
+    ("windows-1256", &["cp1256", "windows-1256", "x-cp1256"]),
+    ("windows-1257", &["cp1257", "windows-1257", "x-cp1257"]),
+    ("windows-1258", &["cp1258", "windows-1258", "x-cp1258"]),
+    ("x-mac-cyrillic", &["x-mac-cyrillic", "x-mac-ukrainian"]),
+    (
+        "GBK",
+        &[
+            "chinese",
+            "csgb2312",
+            "csiso58gb231280",
+            "gb2312",
+            "gb_2312",
+            "gb_2312-80",
+            "gbk",
+            "iso-ir-58",
+            "x-gbk",
+        ],
+    ),
+    ("gb18030", &["gb18030"]),
+    (
+        "Big5",
+        &["big5", "big5-hkscs", "cn-big5", "csbig5", "x-x-big5"],
+    ),
+    ("EUC-JP", &["cseucpkdfmtjapanese", "euc-jp", "x-euc-jp"]),
+    ("ISO-2022-JP", &["csiso2022jp", "iso-2022-jp"]),
+    (
+        "Shift_JIS",
+        &[
+            "csshiftjis",
+            "ms932",
+            "ms_kanji",
+            "shift-jis",
+            "shift_jis",
+            "sjis",
+            "windows-31j",
+            "x-sjis",
+        ],
+    ),
+    (
+        "EUC-KR",
+        &[
+            "cseuckr",
+            "csksc56011987",
+            "euc-kr",
+            "iso-ir-149",
+            "korean",
+            "ks_c_5601-1987",
+            "ks_c_5601-1989",
+            "ksc5601",
+            "ksc_5601",
+            "windows-949",
+        ],
+    ),
+    (
+        "replacement",
+        &[
+            "csiso2022kr",
+            "hz-gb-2312",
+            "iso-2022-cn",
+            "iso-2022-cn-ext",
+            "iso-2022-kr",
+            "replacement",
+        ],
+    ),
+];
+
+fn iso_8859_table(part: u8) -> &'static [char; 96] {
+    match part {
+        2 => &ISO_8859_2,
+        3 => &ISO_8859_3,
+        4 => &ISO_8859_4,
+        5 => &ISO_8859_5,
+        6 => &ISO_8859_6,
+        7 => &ISO_8859_7,
+        8 => &ISO_8859_8,
+        10 => &ISO_8859_10,
+        13 => &ISO_8859_13,
+        14 => &ISO_8859_14,
+        15 => &ISO_8859_15,
+        _ => &ISO_8859_16,
+    }
+}
+
+fn decode_utf16(bytes: &[u8], unit: fn([u8; 2]) -> u16) -> String {
+    let units = bytes.chunks(2).map(|pair| match *pair {
+        [first, second] => unit([first, second]),
+        // A lone trailing byte can't be decoded to anything.
+        _ => 0xFFFD,
+    });
+    char::decode_utf16(units)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+/// Decodes an HTML document, choosing the encoding from its byte order mark,
+/// then a `<meta>` declaration in the first 1024 bytes, then whether those
+/// bytes are valid UTF-8, falling back to windows-1252. Bytes which are
+/// invalid in the chosen encoding decode to U+FFFD. A declaration of an
+/// encoding which isn't supported is warned about and ignored.
+pub fn decode_html(bytes: &[u8], warnings: &mut Vec<Diagnostic>) -> (String, Encoding, Confidence) {
+    let mut decoder = create_html_decoder();
+    let text = decoder.decode(bytes, true, warnings);
+    let (encoding, confidence) = decoder.encoding().unwrap();
+    (text, encoding, confidence)
+}
+
+/// Decodes an HTML document which arrives in chunks, choosing the same
+/// encoding as `decode_html` would for the whole input.
+pub struct HtmlDecoder {
+    // Bytes held back until there are enough to sniff the encoding.
+    sniffed: Vec<u8>,
+    decoder: Option<(Decoder, Encoding, Confidence)>,
+}
+
+pub fn create_html_decoder() -> HtmlDecoder {
+    HtmlDecoder {
+        sniffed: vec![],
+        decoder: None,
+    }
+}
+
+impl HtmlDecoder {
+    /// Returns the text following on from what was decoded before.
+    pub fn decode(&mut self, bytes: &[u8], last: bool, warnings: &mut Vec<Diagnostic>) -> String {
+        match self.decoder {
+            Some((ref mut decoder, _, _)) => decoder.decode(bytes, last),
+            None => {
+                self.sniffed.extend_from_slice(bytes);
+                let sniffed = sniff_html(&self.sniffed, last, warnings);
+                let (encoding, confidence, bom_length) = match sniffed {
+                    Some(sniffed) => sniffed,
+                    None => return String::new(),
+                };
+                let bytes = mem::take(&mut self.sniffed);
+                let mut decoder = create_decoder(encoding);
+                let text = decoder.decode(&bytes[bom_length..], last);
+                self.decoder = Some((decoder, encoding, confidence));
+                text
+            }
+        }
+    }
+
+    /// The encoding being decoded from, once enough input has arrived to
+    /// tell.
+    pub fn encoding(&self) -> Option<(Encoding, Confidence)> {
+        self.decoder
+            .as_ref()
+            .map(|&(_, encoding, confidence)| (encoding, confidence))
+    }
+}
+
+// Picks the encoding of an HTML document from its first 1024 bytes,
+// returning the length of any byte order mark to skip. Returns None while
+// fewer than 1024 bytes of an incomplete input are available. Later bytes
+// are never looked at, so the choice doesn't depend on how the input is
+// split into chunks.
+fn sniff_html(
+    bytes: &[u8],
+    complete: bool,
+    warnings: &mut Vec<Diagnostic>,
+) -> Option<(Encoding, Confidence, usize)> {
+    if let Some((encoding, length)) = byte_order_mark(bytes) {
+        return Some((encoding, Confidence::Certain, length));
+    }
+    if !complete && bytes.len() < 1024 {
+        return None;
+    }
+    let (bytes, complete) = match bytes.get(..1024) {
+        Some(start) if bytes.len() > 1024 => (start, false),
+        _ => (bytes, complete),
+    };
+    let declared = prescan(bytes);
+    if let Some(Declared::Supported(encoding)) = declared {
+        return Some((encoding, Confidence::Tentative, 0));
+    }
+    let encoding = if looks_like_utf8(bytes, complete) {
+        Encoding::Utf8
+    } else {
+        Encoding::Windows1252
+    };
+    if let Some(Declared::Unsupported(name, range)) = declared {
+        warnings.push(diagnostic::warning(
+            "unsupported-encoding",
+            decoded_span(bytes, range, encoding),
+            &format!("The {} encoding is not supported, so it was ignored", name),
+        ));
+    }
+    Some((encoding, Confidence::Tentative, 0))
+}
+
+// Where a range of the input bytes ends up in the text decoded from them.
+fn decoded_span(bytes: &[u8], range: Range<usize>, encoding: Encoding) -> Span {
+    let advance = |position, bytes| {
+        encoding
+            .decode(bytes)
+            .chars()
+            .fold(position, ParserPosition::advance)
+    };
+    let start = advance(ParserPosition::default(), &bytes[..range.start]);
+    Span {
+        start,
+        end: advance(start, &bytes[range]),
+    }
+}
+
+// A sequence cut off at the end of incomplete input may still be valid.
+fn looks_like_utf8(bytes: &[u8], complete: bool) -> bool {
+    match std::str::from_utf8(bytes) {
+        Ok(_) => true,
+        Err(error) => !complete && error.error_len().is_none(),
+    }
+}
+
+/// Decodes input which arrives in chunks, holding back bytes at the end of
+/// a chunk which only make up part of a character.
+pub struct Decoder {
+    encoding: Encoding,
+    pending: Vec<u8>,
+}
+
+pub fn create_decoder(encoding: Encoding) -> Decoder {
+    Decoder {
+        encoding,
+        pending: vec![],
+    }
+}
+
+impl Decoder {
+    pub fn decode(&mut self, bytes: &[u8], last: bool) -> String {
+        let mut input = mem::take(&mut self.pending);
+        input.extend_from_slice(bytes);
+        if !last {
+            let length = input.len() - self.incomplete_suffix(&input);
+            self.pending = input.split_off(length);
+        }
+        self.encoding.decode(&input)
+    }
+
+    fn incomplete_suffix(&self, input: &[u8]) -> usize {
+        match self.encoding {
+            Encoding::Utf8 => {
+                // Look for the lead byte of a sequence longer than the rest.
+                for back in 1..=input.len().min(3) {
+                    let needed = match input[input.len() - back] {
+                        0xC2..=0xDF => 2,
+                        0xE0..=0xEF => 3,
+                        0xF0..=0xF4 => 4,
+                        0x80..=0xBF => continue,
+                        _ => return 0,
+                    };
+                    return if needed > back { back } else { 0 };
+                }
+                0
+            }
+            Encoding::Utf16Le | Encoding::Utf16Be => {
+                let odd = input.len() % 2;
+                let units = &input[..input.len() - odd];
+                if units.is_empty() {
+                    return odd;
+                }
+                let pair = [units[units.len() - 2], units[units.len() - 1]];
+                let last_unit = match self.encoding {
+                    Encoding::Utf16Le => u16::from_le_bytes(pair),
+                    _ => u16::from_be_bytes(pair),
+                };
+                // A high surrogate waits for the low surrogate after it.
+                match last_unit {
+                    0xD800..=0xDBFF => odd + 2,
+                    _ => odd,
+                }
+            }
+            Encoding::Windows1252
+            | Encoding::Windows1254
+            | Encoding::Windows874
+            | Encoding::Iso8859(_) => 0,
+        }
+    }
+}
+
+/// Decodes a style sheet from its byte order mark or `@charset` rule,
+/// defaulting to UTF-8.
+pub fn decode_css(bytes: &[u8]) -> (String, Encoding) {
+    if let Some((encoding, length)) = byte_order_mark(bytes) {
+        return (encoding.decode(&bytes[length..]), encoding);
+    }
+    let encoding = charset_rule(bytes)
+        .map(|encoding| match encoding {
+            Encoding::Utf16Le | Encoding::Utf16Be => Encoding::Utf8,
+            encoding => encoding,
+        })
+        .unwrap_or(Encoding::Utf8);
+    (encoding.decode(bytes), encoding)
+}
+
+fn byte_order_mark(bytes: &[u8]) -> Option<(Encoding, usize)> {
+    if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        Some((Encoding::Utf8, 3))
+    } else if bytes.starts_with(&[0xFE, 0xFF]) {
+        Some((Encoding::Utf16Be, 2))
+    } else if bytes.starts_with(&[0xFF, 0xFE]) {
+        Some((Encoding::Utf16Le, 2))
+    } else {
+        None
+    }
+}
+
+// The rule has to be exactly `@charset "label";` at the very start.
+fn charset_rule(bytes: &[u8]) -> Option<Encoding> {
+    let rest = bytes.strip_prefix(b"@charset \"")?;
+    let end = rest.iter().take(1024).position(|&byte| byte == b'"')?;
+    if rest.get(end + 1) != Some(&b';') {
+        return None;
+    }
+    Encoding::for_label(&String::from_utf8_lossy(&rest[..end]))
+}
+
+// An encoding declared by a `<meta>`.
+enum Declared {
+    Supported(Encoding),
+    // The name of an encoding which can't be decoded here, with the bytes of
+    // the attribute value naming it.
+    Unsupported(&'static str, Range<usize>),
+}
+
+// Follows the WHATWG "prescan a byte stream to determine its encoding"
+// algorithm, which only looks at comments, tags and their attributes.
+fn prescan(bytes: &[u8]) -> Option<Declared> {
+    let bytes = &bytes[..bytes.len().min(1024)];
+    let mut position = 0;
+    while position < bytes.len() {
+        let rest = &bytes[position..];
+        if rest.starts_with(b"<!--") {
+            // The hyphens of `<!-->` count towards the end of the comment.
+            position = find(bytes, position + 2, b"-->")? + 3;
+            continue;
+        }
+        if starts_with_ignore_case(rest, b"<meta")
+            && rest
+                .get(5)
+                .is_some_and(|&byte| is_space(byte) || byte == b'/')
+        {
+            position += 6;
+            if let Some(declared) = meta_encoding(bytes, &mut position) {
+                return Some(declared);
+            }
+        } else if rest.len() > 1
+            && rest[0] == b'<'
+            && (rest[1].is_ascii_alphabetic()
+                || (rest[1] == b'/' && rest.get(2).is_some_and(u8::is_ascii_alphabetic)))
+        {
+            position += 1;
+            while position < bytes.len() && !is_space(bytes[position]) && bytes[position] != b'>' {
+                position += 1;
+            }
+            while get_attribute(bytes, &mut position).is_some() {}
+        } else if rest.starts_with(b"<!") || rest.starts_with(b"</") || rest.starts_with(b"<?") {
+            position = find(bytes, position + 2, b">")?;
+        }
+        position += 1;
+    }
+    None
+}
+
+fn meta_encoding(bytes: &[u8], position: &mut usize) -> Option<Declared> {
+    let mut seen = vec![];
+    let mut got_pragma = false;
+    let mut need_pragma = None;
+    let mut charset = None;
+    while let Some((name, value, range)) = get_attribute(bytes, position) {
+        if seen.contains(&name) {
+            continue;
+        }
+        match name.as_str() {
+            "http-equiv" if value == "content-type" => got_pragma = true,
+            "content" if charset.is_none() => {
+                if let Some(declared) =
+                    content_charset(&value).and_then(|label| declare(label, range))
+                {
+                    charset = Some(declared);
+                    need_pragma = Some(true);
+                }
+            }
+            "charset" if charset.is_none() => {
+                charset = declare(&value, range);
+                need_pragma = Some(false);
+            }
+            _ => {}
+        }
+        seen.push(name);
+    }
+    if need_pragma? && !got_pragma {
+        return None;
+    }
+    match charset? {
+        Declared::Supported(Encoding::Utf16Le) | Declared::Supported(Encoding::Utf16Be) => {
+            Some(Declared::Supported(Encoding::Utf8))
+        }
+        declared => Some(declared),
+    }
+}
+
+// Labels the Encoding standard doesn't know are skipped over, but ones for
+// encodings which aren't supported here end the prescan like any other.
+fn declare(label: &str, range: Range<usize>) -> Option<Declared> {
+    match Encoding::for_label(label) {
+        Some(encoding) => Some(Declared::Supported(encoding)),
+        None => unsupported_label(label).map(|name| Declared::Unsupported(name, range)),
+    }
+}
+
+// Extracts the encoding label from a `text/html; charset=...` content
+// value.
+fn content_charset(content: &str) -> Option<&str> {
+    let mut rest = content;
+    loop {
+        let index = rest.to_ascii_lowercase().find("charset")?;
+        rest = rest[index + 7..].trim_start_matches(is_space_char);
+        if let Some(value) = rest.strip_prefix('=') {
+            let value = value.trim_start_matches(is_space_char);
+            let label = match value.chars().next() {
+                Some(quote) if quote == '"' || quote == '\'' => {
+                    let value = &value[1..];
+                    &value[..value.find(quote)?]
+                }
+                _ => value
+                    .split(|c: char| c == ';' || is_space_char(c))
+                    .next()
+                    .unwrap_or(""),
+            };
+            return Some(label);
+        }
+    }
+}
+
+// Reads the next attribute of a tag, lowercased, with the range of bytes
+// its value came from, leaving `position` on the byte after it. Returns None
+// at the end of the tag.
+fn get_attribute(bytes: &[u8], position: &mut usize) -> Option<(String, String, Range<usize>)> {
+    while *position < bytes.len() && (is_space(bytes[*position]) || bytes[*position] == b'/') {
+        *position += 1;
+    }
+    if *position >= bytes.len() || bytes[*position] == b'>' {
+        return None;
+    }
+    let mut name = vec![];
+    let mut value = vec![];
+    loop {
+        let byte = *bytes.get(*position)?;
+        match byte {
+            b'=' if !name.is_empty() => {
+                *position += 1;
+                break;
+            }
+            _ if is_space(byte) => {
+                while bytes.get(*position).is_some_and(|&byte| is_space(byte)) {
+                    *position += 1;
+                }
+                if bytes.get(*position) != Some(&b'=') {
+                    return Some(attribute(name, value, *position..*position));
+                }
+                *position += 1;
+                break;
+            }
+            b'/' | b'>' => return Some(attribute(name, value, *position..*position)),
+            _ => name.push(byte.to_ascii_lowercase()),
+        }
+        *position += 1;
+    }
+    while bytes.get(*position).is_some_and(|&byte| is_space(byte)) {
+        *position += 1;
+    }
+    let start = *position;
+    match *bytes.get(*position)? {
+        quote @ b'"' | quote @ b'\'' => loop {
+            *position += 1;
+            let byte = *bytes.get(*position)?;
+            if byte == quote {
+                *position += 1;
+                return Some(attribute(name, value, start + 1..*position - 1));
+            }
+            value.push(byte.to_ascii_lowercase());
+        },
+        b'>' => Some(attribute(name, value, start..start)),
+        _ => loop {
+            let byte = *bytes.get(*position)?;
+            if is_space(byte) || byte == b'>' {
+                return Some(attribute(name, value, start..*position));
+            }
+            value.push(byte.to_ascii_lowercase());
+            *position += 1;
+        },
+    }
+}
+
+fn attribute(name: Vec<u8>, value: Vec<u8>, range: Range<usize>) -> (String, String, Range<usize>) {
+    (
+        String::from_utf8_lossy(&name).into_owned(),
+        String::from_utf8_lossy(&value).into_owned(),
+        range,
+    )
+}
+
+fn find(bytes: &[u8], from: usize, needle: &[u8]) -> Option<usize> {
+    bytes
+        .get(from..)?
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|index| from + index)
+}
+
+fn starts_with_ignore_case(bytes: &[u8], prefix: &[u8]) -> bool {
+    bytes.len() >= prefix.len() && bytes[..prefix.len()].eq_ignore_ascii_case(prefix)
+}
+
+fn is_space(byte: u8) -> bool {
+    matches!(byte, b'\t' | b'\n' | b'\x0C' | b'\r' | b' ')
+}
+
+fn is_space_char(c: char) -> bool {
+    c.is_ascii() && is_space(c as u8)
+}
+
+// The upper halves (0xA0 to 0xFF) of the ISO-8859 single byte encodings.
+// Bytes an encoding leaves unassigned decode to U+FFFD.
+const ISO_8859_2: [char; 96] = [
+    '\u{00A0}', '\u{0104}', '\u{02D8}', '\u{0141}', '\u{00A4}', '\u{013D}', '\u{015A}', '\u{00A7}',
+    '\u{00A8}', '\u{0160}', '\u{015E}', '\u{0164}', '\u{0179}', '\u{00AD}', '\u{017D}', '\u{017B}',
+    '\u{00B0}', '\u{0105}', '\u{02DB}', '\u{0142}', '\u{00B4}', '\u{013E}', '\u{015B}', '\u{02C7}',
+    '\u{00B8}', '\u{0161}', '\u{015F}', '\u{0165}', '\u{017A}', '\u{02DD}', '\u{017E}', '\u{017C}',
+    '\u{0154}', '\u{00C1}', '\u{00C2}', '\u{0102}', '\u{00C4}', '\u{0139}', '\u{0106}', '\u{00C7}',
+    '\u{010C}', '\u{00C9}', '\u{0118}', '\u{00CB}', '\u{011A}', '\u{00CD}', '\u{00CE}', '\u{010E}',
+    '\u{0110}', '\u{0143}', '\u{0147}', '\u{00D3}', '\u{00D4}', '\u{0150}', '\u{00D6}', '\u{00D7}',
+    '\u{0158}', '\u{016E}', '\u{00DA}', '\u{0170}', '\u{00DC}', '\u{00DD}', '\u{0162}', '\u{00DF}',
+    '\u{0155}', '\u{00E1}', '\u{00E2}', '\u{0103}', '\u{00E4}', '\u{013A}', '\u{0107}', '\u{00E7}',
+    '\u{010D}', '\u{00E9}', '\u{0119}', '\u{00EB}', '\u{011B}', '\u{00ED}', '\u{00EE}', '\u{010F}',
+    '\u{0111}', '\u{0144}', '\u{0148}', '\u{00F3}', '\u{00F4}', '\u{0151}', '\u{00F6}', '\u{00F7}',
+    '\u{0159}', '\u{016F}', '\u{00FA}', '\u{0171}', '\u{00FC}', '\u{00FD}', '\u{0163}', '\u{02D9}',
+];
+
+const ISO_8859_3: [char; 96] = [
+    '\u{00A0}', '\u{0126}', '\u{02D8}', '\u{00A3}', '\u{00A4}', '\u{FFFD}', '\u{0124}', '\u{00A7}',
+    '\u{00A8}', '\u{0130}', '\u{015E}', '\u{011E}', '\u{0134}', '\u{00AD}', '\u{FFFD}', '\u{017B}',
+    '\u{00B0}', '\u{0127}', '\u{00B2}', '\u{00B3}', '\u{00B4}', '\u{00B5}', '\u{0125}', '\u{00B7}',
+    '\u{00B8}', '\u{0131}', '\u{015F}', '\u{011F}', '\u{0135}', '\u{00BD}', '\u{FFFD}', '\u{017C}',
+    '\u{00C0}', '\u{00C1}', '\u{00C2}', '\u{FFFD}', '\u{00C4}', '\u{010A}', '\u{0108}', '\u{00C7}',
+    '\u{00C8}', '\u{00C9}', '\u{00CA}', '\u{00CB}', '\u{00CC}', '\u{00CD}', '\u{00CE}', '\u{00CF}',
+    '\u{FFFD}', '\u{00D1}', '\u{00D2}', '\u{00D3}', '\u{00D4}', '\u{0120}', '\u{00D6}', '\u{00D7}',
+    '\u{011C}', '\u{00D9}', '\u{00DA}', '\u{00DB}', '\u{00DC}', '\u{016C}', '\u{015C}', '\u{00DF}',
+    '\u{00E0}', '\u{00E1}', '\u{00E2}', '\u{FFFD}', '\u{00E4}', '\u{010B}', '\u{0109}', '\u{00E7}',
+    '\u{00E8}', '\u{00E9}', '\u{00EA}', '\u{00EB}', '\u{00EC}', '\u{00ED}', '\u{00EE}', '\u{00EF}',
+    '\u{FFFD}', '\u{00F1}', '\u{00F2}', '\u{00F3}', '\u{00F4}', '\u{0121}', '\u{00F6}', '\u{00F7}',
+    '\u{011D}', '\u{00F9}', '\u{00FA}', '\u{00FB}', '\u{00FC}', '\u{016D}', '\u{015D}', '\u{02D9}',
+];
+
+const ISO_8859_4: [char; 96] = [
+    '\u{00A0}', '\u{0104}', '\u{0138}', '\u{0156}', '\u{00A4}', '\u{0128}', '\u{013B}', '\u{00A7}',
+    '\u{00A8}', '\u{0160}', '\u{0112}', '\u{0122}', '\u{0166}', '\u{00AD}', '\u{017D}', '\u{00AF}',
+    '\u{00B0}', '\u{0105}', '\u{02DB}', '\u{0157}', '\u{00B4}', '\u{0129}', '\u{013C}', '\u{02C7}',
+    '\u{00B8}', '\u{0161}', '\u{0113}', '\u{0123}', '\u{0167}', '\u{014A}', '\u{017E}', '\u{014B}',
+    '\u{0100}', '\u{00C1}', '\u{00C2}', '\u{00C3}', '\u{00C4}', '\u{00C5}', '\u{00C6}', '\u{012E}',
+    '\u{010C}', '\u{00C9}', '\u{0118}', '\u{00CB}', '\u{0116}', '\u{00CD}', '\u{00CE}', '\u{012A}',
+    '\u{0110}', '\u{0145}', '\u{014C}', '\u{0136}', '\u{00D4}', '\u{00D5}', '\u{00D6}', '\u{00D7}',
+    '\u{00D8}', '\u{0172}', '\u{00DA}', '\u{00DB}', '\u{00DC}', '\u{0168}', '\u{016A}', '\u{00DF}',
+    '\u{0101}', '\u{00E1}', '\u{00E2}', '\u{00E3}', '\u{00E4}', '\u{00E5}', '\u{00E6}', '\u{012F}',
+    '\u{010D}', '\u{00E9}', '\u{0119}', '\u{00EB}', '\u{0117}', '\u{00ED}', '\u{00EE}', '\u{012B}',
+    '\u{0111}', '\u{0146}', '\u{014D}', '\u{0137}', '\u{00F4}', '\u{00F5}', '\u{00F6}', '\u{00F7}',
+    '\u{00F8}', '\u{0173}', '\u{00FA}', '\u{00FB}', '\u{00FC}', '\u{0169}', '\u{016B}', '\u{02D9}',
+];
+
+const ISO_8859_5: [char; 96] = [
+    '\u{00A0}', '\u{0401}', '\u{0402}', '\u{0403}', '\u{0404}', '\u{0405}', '\u{0406}', '\u{0407}',
+    '\u{0408}', '\u{0409}', '\u{040A}', '\u{040B}', '\u{040C}', '\u{00AD}', '\u{040E}', '\u{040F}',
+    '\u{0410}', '\u{0411}', '\u{0412}', '\u{0413}', '\u{0414}', '\u{0415}', '\u{0416}', '\u{0417}',
+    '\u{0418}', '\u{0419}', '\u{041A}', '\u{041B}', '\u{041C}', '\u{041D}', '\u{041E}', '\u{041F}',
+    '\u{0420}', '\u{0421}', '\u{0422}', '\u{0423}', '\u{0424}', '\u{0425}', '\u{0426}', '\u{0427}',
+    '\u{0428}', '\u{0429}', '\u{042A}', '\u{042B}', '\u{042C}', '\u{042D}', '\u{042E}', '\u{042F}',
+    '\u{0430}', '\u{0431}', '\u{0432}', '\u{0433}', '\u{0434}', '\u{0435}', '\u{0436}', '\u{0437}',
+    '\u{0438}', '\u{0439}', '\u{043A}', '\u{043B}', '\u{043C}', '\u{043D}', '\u{043E}', '\u{043F}',
+    '\u{0440}', '\u{0441}', '\u{0442}', '\u{0443}', '\u{0444}', '\u{0445}', '\u{0446}', '\u{0447}',
+    '\u{0448}', '\u{0449}', '\u{044A}', '\u{044B}', '\u{044C}', '\u{044D}', '\u{044E}', '\u{044F}',
+    '\u{2116}', '\u{0451}', '\u{0452}', '\u{0453}', '\u{0454}', '\u{0455}', '\u{0456}', '\u{0457}',
+    '\u{0458}', '\u{0459}', '\u{045A}', '\u{045B}', '\u{045C}', '\u{00A7}', '\u{045E}', '\u{045F}',
+];
+
+const ISO_8859_6: [char; 96] = [
+    '\u{00A0}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{00A4}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}',
+    '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{060C}', '\u{00AD}', '\u{FFFD}', '\u{FFFD}',
+    '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}',
+    '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{061B}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{061F}',
+    '\u{FFFD}', '\u{0621}', '\u{0622}', '\u{0623}', '\u{0624}', '\u{0625}', '\u{0626}', '\u{0627}',
+    '\u{0628}', '\u{0629}', '\u{062A}', '\u{062B}', '\u{062C}', '\u{062D}', '\u{062E}', '\u{062F}',
+    '\u{0630}', '\u{0631}', '\u{0632}', '\u{0633}', '\u{0634}', '\u{0635}', '\u{0636}', '\u{0637}',
+    '\u{0638}', '\u{0639}', '\u{063A}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}',
+    '\u{0640}', '\u{0641}', '\u{0642}', '\u{0643}', '\u{0644}', '\u{0645}', '\u{0646}', '\u{0647}',
+    '\u{0648}', '\u{0649}', '\u{064A}', '\u{064B}', '\u{064C}', '\u{064D}', '\u{064E}', '\u{064F}',
+    '\u{0650}', '\u{0651}', '\u{0652}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}',
+    '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}',
+];
+
+const ISO_8859_7: [char; 96] = [
+    '\u{00A0}', '\u{2018}', '\u{2019}', '\u{00A3}', '\u{20AC}', '\u{20AF}', '\u{00A6}', '\u{00A7}',
+    '\u{00A8}', '\u{00A9}', '\u{037A}', '\u{00AB}', '\u{00AC}', '\u{00AD}', '\u{FFFD}', '\u{2015}',
+    '\u{00B0}', '\u{00B1}', '\u{00B2}', '\u{00B3}', '\u{0384}', '\u{0385}', '\u{0386}', '\u{00B7}',
+    '\u{0388}', '\u{0389}', '\u{038A}', '\u{00BB}', '\u{038C}', '\u{00BD}', '\u{038E}', '\u{038F}',
+    '\u{0390}', '\u{0391}', '\u{0392}', '\u{0393}', '\u{0394}', '\u{0395}', '\u{0396}', '\u{0397}',
+    '\u{0398}', '\u{0399}', '\u{039A}', '\u{039B}', '\u{039C}', '\u{039D}', '\u{039E}', '\u{039F}',
+    '\u{03A0}', '\u{03A1}', '\u{FFFD}', '\u{03A3}', '\u{03A4}', '\u{03A5}', '\u{03A6}', '\u{03A7}',
+    '\u{03A8}', '\u{03A9}', '\u{03AA}', '\u{03AB}', '\u{03AC}', '\u{03AD}', '\u{03AE}', '\u{03AF}',
+    '\u{03B0}', '\u{03B1}', '\u{03B2}', '\u{03B3}', '\u{03B4}', '\u{03B5}', '\u{03B6}', '\u{03B7}',
+    '\u{03B8}', '\u{03B9}', '\u{03BA}', '\u{03BB}', '\u{03BC}', '\u{03BD}', '\u{03BE}', '\u{03BF}',
+    '\u{03C0}', '\u{03C1}', '\u{03C2}', '\u{03C3}', '\u{03C4}', '\u{03C5}', '\u{03C6}', '\u{03C7}',
+    '\u{03C8}', '\u{03C9}', '\u{03CA}', '\u{03CB}', '\u{03CC}', '\u{03CD}', '\u{03CE}', '\u{FFFD}',
+];
+
+const ISO_8859_8: [char; 96] = [
+    '\u{00A0}', '\u{FFFD}', '\u{00A2}', '\u{00A3}', '\u{00A4}', '\u{00A5}', '\u{00A6}', '\u{00A7}',
+    '\u{00A8}', '\u{00A9}', '\u{00D7}', '\u{00AB}', '\u{00AC}', '\u{00AD}', '\u{00AE}', '\u{00AF}',
+    '\u{00B0}', '\u{00B1}', '\u{00B2}', '\u{00B3}', '\u{00B4}', '\u{00B5}', '\u{00B6}', '\u{00B7}',
+    '\u{00B8}', '\u{00B9}', '\u{00F7}', '\u{00BB}', '\u{00BC}', '\u{00BD}', '\u{00BE}', '\u{FFFD}',
+    '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}',
+    '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}',
+    '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}',
+    '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{2017}',
+    '\u{05D0}', '\u{05D1}', '\u{05D2}', '\u{05D3}', '\u{05D4}', '\u{05D5}', '\u{05D6}', '\u{05D7}',
+    '\u{05D8}', '\u{05D9}', '\u{05DA}', '\u{05DB}', '\u{05DC}', '\u{05DD}', '\u{05DE}', '\u{05DF}',
+    '\u{05E0}', '\u{05E1}', '\u{05E2}', '\u{05E3}', '\u{05E4}', '\u{05E5}', '\u{05E6}', '\u{05E7}',
+    '\u{05E8}', '\u{05E9}', '\u{05EA}', '\u{FFFD}', '\u{FFFD}', '\u{200E}', '\u{200F}', '\u{FFFD}',
+];
+
+const ISO_8859_9: [char; 96] = [
+    '\u{00A0}', '\u{00A1}', '\u{00A2}', '\u{00A3}', '\u{00A4}', '\u{00A5}', '\u{00A6}', '\u{00A7}',
+    '\u{00A8}', '\u{00A9}', '\u{00AA}', '\u{00AB}', '\u{00AC}', '\u{00AD}', '\u{00AE}', '\u{00AF}',
+    '\u{00B0}', '\u{00B1}', '\u{00B2}', '\u{00B3}', '\u{00B4}', '\u{00B5}', '\u{00B6}', '\u{00B7}',
+    '\u{00B8}', '\u{00B9}', '\u{00BA}', '\u{00BB}', '\u{00BC}', '\u{00BD}', '\u{00BE}', '\u{00BF}',
+    '\u{00C0}', '\u{00C1}', '\u{00C2}', '\u{00C3}', '\u{00C4}', '\u{00C5}', '\u{00C6}', '\u{00C7}',
+    '\u{00C8}', '\u{00C9}', '\u{00CA}', '\u{00CB}', '\u{00CC}', '\u{00CD}', '\u{00CE}', '\u{00CF}',
+    '\u{011E}', '\u{00D1}', '\u{00D2}', '\u{00D3}', '\u{00D4}', '\u{00D5}', '\u{00D6}', '\u{00D7}',
+    '\u{00D8}', '\u{00D9}', '\u{00DA}', '\u{00DB}', '\u{00DC}', '\u{0130}', '\u{015E}', '\u{00DF}',
+    '\u{00E0}', '\u{00E1}', '\u{00E2}', '\u{00E3}', '\u{00E4}', '\u{00E5}', '\u{00E6}', '\u{00E7}',
+    '\u{00E8}', '\u{00E9}', '\u{00EA}', '\u{00EB}', '\u{00EC}', '\u{00ED}', '\u{00EE}', '\u{00EF}',
+    '\u{011F}', '\u{00F1}', '\u{00F2}', '\u{00F3}', '\u{00F4}', '\u{00F5}', '\u{00F6}', '\u{00F7}',
+    '\u{00F8}', '\u{00F9}', '\u{00FA}', '\u{00FB}', '\u{00FC}', '\u{0131}', '\u{015F}', '\u{00FF}',
+];
+
+const ISO_8859_10: [char; 96] = [
+    '\u{00A0}', '\u{0104}', '\u{0112}', '\u{0122}', '\u{012A}', '\u{0128}', '\u{0136}', '\u{00A7}',
+    '\u{013B}', '\u{0110}', '\u{0160}', '\u{0166}', '\u{017D}', '\u{00AD}', '\u{016A}', '\u{014A}',
+    '\u{00B0}', '\u{0105}', '\u{0113}', '\u{0123}', '\u{012B}', '\u{0129}', '\u{0137}', '\u{00B7}',
+    '\u{013C}', '\u{0111}', '\u{0161}', '\u{0167}', '\u{017E}', '\u{2015}', '\u{016B}', '\u{014B}',
+    '\u{0100}', '\u{00C1}', '\u{00C2}', '\u{00C3}', '\u{00C4}', '\u{00C5}', '\u{00C6}', '\u{012E}',
+    '\u{010C}', '\u{00C9}', '\u{0118}', '\u{00CB}', '\u{0116}', '\u{00CD}', '\u{00CE}', '\u{00CF}',
+    '\u{00D0}', '\u{0145}', '\u{014C}', '\u{00D3}', '\u{00D4}', '\u{00D5}', '\u{00D6}', '\u{0168}',
+    '\u{00D8}', '\u{0172}', '\u{00DA}', '\u{00DB}', '\u{00DC}', '\u{00DD}', '\u{00DE}', '\u{00DF}',
+    '\u{0101}', '\u{00E1}', '\u{00E2}', '\u{00E3}', '\u{00E4}', '\u{00E5}', '\u{00E6}', '\u{012F}',
+    '\u{010D}', '\u{00E9}', '\u{0119}', '\u{00EB}', '\u{0117}', '\u{00ED}', '\u{00EE}', '\u{00EF}',
+    '\u{00F0}', '\u{0146}', '\u{014D}', '\u{00F3}', '\u{00F4}', '\u{00F5}', '\u{00F6}', '\u{0169}',
+    '\u{00F8}', '\u{0173}', '\u{00FA}', '\u{00FB}', '\u{00FC}', '\u{00FD}', '\u{00FE}', '\u{0138}',
+];
+
+const ISO_8859_11: [char; 96] = [
+    '\u{00A0}', '\u{0E01}', '\u{0E02}', '\u{0E03}', '\u{0E04}', '\u{0E05}', '\u{0E06}', '\u{0E07}',
+    '\u{0E08}', '\u{0E09}', '\u{0E0A}', '\u{0E0B}', '\u{0E0C}', '\u{0E0D}', '\u{0E0E}', '\u{0E0F}',
+    '\u{0E10}', '\u{0E11}', '\u{0E12}', '\u{0E13}', '\u{0E14}', '\u{0E15}', '\u{0E16}', '\u{0E17}',
+    '\u{0E18}', '\u{0E19}', '\u{0E1A}', '\u{0E1B}', '\u{0E1C}', '\u{0E1D}', '\u{0E1E}', '\u{0E1F}',
+    '\u{0E20}', '\u{0E21}', '\u{0E22}', '\u{0E23}', '\u{0E24}', '\u{0E25}', '\u{0E26}', '\u{0E27}',
+    '\u{0E28}', '\u{0E29}', '\u{0E2A}', '\u{0E2B}', '\u{0E2C}', '\u{0E2D}', '\u{0E2E}', '\u{0E2F}',
+    '\u{0E30}', '\u{0E31}', '\u{0E32}', '\u{0E33}', '\u{0E34}', '\u{0E35}', '\u{0E36}', '\u{0E37}',
+    '\u{0E38}', '\u{0E39}', '\u{0E3A}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{0E3F}',
+    '\u{0E40}', '\u{0E41}', '\u{0E42}', '\u{0E43}', '\u{0E44}', '\u{0E45}', '\u{0E46}', '\u{0E47}',
+    '\u{0E48}', '\u{0E49}', '\u{0E4A}', '\u{0E4B}', '\u{0E4C}', '\u{0E4D}', '\u{0E4E}', '\u{0E4F}',
+    '\u{0E50}', '\u{0E51}', '\u{0E52}', '\u{0E53}', '\u{0E54}', '\u{0E55}', '\u{0E56}', '\u{0E57}',
+    '\u{0E58}', '\u{0E59}', '\u{0E5A}', '\u{0E5B}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}',
+];
+
+const ISO_8859_13: [char; 96] = [
+    '\u{00A0}', '\u{201D}', '\u{00A2}', '\u{00A3}', '\u{00A4}', '\u{201E}', '\u{00A6}', '\u{00A7}',
+    '\u{00D8}', '\u{00A9}', '\u{0156}', '\u{00AB}', '\u{00AC}', '\u{00AD}', '\u{00AE}', '\u{00C6}',
+    '\u{00B0}', '\u{00B1}', '\u{00B2}', '\u{00B3}', '\u{201C}', '\u{00B5}', '\u{00B6}', '\u{00B7}',
+    '\u{00F8}', '\u{00B9}', '\u{0157}', '\u{00BB}', '\u{00BC}', '\u{00BD}', '\u{00BE}', '\u{00E6}',
+    '\u{0104}', '\u{012E}', '\u{0100}', '\u{0106}', '\u{00C4}', '\u{00C5}', '\u{0118}', '\u{0112}',
+    '\u{010C}', '\u{00C9}', '\u{0179}', '\u{0116}', '\u{0122}', '\u{0136}', '\u{012A}', '\u{013B}',
+    '\u{0160}', '\u{0143}', '\u{0145}', '\u{00D3}', '\u{014C}', '\u{00D5}', '\u{00D6}', '\u{00D7}',
+    '\u{0172}', '\u{0141}', '\u{015A}', '\u{016A}', '\u{00DC}', '\u{017B}', '\u{017D}', '\u{00DF}',
+    '\u{0105}', '\u{012F}', '\u{0101}', '\u{0107}', '\u{00E4}', '\u{00E5}', '\u{0119}', '\u{0113}',
+    '\u{010D}', '\u{00E9}', '\u{017A}', '\u{0117}', '\u{0123}', '\u{0137}', '\u{012B}', '\u{013C}',
+    '\u{0161}', '\u{0144}', '\u{0146}', '\u{00F3}', '\u{014D}', '\u{00F5}', '\u{00F6}', '\u{00F7}',
+    '\u{0173}', '\u{0142}', '\u{015B}', '\u{016B}', '\u{00FC}', '\u{017C}', '\u{017E}', '\u{2019}',
+];
+
+const ISO_8859_14: [char; 96] = [
+    '\u{00A0}', '\u{1E02}', '\u{1E03}', '\u{00A3}', '\u{010A}', '\u{010B}', '\u{1E0A}', '\u{00A7}',
+    '\u{1E80}', '\u{00A9}', '\u{1E82}', '\u{1E0B}', '\u{1EF2}', '\u{00AD}', '\u{00AE}', '\u{0178}',
+    '\u{1E1E}', '\u{1E1F}', '\u{0120}', '\u{0121}', '\u{1E40}', '\u{1E41}', '\u{00B6}', '\u{1E56}',
+    '\u{1E81}', '\u{1E57}', '\u{1E83}', '\u{1E60}', '\u{1EF3}', '\u{1E84}', '\u{1E85}', '\u{1E61}',
+    '\u{00C0}', '\u{00C1}', '\u{00C2}', '\u{00C3}', '\u{00C4}', '\u{00C5}', '\u{00C6}', '\u{00C7}',
+    '\u{00C8}', '\u{00C9}', '\u{00CA}', '\u{00CB}', '\u{00CC}', '\u{00CD}', '\u{00CE}', '\u{00CF}',
+    '\u{0174}', '\u{00D1}', '\u{00D2}', '\u{00D3}', '\u{00D4}', '\u{00D5}', '\u{00D6}', '\u{1E6A}',
+    '\u{00D8}', '\u{00D9}', '\u{00DA}', '\u{00DB}', '\u{00DC}', '\u{00DD}', '\u{0176}', '\u{00DF}',
+    '\u{00E0}', '\u{00E1}', '\u{00E2}', '\u{00E3}', '\u{00E4}', '\u{00E5}', '\u{00E6}', '\u{00E7}',
+    '\u{00E8}', '\u{00E9}', '\u{00EA}', '\u{00EB}', '\u{00EC}', '\u{00ED}', '\u{00EE}', '\u{00EF}',
+    '\u{0175}', '\u{00F1}', '\u{00F2}', '\u{00F3}', '\u{00F4}', '\u{00F5}', '\u{00F6}', '\u{1E6B}',
+    '\u{00F8}', '\u{00F9}', '\u{00FA}', '\u{00FB}', '\u{00FC}', '\u{00FD}', '\u{0177}', '\u{00FF}',
+];
+
+const ISO_8859_15: [char; 96] = [
+    '\u{00A0}', '\u{00A1}', '\u{00A2}', '\u{00A3}', '\u{20AC}', '\u{00A5}', '\u{0160}', '\u{00A7}',
+    '\u{0161}', '\u{00A9}', '\u{00AA}', '\u{00AB}', '\u{00AC}', '\u{00AD}', '\u{00AE}', '\u{00AF}',
+    '\u{00B0}', '\u{00B1}', '\u{00B2}', '\u{00B3}', '\u{017D}', '\u{00B5}', '\u{00B6}', '\u{00B7}',
+    '\u{017E}', '\u{00B9}', '\u{00BA}', '\u{00BB}', '\u{0152}', '\u{0153}', '\u{0178}', '\u{00BF}',
+    '\u{00C0}', '\u{00C1}', '\u{00C2}', '\u{00C3}', '\u{00C4}', '\u{00C5}', '\u{00C6}', '\u{00C7}',
+    '\u{00C8}', '\u{00C9}', '\u{00CA}', '\u{00CB}', '\u{00CC}', '\u{00CD}', '\u{00CE}', '\u{00CF}',
+    '\u{00D0}', '\u{00D1}', '\u{00D2}', '\u{00D3}', '\u{00D4}', '\u{00D5}', '\u{00D6}', '\u{00D7}',
+    '\u{00D8}', '\u{00D9}', '\u{00DA}', '\u{00DB}', '\u{00DC}', '\u{00DD}', '\u{00DE}', '\u{00DF}',
+    '\u{00E0}', '\u{00E1}', '\u{00E2}', '\u{00E3}', '\u{00E4}', '\u{00E5}', '\u{00E6}', '\u{00E7}',
+    '\u{00E8}', '\u{00E9}', '\u{00EA}', '\u{00EB}', '\u{00EC}', '\u{00ED}', '\u{00EE}', '\u{00EF}',
+    '\u{00F0}', '\u{00F1}', '\u{00F2}', '\u{00F3}', '\u{00F4}', '\u{00F5}', '\u{00F6}', '\u{00F7}',
+    '\u{00F8}', '\u{00F9}', '\u{00FA}', '\u{00FB}', '\u{00FC}', '\u{00FD}', '\u{00FE}', '\u{00FF}',
+];
+
+const ISO_8859_16: [char; 96] = [
+    '\u{00A0}', '\u{0104}', '\u{0105}', '\u{0141}', '\u{20AC}', '\u{201E}', '\u{0160}', '\u{00A7}',
+    '\u{0161}', '\u{00A9}', '\u{0218}', '\u{00AB}', '\u{0179}', '\u{00AD}', '\u{017A}', '\u{017B}',
+    '\u{00B0}', '\u{00B1}', '\u{010C}', '\u{0142}', '\u{017D}', '\u{201D}', '\u{00B6}', '\u{00B7}',
+    '\u{017E}', '\u{010D}', '\u{0219}', '\u{00BB}', '\u{0152}', '\u{0153}', '\u{0178}', '\u{017C}',
+    '\u{00C0}', '\u{00C1}', '\u{00C2}', '\u{0102}', '\u{00C4}', '\u{0106}', '\u{00C6}', '\u{00C7}',
+    '\u{00C8}', '\u{00C9}', '\u{00CA}', '\u{00CB}', '\u{00CC}', '\u{00CD}', '\u{00CE}', '\u{00CF}',
+    '\u{0110}', '\u{0143}', '\u{00D2}', '\u{00D3}', '\u{00D4}', '\u{0150}', '\u{00D6}', '\u{015A}',
+    '\u{0170}', '\u{00D9}', '\u{00DA}', '\u{00DB}', '\u{00DC}', '\u{0118}', '\u{021A}', '\u{00DF}',
+    '\u{00E0}', '\u{00E1}', '\u{00E2}', '\u{0103}', '\u{00E4}', '\u{0107}', '\u{00E6}', '\u{00E7}',
+    '\u{00E8}', '\u{00E9}', '\u{00EA}', '\u{00EB}', '\u{00EC}', '\u{00ED}', '\u{00EE}', '\u{00EF}',
+    '\u{0111}', '\u{0144}', '\u{00F2}', '\u{00F3}', '\u{00F4}', '\u{0151}', '\u{00F6}', '\u{015B}',
+    '\u{0171}', '\u{00F9}', '\u{00FA}', '\u{00FB}', '\u{00FC}', '\u{0119}', '\u{021B}', '\u{00FF}',
+];
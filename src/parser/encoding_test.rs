@@ -0,0 +1,153 @@
+use parser::encoding::*;
+
+#[test]
+fn it_trusts_a_byte_order_mark() {
+    let (text, encoding, confidence) = decode_html(b"\xEF\xBB\xBF<p>caf\xC3\xA9", &mut vec![]);
+    assert_eq!(text, "<p>café");
+    assert_eq!(encoding, Encoding::Utf8);
+    assert_eq!(confidence, Confidence::Certain);
+
+    let (text, encoding, _) = decode_html(b"\xFF\xFE<\x00p\x00>\x00\xAC\x20", &mut vec![]);
+    assert_eq!(text, "<p>€");
+    assert_eq!(encoding, Encoding::Utf16Le);
+
+    let (text, encoding, _) = decode_html(b"\xFE\xFF\x00<\x00p\x00>\xD8\x3D\xDE\x00", &mut vec![]);
+    assert_eq!(text, "<p>😀");
+    assert_eq!(encoding, Encoding::Utf16Be);
+}
+
+#[test]
+fn it_finds_a_meta_charset() {
+    let (text, encoding, confidence) = decode_html(
+        b"<!DOCTYPE html><meta charset=\"iso-8859-2\"><p>\xB1",
+        &mut vec![],
+    );
+    assert_eq!(encoding, Encoding::Iso8859(2));
+    assert_eq!(confidence, Confidence::Tentative);
+    assert!(text.ends_with("<p>ą"));
+}
+
+#[test]
+fn it_finds_a_content_type_pragma() {
+    let source = b"<head><META HTTP-EQUIV='Content-Type' CONTENT='text/html; charset=latin1'>";
+    assert_eq!(decode_html(source, &mut vec![]).1, Encoding::Windows1252);
+
+    // The content attribute only counts alongside the http-equiv pragma.
+    let source = b"<meta content='text/html; charset=latin2'><p>\xC3\xA9";
+    assert_eq!(decode_html(source, &mut vec![]).1, Encoding::Utf8);
+}
+
+#[test]
+fn it_ignores_charsets_outside_of_meta_tags() {
+    let source = b"<!-- <meta charset=latin2> --><p title='<meta charset=latin2>'>";
+    assert_eq!(decode_html(source, &mut vec![]).1, Encoding::Utf8);
+}
+
+#[test]
+fn it_treats_a_utf_16_meta_as_utf_8() {
+    assert_eq!(
+        decode_html(b"<meta charset=utf-16>", &mut vec![]).1,
+        Encoding::Utf8
+    );
+}
+
+#[test]
+fn it_falls_back_to_windows_1252_for_invalid_utf_8() {
+    let (text, encoding, confidence) = decode_html(b"<p>\x93caf\xE9\x94", &mut vec![]);
+    assert_eq!(text, "<p>\u{201C}café\u{201D}");
+    assert_eq!(encoding, Encoding::Windows1252);
+    assert_eq!(confidence, Confidence::Tentative);
+}
+
+#[test]
+fn it_looks_up_encoding_labels() {
+    assert_eq!(Encoding::for_label(" UTF8 "), Some(Encoding::Utf8));
+    assert_eq!(Encoding::for_label("ascii"), Some(Encoding::Windows1252));
+    assert_eq!(
+        Encoding::for_label("ISO_8859-15"),
+        Some(Encoding::Iso8859(15))
+    );
+    assert_eq!(Encoding::for_label("iso-8859-12"), None);
+    assert_eq!(Encoding::for_label("iso8859-16"), None);
+    for &(label, part) in &[
+        ("iso_8859-2:1987", 2),
+        ("csisolatin2", 2),
+        ("iso-ir-101", 2),
+        ("ecma-118", 7),
+        ("visual", 8),
+        ("iso-8859-8-i", 8),
+        ("csiso88598i", 8),
+        ("logical", 8),
+    ] {
+        assert_eq!(Encoding::for_label(label), Some(Encoding::Iso8859(part)));
+    }
+    assert_eq!(Encoding::for_label("shift_jis"), None);
+    assert_eq!(Encoding::Iso8859(7).to_string(), "ISO-8859-7");
+}
+
+#[test]
+fn it_reads_latin_5_and_thai_as_their_windows_supersets() {
+    assert_eq!(
+        Encoding::for_label("iso-8859-9"),
+        Some(Encoding::Windows1254)
+    );
+    assert_eq!(Encoding::for_label("latin5"), Some(Encoding::Windows1254));
+    assert_eq!(
+        Encoding::for_label("ISO8859-11"),
+        Some(Encoding::Windows874)
+    );
+    assert_eq!(Encoding::for_label("tis-620"), Some(Encoding::Windows874));
+
+    let (text, encoding, _) =
+        decode_html(b"<meta charset=iso-8859-9><p>\x80\x8E\xD0\xFD", &mut vec![]);
+    assert_eq!(encoding, Encoding::Windows1254);
+    assert!(text.ends_with("<p>€\u{8E}Ğı"));
+
+    let (text, encoding, _) = decode_html(
+        b"<meta charset=iso-8859-11><p>\x85\x81\xA1\xDB",
+        &mut vec![],
+    );
+    assert_eq!(encoding, Encoding::Windows874);
+    assert!(text.ends_with("<p>…\u{81}ก\u{FFFD}"));
+}
+
+#[test]
+fn it_names_unsupported_encodings() {
+    assert_eq!(unsupported_label(" Shift_JIS"), Some("Shift_JIS"));
+    assert_eq!(unsupported_label("x-euc-jp"), Some("EUC-JP"));
+    assert_eq!(unsupported_label("windows-1251"), Some("windows-1251"));
+    assert_eq!(unsupported_label("utf-8"), None);
+    assert_eq!(unsupported_label("nonsense"), None);
+}
+
+#[test]
+fn it_warns_when_the_prescan_finds_an_unsupported_encoding() {
+    let mut warnings = vec![];
+    let source = b"<!DOCTYPE html><meta charset='shift_jis'><meta charset=latin2><p>caf\xC3\xA9";
+    let (text, encoding, _) = decode_html(source, &mut warnings);
+    assert_eq!(encoding, Encoding::Utf8);
+    assert!(text.ends_with("<p>café"));
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].code, "unsupported-encoding");
+    assert!(warnings[0].message.contains("Shift_JIS"));
+    assert_eq!(warnings[0].span.to_string(), "1:30-1:39");
+
+    // Nothing after the declaration the prescan settled on is looked at.
+    let mut warnings = vec![];
+    let source = b"<meta charset=utf-8><meta charset=shift_jis><meta charset=euc-jp>";
+    assert_eq!(decode_html(source, &mut warnings).1, Encoding::Utf8);
+    assert!(warnings.is_empty());
+
+    // Labels the Encoding standard doesn't know are skipped without a word.
+    let source = b"<meta charset=nonsense><meta http-equiv=content-type content='charset=latin2'>";
+    assert_eq!(decode_html(source, &mut warnings).1, Encoding::Iso8859(2));
+    assert!(warnings.is_empty());
+}
+
+#[test]
+fn it_decodes_style_sheets() {
+    let (text, encoding) = decode_css(b"@charset \"latin1\"; p::before { content: \"\xE9\"; }");
+    assert_eq!(encoding, Encoding::Windows1252);
+    assert!(text.ends_with("\"é\"; }"));
+    assert_eq!(decode_css(b"p { }").1, Encoding::Utf8);
+}
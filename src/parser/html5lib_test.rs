@@ -0,0 +1,557 @@
+// Runs the vendored html5lib-tests suites from tests/html5lib-tests.
+//
+// Each fixture file prints a one line summary. Known failures are listed in
+// tests/html5lib-expected-failures/<suite>.txt; a listed case may fail, an
+// unlisted one may not, and a listed case which starts passing must be
+// removed. Run with HTML5LIB_UPDATE_EXPECTED=1 to rewrite the lists from the
+// current results.
+use dom::*;
+use json;
+use json::JsonValue;
+use parser::html_tokenizer::{self, State, Token};
+use parser::html_tree_builder::{self, TreeBuilder};
+use std::collections::BTreeSet;
+use std::env;
+use std::fs::{read_dir, File};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+const FIXTURES: &str = "tests/html5lib-tests";
+const EXPECTED_FAILURES: &str = "tests/html5lib-expected-failures";
+
+enum Outcome {
+    Pass,
+    Fail(String),
+    Skip,
+}
+
+type Case = (String, Outcome);
+
+#[test]
+fn it_passes_html5lib_tokenizer_tests() {
+    check_suite("tokenizer", "test", run_tokenizer_file);
+}
+
+#[test]
+fn it_passes_html5lib_tree_construction_tests() {
+    check_suite("tree-construction", "dat", run_tree_construction_file);
+}
+
+fn check_suite(suite: &str, extension: &str, run_file: fn(&str, &str) -> Vec<Case>) {
+    let expected_path = Path::new(EXPECTED_FAILURES).join(format!("{}.txt", suite));
+    let expected = read_expected_failures(&expected_path);
+    let mut failures = BTreeSet::new();
+    let mut unexpected_failures = vec![];
+    let mut unexpected_passes = vec![];
+
+    for path in fixture_files(suite, extension) {
+        let file_name = path.file_name().unwrap().to_str().unwrap().to_string();
+        let cases = run_file(&file_name, &read_file(&path));
+        let (mut passed, mut failed, mut known, mut skipped) = (0, 0, 0, 0);
+        for (id, outcome) in cases {
+            match outcome {
+                Outcome::Pass => {
+                    passed += 1;
+                    if expected.contains(&id) {
+                        unexpected_passes.push(id);
+                    }
+                }
+                Outcome::Fail(details) => {
+                    failed += 1;
+                    if expected.contains(&id) {
+                        known += 1;
+                    } else {
+                        unexpected_failures.push(format!("{}\n{}", id, details));
+                    }
+                    failures.insert(id);
+                }
+                Outcome::Skip => skipped += 1,
+            }
+        }
+        println!(
+            "{}: {} passed, {} failed ({} expected), {} skipped",
+            file_name, passed, failed, known, skipped
+        );
+    }
+
+    if env::var("HTML5LIB_UPDATE_EXPECTED").is_ok() {
+        write_expected_failures(&expected_path, &failures);
+        return;
+    }
+    for failure in &unexpected_failures {
+        println!("Unexpected failure: {}\n", failure);
+    }
+    for pass in &unexpected_passes {
+        println!("Unexpected pass, remove from expected failures: {}", pass);
+    }
+    assert!(
+        unexpected_failures.is_empty() && unexpected_passes.is_empty(),
+        "{} unexpected failures and {} unexpected passes",
+        unexpected_failures.len(),
+        unexpected_passes.len()
+    );
+}
+
+fn fixture_files(suite: &str, extension: &str) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = read_dir(Path::new(FIXTURES).join(suite))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|e| e == extension))
+        .collect();
+    paths.sort();
+    paths
+}
+
+fn read_file(path: &Path) -> String {
+    let mut content = String::new();
+    File::open(path)
+        .unwrap()
+        .read_to_string(&mut content)
+        .unwrap();
+    content
+}
+
+fn read_expected_failures(path: &Path) -> BTreeSet<String> {
+    if !path.exists() {
+        return BTreeSet::new();
+    }
+    read_file(path)
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect()
+}
+
+fn write_expected_failures(path: &Path, failures: &BTreeSet<String>) {
+    let mut content = String::from(
+        "# html5lib-tests cases which are known to fail, as <file>:<index of the test in the file>.\n",
+    );
+    for failure in failures {
+        content.push_str(failure);
+        content.push('\n');
+    }
+    File::create(path)
+        .unwrap()
+        .write_all(content.as_bytes())
+        .unwrap();
+}
+
+// Tokenizer tests
+
+fn run_tokenizer_file(file_name: &str, content: &str) -> Vec<Case> {
+    let tests = json::parse(content).unwrap();
+    let key = if tests.has_key("tests") {
+        "tests"
+    } else {
+        "xmlViolationTests"
+    };
+    tests[key]
+        .members()
+        .enumerate()
+        .map(|(index, test)| (format!("{}:{}", file_name, index), run_tokenizer_test(test)))
+        .collect()
+}
+
+fn run_tokenizer_test(test: &JsonValue) -> Outcome {
+    let double_escaped = test["doubleEscaped"].as_bool().unwrap_or(false);
+    let input = match unescape(test["input"].as_str().unwrap(), double_escaped) {
+        Some(input) => input,
+        // Lone surrogates can not be represented in a Rust string.
+        None => return Outcome::Skip,
+    };
+    let expected = match expected_tokens(&test["output"], double_escaped) {
+        Some(expected) => expected,
+        None => return Outcome::Skip,
+    };
+    let states: Vec<&str> = if test["initialStates"].is_array() {
+        test["initialStates"]
+            .members()
+            .map(|state| state.as_str().unwrap())
+            .collect()
+    } else {
+        vec!["Data state"]
+    };
+
+    for state_name in states {
+        let state = match state_name {
+            "Data state" => State::Data,
+            "PLAINTEXT state" => State::Plaintext,
+            "RCDATA state" => State::Rcdata,
+            "RAWTEXT state" => State::Rawtext,
+            "Script data state" => State::ScriptData,
+            "CDATA section state" => State::CdataSection,
+            _ => return Outcome::Fail(format!("Unknown initial state {}", state_name)),
+        };
+        // The same tokens must come out when the input is fed a character
+        // at a time.
+        for &streaming in &[false, true] {
+            let mut tokenizer = if streaming {
+                html_tokenizer::create_streaming()
+            } else {
+                html_tokenizer::create(input.clone())
+            };
+            tokenizer.set_state(state);
+            if let Some(name) = test["lastStartTag"].as_str() {
+                tokenizer.set_last_start_tag_name(name);
+            }
+            let mut actual = token_list();
+            if streaming {
+                for c in input.chars() {
+                    tokenizer.feed(c.encode_utf8(&mut [0; 4]));
+                    while let Some((token, _)) = tokenizer.next_token() {
+                        collect_token(token, &mut actual);
+                    }
+                }
+                tokenizer.finish();
+            }
+            while let Some((token, _)) = tokenizer.next_token() {
+                if token == Token::Eof {
+                    break;
+                }
+                collect_token(token, &mut actual);
+            }
+            let actual = actual.finish();
+            if actual != expected {
+                return Outcome::Fail(format!(
+                    "  description: {}\n  state: {}\n  streaming: {}\n  input: {:?}\n  expected: {:#?}\n  actual: {:#?}",
+                    test["description"], state_name, streaming, input, expected, actual
+                ));
+            }
+        }
+    }
+    Outcome::Pass
+}
+
+fn collect_token(token: Token, tokens: &mut TokenList) {
+    match token {
+        Token::Characters(data) | Token::CData(data) => tokens.push_characters(&data),
+        token => tokens.push(describe_token(&token)),
+    }
+}
+
+fn describe_token(token: &Token) -> String {
+    match token {
+        Token::Doctype(doctype) => format!(
+            "DOCTYPE {:?} {:?} {:?} {}",
+            doctype.name, doctype.public_id, doctype.system_id, !doctype.force_quirks
+        ),
+        Token::StartTag(tag) => {
+            let mut attributes: Vec<(&str, &str)> = tag
+                .attributes
+                .iter()
+                .map(|attribute| (attribute.name.as_str(), attribute.value.as_str()))
+                .collect();
+            attributes.sort();
+            format!(
+                "StartTag {:?} {:?} {}",
+                tag.name, attributes, tag.self_closing
+            )
+        }
+        Token::EndTag(tag) => format!("EndTag {:?}", tag.name),
+        Token::Comment(data) => format!("Comment {:?}", data),
+        Token::ProcessingInstruction(instruction) => format!(
+            "ProcessingInstruction {:?} {:?}",
+            instruction.target, instruction.data
+        ),
+        Token::Characters(data) | Token::CData(data) => format!("Character {:?}", data),
+        Token::Eof => String::from("EOF"),
+    }
+}
+
+// Parse errors are not compared, only the tokens.
+fn expected_tokens(output: &JsonValue, double_escaped: bool) -> Option<Vec<String>> {
+    let mut tokens = token_list();
+    for token in output.members() {
+        if token.is_string() {
+            continue;
+        }
+        let text = |value: &JsonValue| unescape(value.as_str().unwrap(), double_escaped);
+        let optional = |value: &JsonValue| {
+            if value.is_null() {
+                Some(None)
+            } else {
+                text(value).map(Some)
+            }
+        };
+        let description = match token[0].as_str().unwrap() {
+            "DOCTYPE" => format!(
+                "DOCTYPE {:?} {:?} {:?} {}",
+                optional(&token[1])?,
+                optional(&token[2])?,
+                optional(&token[3])?,
+                token[4].as_bool().unwrap()
+            ),
+            "StartTag" => {
+                let mut attributes = vec![];
+                for (name, value) in token[2].entries() {
+                    attributes.push((unescape(name, double_escaped)?, text(value)?));
+                }
+                attributes.sort();
+                let attributes: Vec<(&str, &str)> = attributes
+                    .iter()
+                    .map(|(name, value)| (name.as_str(), value.as_str()))
+                    .collect();
+                format!(
+                    "StartTag {:?} {:?} {}",
+                    text(&token[1])?,
+                    attributes,
+                    token[3].as_bool().unwrap_or(false)
+                )
+            }
+            "EndTag" => format!("EndTag {:?}", text(&token[1])?),
+            "Comment" => format!("Comment {:?}", text(&token[1])?),
+            "Character" => {
+                tokens.push_characters(&text(&token[1])?);
+                continue;
+            }
+            other => panic!("Unknown token type {}", other),
+        };
+        tokens.push(description);
+    }
+    Some(tokens.finish())
+}
+
+// Adjacent character tokens are compared as one token.
+struct TokenList {
+    tokens: Vec<String>,
+    characters: String,
+}
+
+impl TokenList {
+    fn push(&mut self, token: String) {
+        self.flush_characters();
+        self.tokens.push(token);
+    }
+
+    fn push_characters(&mut self, data: &str) {
+        self.characters.push_str(data);
+    }
+
+    fn flush_characters(&mut self) {
+        if !self.characters.is_empty() {
+            let token = format!("Character {:?}", self.characters);
+            self.tokens.push(token);
+            self.characters.clear();
+        }
+    }
+
+    fn finish(mut self) -> Vec<String> {
+        self.flush_characters();
+        self.tokens
+    }
+}
+
+fn token_list() -> TokenList {
+    TokenList {
+        tokens: vec![],
+        characters: String::new(),
+    }
+}
+
+fn unescape(text: &str, double_escaped: bool) -> Option<String> {
+    if !double_escaped {
+        return Some(String::from(text));
+    }
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some(index) = rest.find("\\u") {
+        result.push_str(&rest[..index]);
+        let code = u32::from_str_radix(rest.get(index + 2..index + 6)?, 16).ok()?;
+        result.push(std::char::from_u32(code)?);
+        rest = &rest[index + 6..];
+    }
+    result.push_str(rest);
+    Some(result)
+}
+
+// Tree construction tests
+
+struct TreeTest {
+    data: String,
+    fragment_context: Option<String>,
+    scripting: Option<bool>,
+    document: String,
+}
+
+fn run_tree_construction_file(file_name: &str, content: &str) -> Vec<Case> {
+    parse_tree_tests(content)
+        .iter()
+        .enumerate()
+        .map(|(index, test)| {
+            (
+                format!("{}:{}", file_name, index),
+                run_tree_construction_test(test),
+            )
+        })
+        .collect()
+}
+
+fn parse_tree_tests(content: &str) -> Vec<TreeTest> {
+    let content = String::from("\n") + content;
+    content
+        .split("\n#data\n")
+        .skip(1)
+        .map(|test| {
+            let mut data = vec![];
+            let mut fragment_context = None;
+            let mut scripting = None;
+            let mut document = vec![];
+            let mut section = "#data";
+            for line in test.lines() {
+                if section == "#document" {
+                    document.push(line);
+                    continue;
+                }
+                match line {
+                    "#errors" | "#new-errors" | "#document-fragment" | "#document" => {
+                        section = line;
+                    }
+                    "#script-on" => scripting = Some(true),
+                    "#script-off" => scripting = Some(false),
+                    _ => match section {
+                        "#data" => data.push(line),
+                        "#document-fragment" => fragment_context = Some(String::from(line)),
+                        _ => {}
+                    },
+                }
+            }
+            TreeTest {
+                data: data.join("\n"),
+                fragment_context,
+                scripting,
+                document: document.join("\n").trim_end_matches('\n').to_string(),
+            }
+        })
+        .collect()
+}
+
+fn run_tree_construction_test(test: &TreeTest) -> Outcome {
+    let scripting = test.scripting == Some(true);
+    let create_builder = |tokenizer| match test.fragment_context {
+        Some(ref context) => {
+            let (namespace, name) = match context.split_once(' ') {
+                Some(("svg", name)) => (Namespace::Svg, name),
+                Some(("math", name)) => (Namespace::MathMl, name),
+                _ => (Namespace::Html, context.as_str()),
+            };
+            html_tree_builder::create_fragment(tokenizer, scripting, namespace, name)
+        }
+        None => html_tree_builder::create(tokenizer, scripting),
+    };
+    let finish = |builder: TreeBuilder| match test.fragment_context {
+        Some(_) => dump_nodes(&builder.into_fragment(&mut vec![])),
+        None => dump_nodes(&builder.into_document(&mut vec![]).children),
+    };
+
+    let mut builder = create_builder(html_tokenizer::create(test.data.clone()));
+    builder.finish();
+    let actual = finish(builder);
+
+    // Feeding the input a character at a time must build the same tree.
+    let mut builder = create_builder(html_tokenizer::create_streaming());
+    for c in test.data.chars() {
+        builder.feed(c.encode_utf8(&mut [0; 4]));
+    }
+    builder.finish();
+    let streamed = finish(builder);
+    if streamed != actual {
+        return Outcome::Fail(format!(
+            "  data: {:?}\n  parsed:\n{}\n  streamed:\n{}",
+            test.data, actual, streamed
+        ));
+    }
+
+    if actual == test.document {
+        Outcome::Pass
+    } else {
+        Outcome::Fail(format!(
+            "  data: {:?}\n  expected:\n{}\n  actual:\n{}",
+            test.data, test.document, actual
+        ))
+    }
+}
+
+fn dump_nodes(nodes: &[Node]) -> String {
+    let mut lines = vec![];
+    for node in nodes {
+        dump_node(node, 0, &mut lines);
+    }
+    lines.join("\n")
+}
+
+fn dump_node(node: &Node, depth: usize, lines: &mut Vec<String>) {
+    let indent = |depth: usize| String::from("| ") + &" ".repeat(depth * 2);
+    match &node.node_type {
+        NodeType::Document(_) => {}
+        NodeType::DocType(doctype) => {
+            if doctype.public_id.is_empty() && doctype.system_id.is_empty() {
+                lines.push(format!("{}<!DOCTYPE {}>", indent(depth), doctype.name));
+            } else {
+                lines.push(format!(
+                    "{}<!DOCTYPE {} \"{}\" \"{}\">",
+                    indent(depth),
+                    doctype.name,
+                    doctype.public_id,
+                    doctype.system_id
+                ));
+            }
+        }
+        NodeType::Comment(data) => lines.push(format!("{}<!-- {} -->", indent(depth), data)),
+        NodeType::ProcessingInstruction(instruction) => lines.push(format!(
+            "{}<?{} {}>",
+            indent(depth),
+            instruction.target,
+            instruction.data
+        )),
+        NodeType::Text(data) => lines.push(format!("{}\"{}\"", indent(depth), data)),
+        NodeType::Element(element) => {
+            let name = match element.namespace {
+                Namespace::Html => element.tag_name.clone(),
+                namespace => format!("{} {}", namespace, element.tag_name),
+            };
+            lines.push(format!("{}<{}>", indent(depth), name));
+            let mut attributes: Vec<(String, &String)> = element
+                .attributes
+                .iter()
+                .map(|(name, value)| (dump_attribute_name(element, name), value))
+                .collect();
+            attributes.sort();
+            for (name, value) in attributes {
+                lines.push(format!("{}{}=\"{}\"", indent(depth + 1), name, value));
+            }
+            if element.namespace == Namespace::Html && element.tag_name == "template" {
+                lines.push(format!("{}content", indent(depth + 1)));
+                for child in &node.children {
+                    dump_node(child, depth + 2, lines);
+                }
+                return;
+            }
+        }
+    }
+    for child in &node.children {
+        dump_node(child, depth + 1, lines);
+    }
+}
+
+// Prefixed attributes of foreign elements are in their own namespace, which
+// the expected trees separate from the name with a space.
+fn dump_attribute_name(element: &ElementData, name: &str) -> String {
+    const NAMESPACED: &[&str] = &[
+        "xlink:actuate",
+        "xlink:arcrole",
+        "xlink:href",
+        "xlink:role",
+        "xlink:show",
+        "xlink:title",
+        "xlink:type",
+        "xml:lang",
+        "xml:space",
+        "xmlns:xlink",
+    ];
+    if element.namespace != Namespace::Html && NAMESPACED.contains(&name) {
+        name.replacen(':', " ", 1)
+    } else {
+        String::from(name)
+    }
+}
@@ -1,245 +1,213 @@
 use dom::*;
-use parser;
+use parser::diagnostic::{self, Diagnostic};
+use parser::encoding::{self, Confidence, Encoding, HtmlDecoder};
+use parser::html_tokenizer::{self, State, Token, Tokenizer};
+use parser::html_tree_builder::{self, TreeBuilder};
+use parser::Span;
 
-pub fn parse(html: String, warnings: &mut Vec<String>) -> Vec<Node> {
-    let mut parser = HTMLParser {
-        parser: parser::parser::create(html),
-        stack: vec![],
-    };
-    parser.parse_nodes(warnings)
+pub use parser::html_tokenizer::{Attribute, Doctype, ProcessingInstruction, Tag};
+pub use parser::html_tree_builder::{NodeId, NodeRef};
+
+pub fn parse(html: String, warnings: &mut Vec<Diagnostic>) -> Node {
+    html_tree_builder::build(html_tokenizer::create(html), false, warnings)
 }
 
-struct HTMLParser {
-    parser: parser::parser::Parser,
-    stack: Vec<String>,
+/// Parses a document in XML compatibility mode, where `<?target data?>` is
+/// kept as a processing instruction node instead of becoming a bogus
+/// comment, for XHTML documents such as those starting with `<?xml ...?>`.
+pub fn parse_xml_compatible(html: String, warnings: &mut Vec<Diagnostic>) -> Node {
+    let mut tokenizer = html_tokenizer::create(html);
+    tokenizer.set_xml_compatible(true);
+    html_tree_builder::build(tokenizer, false, warnings)
 }
 
-impl HTMLParser {
-    fn parse_nodes(&mut self, warnings: &mut Vec<String>) -> Vec<Node> {
-        let mut nodes = vec![];
-        loop {
-            self.parser.consume_whitespace();
-            if self.parser.eof() || self.parser.starts_with("</") {
-                break;
-            }
-            match self.parse_node(warnings) {
-                Ok(node) => nodes.push(node),
-                Err(err) => warnings.push(format!("{} - {}", self.parser.position(), err)),
-            }
-        }
-        return nodes;
+/// Parses markup as the contents of an element named `context_tag`, the way
+/// `innerHTML` does, so `<td>` cells can be parsed in the context of a `tr`.
+/// The context is an HTML element, unless it is `svg` or `math`. Its name is
+/// matched ignoring ASCII case, like a tag name in markup, and is an error
+/// if it is empty or has characters which would end a tag name.
+pub fn parse_fragment(
+    html: String,
+    context_tag: &str,
+    warnings: &mut Vec<Diagnostic>,
+) -> Result<Vec<Node>, Diagnostic> {
+    if context_tag.is_empty() || context_tag.contains(is_tag_name_end) {
+        return Err(diagnostic::error(
+            "invalid-context-element",
+            Span::default(),
+            &format!("'{}' is not a valid element name", context_tag),
+        ));
     }
+    let context_tag = context_tag.to_ascii_lowercase();
+    let namespace = match context_tag.as_str() {
+        "svg" => Namespace::Svg,
+        "math" => Namespace::MathMl,
+        _ => Namespace::Html,
+    };
+    let mut builder = html_tree_builder::create_fragment(
+        html_tokenizer::create(html),
+        false,
+        namespace,
+        &context_tag,
+    );
+    builder.finish();
+    Ok(builder.into_fragment(warnings))
+}
 
-    fn parse_node(&mut self, warnings: &mut Vec<String>) -> Result<Node, String> {
-        match self.parser.next_char() {
-            '<' => {
-                if self.parser.starts_with("<!--") {
-                    self.parse_comment()
-                } else if self.parser.starts_with("<![CDATA[") {
-                    self.parse_cdata()
-                } else if self.parser.starts_with("<!") {
-                    self.parse_doctype()
-                } else {
-                    self.parse_element(warnings)
-                }
-            }
-            _ => self.parse_text(),
-        }
-    }
+fn is_tag_name_end(c: char) -> bool {
+    matches!(c, '\t' | '\n' | '\x0C' | '\r' | ' ' | '/' | '>')
+}
 
-    fn parse_text(&mut self) -> Result<Node, String> {
-        let text_value = self.parser.consume_while(|c| match c {
-            '<' => false,
-            _ => true,
-        });
-        Ok(text(text_value))
-    }
+/// Parses a document pushed in as chunks of bytes. Chunks may end anywhere,
+/// including inside a tag, a character reference or a multi-byte character.
+pub struct StreamingParser {
+    decoder: HtmlDecoder,
+    builder: TreeBuilder,
+    // Warnings from choosing the encoding.
+    warnings: Vec<Diagnostic>,
+}
 
-    fn parse_doctype(&mut self) -> Result<Node, String> {
-        match self.parser.consume_expected_text("<!DOCTYPE") {
-            Err(e) => return Err(e),
-            _ => {}
-        }
-        self.parser.consume_whitespace();
-        let version = self.parser.consume_alphanumeric_word();
-        self.parser.consume_whitespace();
-        match self.parser.consume_expected_text(">") {
-            Err(e) => return Err(e),
-            _ => {}
-        }
-        Ok(doctype(version))
+pub fn create_streaming() -> StreamingParser {
+    StreamingParser {
+        decoder: encoding::create_html_decoder(),
+        builder: html_tree_builder::create(html_tokenizer::create_streaming(), false),
+        warnings: vec![],
     }
+}
 
-    fn parse_comment(&mut self) -> Result<Node, String> {
-        match self.parser.consume_expected_text("<!--") {
-            Err(e) => return Err(e),
-            _ => {}
-        };
-        let mut comment_text = String::new();
-        loop {
-            let partial = self.parser.consume_while(|c| match c {
-                '-' => false,
-                _ => true,
-            });
-            comment_text.push_str(partial.as_str());
-            if self.parser.eof() || self.parser.starts_with("-->") {
-                break;
-            } else {
-                comment_text.push(self.parser.consume_char());
-            }
-        }
-        match self.parser.consume_expected_text("-->") {
-            Err(e) => return Err(e),
-            _ => {}
-        };
-        Ok(comment(comment_text))
+impl StreamingParser {
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.decode(bytes, false);
     }
 
-    fn parse_cdata(&mut self) -> Result<Node, String> {
-        match self.parser.consume_expected_text("<![CDATA[") {
-            Err(e) => return Err(e),
-            _ => {}
-        }
-        let mut comment = String::new();
-        loop {
-            let partial = self.parser.consume_while(|c| match c {
-                ']' => false,
-                _ => true,
-            });
-            comment.push_str(partial.as_str());
-            if self.parser.eof() || self.parser.starts_with("]]>") {
-                break;
-            } else {
-                comment.push(self.parser.consume_char());
-            }
-        }
-        match self.parser.consume_expected_text("]]>") {
-            Err(e) => return Err(e),
-            _ => {}
-        }
-        Ok(cdata(comment))
+    /// Ends the input, processing whatever was held back for it.
+    pub fn finish(&mut self) {
+        self.decode(&[], true);
+        self.builder.finish();
     }
 
-    fn parse_element(&mut self, warnings: &mut Vec<String>) -> Result<Node, String> {
-        match self.parser.consume_expected_text("<") {
-            Err(e) => return Err(e),
-            _ => {}
-        }
-        let (tag_name, attributes, has_closed_self) = match self.parse_tag() {
-            Ok((tag_name, attributes, self_closed)) => (tag_name, attributes, self_closed),
-            Err(e) => return Err(e),
-        };
-        let closed = has_closed_self || self.is_self_closing(tag_name.as_str());
-        let mut children = vec![];
-        if !closed {
-            self.stack.push(tag_name.clone());
-            children = self.parse_nodes(warnings);
-            self.stack.pop();
-            match self.consume_closing_tag(tag_name.as_str()) {
-                Err(e) => return Err(e),
-                _ => {}
-            }
-        }
-        Ok(element(tag_name, attributes, children))
+    /// The encoding the input is being decoded from, once enough of it has
+    /// arrived to tell.
+    pub fn encoding(&self) -> Option<(Encoding, Confidence)> {
+        self.decoder.encoding()
     }
 
-    fn parse_tag(&mut self) -> Result<(String, AttrMap, bool), String> {
-        let tag_name = self.parse_tag_name();
-        let attributes = match self.parse_attributes() {
-            Ok(atts) => atts,
-            Err(e) => return Err(e),
-        };
+    pub fn into_document(mut self, warnings: &mut Vec<Diagnostic>) -> Node {
+        warnings.append(&mut self.warnings);
+        self.builder.into_document(warnings)
+    }
 
-        self.parser.consume_whitespace();
-        let has_closed_self = self.parser.starts_with("/");
-        let ending_len = if has_closed_self { 2 } else { 1 };
-        let ending = self.parser.consume_next_n_chars(ending_len);
-        if ending.ends_with(">") {
-            Ok((tag_name, attributes, has_closed_self))
-        } else {
-            Err(format!("Expected end of tag but found: {}", ending))
-        }
+    /// Returns the elements closed since the last call, in the order they
+    /// were closed, for looking up with `node`.
+    pub fn take_completed(&mut self) -> Vec<NodeId> {
+        self.builder.take_completed()
     }
 
-    fn parse_tag_name(&mut self) -> String {
-        self.parser.consume_whitespace();
-        self.parser.consume_alphanumeric_word()
+    /// A node parsed so far. Its descendants include any elements returned
+    /// by earlier calls to `take_completed`.
+    pub fn node(&self, id: NodeId) -> NodeRef<'_> {
+        self.builder.node(id)
     }
 
-    fn parse_attributes(&mut self) -> Result<AttrMap, String> {
-        let mut attrs = AttrMap::new();
-        loop {
-            self.parser.consume_whitespace();
-            if self.parser.next_char() == '>' || self.parser.next_char() == '/' {
-                break;
-            }
-            let name = self.parse_attribute_name();
-            let next_char = self.parser.consume_char();
-            if next_char != '=' {
-                return Err(format!(
-                    "Unexpected char in attribute parsing Expected: = found: {}",
-                    next_char
-                ));
-            }
-            let value = match self.parse_attribute_value() {
-                Ok(v) => v,
-                Err(e) => return Err(e),
-            };
-            attrs.insert(name, value);
-        }
-        Ok(attrs)
+    fn decode(&mut self, bytes: &[u8], last: bool) {
+        let text = self.decoder.decode(bytes, last, &mut self.warnings);
+        self.builder.feed(&text);
     }
+}
+
+/// A piece of markup, as seen by the tokenizer before any tree building.
+/// Text is not merged across character references, so neighbouring `Text`
+/// events may need joining.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    StartTag(Tag),
+    EndTag(Tag),
+    Text(String),
+    Comment(String),
+    Doctype(Doctype),
+    CData(String),
+    ProcessingInstruction(ProcessingInstruction),
+}
 
-    fn parse_attribute_name(&mut self) -> String {
-        self.parser.consume_while(|c| match c {
-            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => true,
-            _ => false,
-        })
+/// Iterates over the markup of a document without building a tree, for
+/// extracting links or text with little memory.
+pub struct Events {
+    tokenizer: Tokenizer,
+    // How many svg and math elements are open, as CDATA sections are only
+    // recognised inside them and raw text elements are not raw there.
+    foreign_depth: usize,
+}
+
+pub fn events(html: String) -> Events {
+    Events {
+        tokenizer: html_tokenizer::create(html),
+        foreign_depth: 0,
     }
+}
 
-    fn parse_attribute_value(&mut self) -> Result<String, String> {
-        let first_char = self.parser.consume_char();
-        if first_char != '"' && first_char != '\'' {
-            return Err(format!(
-                "Expected opening of attribute value but found: {}",
-                first_char
-            ));
-        }
+impl Events {
+    /// The tokenizer errors found so far.
+    pub fn take_errors(&mut self) -> Vec<Diagnostic> {
+        self.tokenizer.take_errors()
+    }
 
-        let value = self.parser.consume_while(|c| c != first_char);
-        self.parser.consume_char();
-        Ok(value)
+    /// Reports processing instructions as such rather than as comments.
+    pub fn set_xml_compatible(&mut self, xml_compatible: bool) {
+        self.tokenizer.set_xml_compatible(xml_compatible);
     }
 
-    fn consume_closing_tag(&mut self, tag_name: &str) -> Result<(), String> {
-        self.parser.set_save_point();
-        match self.parser.consume_expected_text("</") {
-            Err(e) => return Err(e),
-            _ => {}
-        }
-        let closing_tag_name = self.parse_tag_name();
-        self.parser.consume_whitespace();
-        match self.parser.consume_expected_text(">") {
-            Err(e) => return Err(e),
-            _ => {}
-        }
-        if closing_tag_name == tag_name {
-            return Ok(());
-        }
-        for parent_tag_name in &self.stack {
-            if &closing_tag_name == parent_tag_name {
-                self.parser.restore_from_save_point();
-                return Ok(());
+    // The tree builder normally switches the tokenizer into the text states,
+    // which decide where elements such as script and title end.
+    fn start_tag(&mut self, tag: &Tag) {
+        if tag.name == "svg" || tag.name == "math" {
+            if !tag.self_closing {
+                self.foreign_depth += 1;
+                self.tokenizer.set_allow_cdata(true);
             }
+            return;
         }
-        Err(format!("Expected closing tag for: {} but found closing tag for: {} which is not in the stack: {:?}",
-                tag_name, closing_tag_name, self.stack))
+        if self.foreign_depth > 0 || tag.self_closing {
+            return;
+        }
+        let state = match tag.name.as_str() {
+            "title" | "textarea" => State::Rcdata,
+            "style" | "xmp" | "iframe" | "noembed" | "noframes" => State::Rawtext,
+            "script" => State::ScriptData,
+            "plaintext" => State::Plaintext,
+            _ => return,
+        };
+        self.tokenizer.set_state(state);
     }
 
-    fn is_self_closing(&self, tag_name: &str) -> bool {
-        match tag_name {
-            "link" | "meta" => true,
-            _ => false,
+    fn end_tag(&mut self, tag: &Tag) {
+        if (tag.name == "svg" || tag.name == "math") && self.foreign_depth > 0 {
+            self.foreign_depth -= 1;
+            self.tokenizer.set_allow_cdata(self.foreign_depth > 0);
         }
     }
 }
+
+impl Iterator for Events {
+    type Item = (Event, Span);
+
+    fn next(&mut self) -> Option<(Event, Span)> {
+        let (token, span) = self.tokenizer.next_token()?;
+        let event = match token {
+            Token::StartTag(tag) => {
+                self.start_tag(&tag);
+                Event::StartTag(tag)
+            }
+            Token::EndTag(tag) => {
+                self.end_tag(&tag);
+                Event::EndTag(tag)
+            }
+            Token::Characters(text) => Event::Text(text),
+            Token::Comment(text) => Event::Comment(text),
+            Token::Doctype(doctype) => Event::Doctype(doctype),
+            Token::CData(text) => Event::CData(text),
+            Token::ProcessingInstruction(instruction) => Event::ProcessingInstruction(instruction),
+            Token::Eof => return None,
+        };
+        Some((event, span))
+    }
+}
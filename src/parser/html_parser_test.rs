@@ -1,5 +1,8 @@
 use dom::*;
-use parser::html_parser;
+use parser::diagnostic::Diagnostic;
+use parser::encoding::{self, Confidence, Encoding};
+use parser::html_parser::{self, Event, NodeRef};
+use parser::Span;
 use std::fs::read_dir;
 use std::fs::File;
 use std::io::prelude::*;
@@ -10,17 +13,17 @@ fn it_parses_element() {
     let node = get_nth_child("<test></test>".to_string(), 0);
     match node.node_type {
         NodeType::Element(e) => assert_eq!(e.tag_name, "test"),
-        _ => assert!(false, "Wrong node type"),
+        _ => panic!("Wrong node type"),
     }
 }
 
 #[test]
 fn it_parses_child() {
     let node = get_nth_child("<test><child></child></test>".to_string(), 0);
-    let ref first_child = node.children[0];
+    let first_child = &node.children[0];
     match &first_child.node_type {
-        &NodeType::Element(ref e) => assert_eq!(e.tag_name, "child"),
-        _ => assert!(false, "Wrong node type"),
+        NodeType::Element(e) => assert_eq!(e.tag_name, "child"),
+        _ => panic!("Wrong node type"),
     }
 }
 
@@ -30,20 +33,20 @@ fn it_parses_siblings() {
         "<test><child></child><child2></child2></test>".to_string(),
         0,
     );
-    let ref second_child = node.children[1];
+    let second_child = &node.children[1];
     match &second_child.node_type {
-        &NodeType::Element(ref e) => assert_eq!(e.tag_name, "child2"),
-        _ => assert!(false, "Wrong node type"),
+        NodeType::Element(e) => assert_eq!(e.tag_name, "child2"),
+        _ => panic!("Wrong node type"),
     }
 }
 
 #[test]
 fn it_parse_text_node() {
     let node = get_nth_child("<h1>hello world</h1>".to_string(), 0);
-    let ref first_child = node.children[0];
+    let first_child = &node.children[0];
     match &first_child.node_type {
-        &NodeType::Text(ref c) => assert_eq!(c, "hello world"),
-        _ => assert!(false, "Wrong node type"),
+        NodeType::Text(c) => assert_eq!(c, "hello world"),
+        _ => panic!("Wrong node type"),
     }
 }
 
@@ -58,7 +61,7 @@ fn it_parses_attributes() {
             };
             assert_eq!(id, "title");
         }
-        _ => assert!(false, "Wrong node type"),
+        _ => panic!("Wrong node type"),
     }
 }
 
@@ -72,130 +75,1004 @@ fn it_parses_attributes_with_quotes() {
         NodeType::Element(e) => {
             match e.attributes.get("title") {
                 Some(v) => assert_eq!(v, "here is - 'something'"),
-                None => assert!(false, "No title!"),
+                None => panic!("No title!"),
             };
         }
-        _ => assert!(false, "Wrong node type"),
+        _ => panic!("Wrong node type"),
     }
 }
 
 #[test]
 fn it_parses_doctype() {
-    let node = get_nth_child("<!DOCTYPE html>".to_string(), 0);
-    match node.node_type {
-        NodeType::DocType(ref e) => assert_eq!(e.version, "html"),
-        _ => assert!(false, "Wrong node type"),
+    let (document, warnings) = parse_document("<!DOCTYPE html>".to_string());
+    assert_eq!(warnings.len(), 0);
+    match document.children[0].node_type {
+        NodeType::DocType(ref e) => assert_eq!(e.name, "html"),
+        _ => panic!("Wrong node type"),
     }
 }
 
 #[test]
 fn it_parses_document_as_doctype_sibling() {
-    let node = get_nth_child("<!DOCTYPE html><html></html>".to_string(), 1);
-    match node.node_type {
+    let (document, warnings) = parse_document("<!DOCTYPE html><html></html>".to_string());
+    assert_eq!(warnings.len(), 0);
+    match document.children[1].node_type {
         NodeType::Element(ref e) => assert_eq!(e.tag_name, "html"),
-        _ => assert!(false, "Wrong node type"),
+        _ => panic!("Wrong node type"),
     }
 }
 
 #[test]
 fn it_parses_self_closing_link() {
-    let node = get_nth_child("<head><link></head>".to_string(), 0);
-    let ref link = node.children[0];
+    let node = get_head("<head><link></head>".to_string());
+    let link = &node.children[0];
     match &link.node_type {
-        &NodeType::Element(ref e) => assert_eq!(e.tag_name, "link"),
-        _ => assert!(false, "Wrong node type"),
+        NodeType::Element(e) => assert_eq!(e.tag_name, "link"),
+        _ => panic!("Wrong node type"),
     }
 }
 
 #[test]
 fn it_parses_self_closing_meta() {
-    let node = get_nth_child("<head><meta></head>".to_string(), 0);
-    let ref link = node.children[0];
+    let node = get_head("<head><meta></head>".to_string());
+    let link = &node.children[0];
     match &link.node_type {
-        &NodeType::Element(ref e) => assert_eq!(e.tag_name, "meta"),
-        _ => assert!(false, "Wrong node type"),
+        NodeType::Element(e) => assert_eq!(e.tag_name, "meta"),
+        _ => panic!("Wrong node type"),
     }
 }
 
 #[test]
 fn it_does_not_include_next_inside_self_closing() {
-    let node = get_nth_child("<meta><link>".to_string(), 1);
-    match node.node_type {
-        NodeType::Element(e) => assert_eq!(e.tag_name, "link"),
-        _ => assert!(false, "Wrong node type"),
-    }
+    let node = get_head("<meta><link>".to_string());
+    assert_eq!(tag_names(&node.children), vec!["meta", "link"]);
+    assert_eq!(node.children[0].children.len(), 0);
 }
 
 #[test]
 fn it_parses_comments() {
-    let node = get_nth_child("<!-- hello world -->".to_string(), 0);
-    match node.node_type {
+    let node = get_nth_child("<p><!-- hello world --></p>".to_string(), 0);
+    match &node.children[0].node_type {
         NodeType::Comment(c) => assert_eq!(c, " hello world "),
-        _ => assert!(false, "Wrong node type"),
+        _ => panic!("Wrong node type"),
     }
 }
 
 #[test]
 fn it_parses_coments_with_dashes() {
-    let node = get_nth_child("<!-- hello - world -->".to_string(), 0);
-    match node.node_type {
+    let node = get_nth_child("<p><!-- hello - world --></p>".to_string(), 0);
+    match &node.children[0].node_type {
         NodeType::Comment(c) => assert_eq!(c, " hello - world "),
-        _ => assert!(false, "Wrong node type"),
+        _ => panic!("Wrong node type"),
     }
 }
 
 #[test]
-fn it_parses_cdata() {
-    let node = get_nth_child("<![CDATA[<h1>Hello world]]>".to_string(), 0);
-    match node.node_type {
-        NodeType::CData(c) => assert_eq!(c, "<h1>Hello world"),
-        _ => assert!(false, "Wrong node type"),
+fn it_parses_cdata_in_html_as_bogus_comment() {
+    let (nodes, warnings) = parse_with_warnings("<p><![CDATA[Hello world]]></p>".to_string());
+    match &nodes[0].children[0].node_type {
+        NodeType::Comment(c) => assert_eq!(c, "[CDATA[Hello world]]"),
+        _ => panic!("Wrong node type"),
+    }
+    assert_eq!(warnings.len(), 1);
+}
+
+#[test]
+fn it_parses_processing_instructions_as_bogus_comments() {
+    let (document, warnings) = parse_document(
+        "<?xml version=\"1.0\"?><!DOCTYPE html><p><?php echo 1 ?><!ELEMENT p ANY>".to_string(),
+    );
+    let codes: Vec<&str> = warnings.iter().map(|w| w.code).collect();
+    assert_eq!(
+        codes,
+        vec![
+            "unexpected-question-mark-instead-of-tag-name",
+            "unexpected-question-mark-instead-of-tag-name",
+            "incorrectly-opened-comment",
+        ]
+    );
+    match &document.children[0].node_type {
+        NodeType::Comment(c) => assert_eq!(c, "?xml version=\"1.0\"?"),
+        _ => panic!("Wrong node type"),
+    }
+    let body = document_element(document).children.pop().unwrap();
+    let comments: Vec<&str> = body.children[0]
+        .children
+        .iter()
+        .filter_map(|node| match &node.node_type {
+            NodeType::Comment(c) => Some(c.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(comments, vec!["?php echo 1 ?", "ELEMENT p ANY"]);
+}
+
+#[test]
+fn it_keeps_processing_instructions_in_xml_compatibility_mode() {
+    let mut warnings = vec![];
+    let document = html_parser::parse_xml_compatible(
+        "<?xml version=\"1.0\"?>\n<!DOCTYPE html><p><?php echo '?'; ?><? x ?>".to_string(),
+        &mut warnings,
+    );
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(
+        warnings[0].code,
+        "unexpected-question-mark-instead-of-tag-name"
+    );
+    match &document.children[0].node_type {
+        NodeType::ProcessingInstruction(pi) => {
+            assert_eq!(
+                (pi.target.as_str(), pi.data.as_str()),
+                ("xml", "version=\"1.0\"")
+            )
+        }
+        _ => panic!("Wrong node type"),
+    }
+    assert_eq!(offsets(document.children[0].span()), (0, 21));
+    let body = document_element(document).children.pop().unwrap();
+    let paragraph = &body.children[0];
+    match &paragraph.children[0].node_type {
+        NodeType::ProcessingInstruction(pi) => {
+            assert_eq!(
+                (pi.target.as_str(), pi.data.as_str()),
+                ("php", "echo '?'; ")
+            )
+        }
+        _ => panic!("Wrong node type"),
+    }
+    match &paragraph.children[1].node_type {
+        NodeType::Comment(c) => assert_eq!(c, "? x ?"),
+        _ => panic!("Wrong node type"),
     }
 }
 
 #[test]
 fn it_parses_self_closing_tags() {
-    let node = get_nth_child("<h1 /><p>Hello</p>".to_string(), 0);
+    let node = get_nth_child("<br /><p>Hello</p>".to_string(), 0);
     match node.node_type {
-        NodeType::Element(e) => assert_eq!(e.tag_name, "h1"),
-        _ => assert!(false, "Wrong node type"),
+        NodeType::Element(e) => assert_eq!(e.tag_name, "br"),
+        _ => panic!("Wrong node type"),
     }
+    assert_eq!(node.children.len(), 0);
 }
 
 #[test]
 fn it_closes_children_with_parent() {
     let node = get_nth_child("<ul><li>Hello</ul>".to_string(), 0);
-    let ref child = node.children[0];
+    let child = &node.children[0];
     match &child.node_type {
-        &NodeType::Element(ref e) => assert_eq!(e.tag_name, "li"),
-        _ => assert!(false, "Wrong node type"),
+        NodeType::Element(e) => assert_eq!(e.tag_name, "li"),
+        _ => panic!("Wrong node type"),
     }
 }
 
 #[test]
 fn it_parser_multi_line_self_closing_elements() {
     let node = get_nth_child(
-        "<div
+        "<img
     a='1'
     />"
         .to_string(),
         0,
     );
     match node.node_type {
-        NodeType::Element(ref e) => assert_eq!(e.tag_name, "div"),
-        _ => assert!(false, "Wrong node type"),
+        NodeType::Element(ref e) => assert_eq!(e.tag_name, "img"),
+        _ => panic!("Wrong node type"),
     }
 }
 
-fn get_nth_child(text: String, pos: usize) -> Node {
+#[test]
+fn it_decodes_named_character_references() {
+    let node = get_nth_child("<p>a &amp; b &lt;c&gt;</p>".to_string(), 0);
+    match &node.children[0].node_type {
+        NodeType::Text(c) => assert_eq!(c, "a & b <c>"),
+        _ => panic!("Wrong node type"),
+    }
+}
+
+#[test]
+fn it_decodes_numeric_character_references() {
+    let node = get_nth_child("<p>&#169; &#x1F600; &#X41;</p>".to_string(), 0);
+    match &node.children[0].node_type {
+        NodeType::Text(c) => assert_eq!(c, "\u{A9} \u{1F600} A"),
+        _ => panic!("Wrong node type"),
+    }
+}
+
+#[test]
+fn it_decodes_character_references_in_attributes() {
+    let node = get_nth_child("<a title=\"Tom &amp; Jerry\"></a>".to_string(), 0);
+    match node.node_type {
+        NodeType::Element(e) => assert_eq!(e.attributes.get("title").unwrap(), "Tom & Jerry"),
+        _ => panic!("Wrong node type"),
+    }
+}
+
+#[test]
+fn it_leaves_bare_ampersands_alone() {
+    let node = get_nth_child("<p>fish & chips &</p>".to_string(), 0);
+    match &node.children[0].node_type {
+        NodeType::Text(c) => assert_eq!(c, "fish & chips &"),
+        _ => panic!("Wrong node type"),
+    }
+}
+
+#[test]
+fn it_decodes_legacy_references_without_semicolon() {
+    let (nodes, warnings) = parse_with_warnings("<p>&copy 2018 &notit;</p>".to_string());
+    match &nodes[0].children[0].node_type {
+        NodeType::Text(c) => assert_eq!(c, "\u{A9} 2018 \u{AC}it;"),
+        _ => panic!("Wrong node type"),
+    }
+    assert_eq!(warnings.len(), 2);
+}
+
+#[test]
+fn it_keeps_legacy_references_in_attribute_query_strings() {
+    let node = get_nth_child("<a href=\"?a=1&copy=2&notx\"></a>".to_string(), 0);
+    match node.node_type {
+        NodeType::Element(e) => assert_eq!(e.attributes.get("href").unwrap(), "?a=1&copy=2&notx"),
+        _ => panic!("Wrong node type"),
+    }
+}
+
+#[test]
+fn it_remaps_windows_1252_numeric_references() {
+    let (nodes, warnings) = parse_with_warnings("<p>&#128;&#x99;</p>".to_string());
+    match &nodes[0].children[0].node_type {
+        NodeType::Text(c) => assert_eq!(c, "\u{20AC}\u{2122}"),
+        _ => panic!("Wrong node type"),
+    }
+    assert_eq!(warnings.len(), 2);
+}
+
+#[test]
+fn it_replaces_invalid_numeric_references() {
+    let (nodes, warnings) = parse_with_warnings("<p>&#0;&#xD800;&#x110000;</p>".to_string());
+    match &nodes[0].children[0].node_type {
+        NodeType::Text(c) => assert_eq!(c, "\u{FFFD}\u{FFFD}\u{FFFD}"),
+        _ => panic!("Wrong node type"),
+    }
+    assert_eq!(warnings.len(), 3);
+}
+
+#[test]
+fn it_warns_on_malformed_references() {
+    let (nodes, warnings) = parse_with_warnings("<p>&#; &#x; &madeup; &#65</p>".to_string());
+    match &nodes[0].children[0].node_type {
+        NodeType::Text(c) => assert_eq!(c, "&#; &#x; &madeup; A"),
+        _ => panic!("Wrong node type"),
+    }
+    assert_eq!(warnings.len(), 4);
+}
+
+#[test]
+fn it_parses_boolean_attributes() {
+    let node = get_nth_child("<input disabled required/>".to_string(), 0);
+    match node.node_type {
+        NodeType::Element(e) => {
+            assert_eq!(e.attributes.get("disabled").unwrap(), "");
+            assert_eq!(e.attributes.get("required").unwrap(), "");
+        }
+        _ => panic!("Wrong node type"),
+    }
+}
+
+#[test]
+fn it_parses_unquoted_attributes() {
+    let node = get_nth_child("<div data-n=2 data-x=foo&amp;bar></div>".to_string(), 0);
+    match node.node_type {
+        NodeType::Element(e) => {
+            assert_eq!(e.attributes.get("data-n").unwrap(), "2");
+            assert_eq!(e.attributes.get("data-x").unwrap(), "foo&bar");
+        }
+        _ => panic!("Wrong node type"),
+    }
+}
+
+#[test]
+fn it_parses_attributes_with_spaces_around_equals() {
+    let node = get_nth_child("<a href = \"/home\" title= 'Home'></a>".to_string(), 0);
+    match node.node_type {
+        NodeType::Element(e) => {
+            assert_eq!(e.attributes.get("href").unwrap(), "/home");
+            assert_eq!(e.attributes.get("title").unwrap(), "Home");
+        }
+        _ => panic!("Wrong node type"),
+    }
+}
+
+#[test]
+fn it_parses_attribute_names_with_punctuation() {
+    let node = get_nth_child(
+        "<use xlink:href=\"#a\" v-on:click=\"go\" @click.prevent=\"stop\"></use>".to_string(),
+        0,
+    );
+    match node.node_type {
+        NodeType::Element(e) => {
+            assert_eq!(e.attributes.get("xlink:href").unwrap(), "#a");
+            assert_eq!(e.attributes.get("v-on:click").unwrap(), "go");
+            assert_eq!(e.attributes.get("@click.prevent").unwrap(), "stop");
+        }
+        _ => panic!("Wrong node type"),
+    }
+}
+
+#[test]
+fn it_keeps_the_first_duplicate_attribute() {
+    let (nodes, warnings) = parse_with_warnings("<p class=\"a\" class=\"b\"></p>".to_string());
+    match &nodes[0].node_type {
+        NodeType::Element(e) => assert_eq!(e.attributes.get("class").unwrap(), "a"),
+        _ => panic!("Wrong node type"),
+    }
+    assert_eq!(warnings.len(), 1);
+}
+
+#[test]
+fn it_does_not_nest_siblings_inside_void_elements() {
+    let node = get_nth_child("<div>a<br>b<img src=x.png><input><hr></div>".to_string(), 0);
+    assert_eq!(node.children.len(), 6);
+    for child in &node.children {
+        assert_eq!(child.children.len(), 0);
+    }
+}
+
+#[test]
+fn it_parses_script_contents_as_text() {
+    let node =
+        get_head("<script>if (a < b) { x = '</div>'; }</script>".to_string()).children[0].clone();
+    assert_eq!(node.children.len(), 1);
+    match &node.children[0].node_type {
+        NodeType::Text(c) => assert_eq!(c, "if (a < b) { x = '</div>'; }"),
+        _ => panic!("Wrong node type"),
+    }
+}
+
+#[test]
+fn it_parses_escaped_script_contents_as_text() {
+    let node =
+        get_head("<script><!-- document.write('<script></script>'); --></script>".to_string())
+            .children[0]
+            .clone();
+    match &node.children[0].node_type {
+        NodeType::Text(c) => assert_eq!(c, "<!-- document.write('<script></script>'); -->"),
+        _ => panic!("Wrong node type"),
+    }
+}
+
+#[test]
+fn it_parses_style_contents_as_text() {
+    let node =
+        get_head("<style>a > b { content: \"&amp;\" }</STYLE>".to_string()).children[0].clone();
+    match &node.children[0].node_type {
+        NodeType::Text(c) => assert_eq!(c, "a > b { content: \"&amp;\" }"),
+        _ => panic!("Wrong node type"),
+    }
+}
+
+#[test]
+fn it_decodes_references_in_textarea_contents() {
+    let node = get_nth_child("<textarea><b>&lt;</b></textarea>".to_string(), 0);
+    match &node.children[0].node_type {
+        NodeType::Text(c) => assert_eq!(c, "<b><</b>"),
+        _ => panic!("Wrong node type"),
+    }
+}
+
+#[test]
+fn it_preserves_whitespace_in_text() {
+    let node = get_nth_child("<p>  a \t\n b  </p>".to_string(), 0);
+    match &node.children[0].node_type {
+        NodeType::Text(c) => assert_eq!(c, "  a \t\n b  "),
+        _ => panic!("Wrong node type"),
+    }
+    let node = get_nth_child("<pre>  code\n    indented</pre>".to_string(), 0);
+    match &node.children[0].node_type {
+        NodeType::Text(c) => assert_eq!(c, "  code\n    indented"),
+        _ => panic!("Wrong node type"),
+    }
+}
+
+#[test]
+fn it_ignores_the_first_newline_in_pre_and_textarea() {
+    let text = |source: &str, expected: &str| {
+        let node = get_nth_child(source.to_string(), 0);
+        match &node.children[0].node_type {
+            NodeType::Text(c) => assert_eq!(c, expected),
+            _ => panic!("Wrong node type"),
+        }
+        node.children[0].span()
+    };
+    let span = text("<pre>\n\ncode</pre>", "\ncode");
+    assert_eq!(offsets(span), (21, 26));
+    text("<textarea>\r\nvalue</textarea>", "value");
+    text("<listing>\n x</listing>", " x");
+    text("<pre> \nx</pre>", " \nx");
+}
+
+#[test]
+fn it_closes_list_items_implicitly() {
+    let node = get_nth_child("<ul><li>One<li>Two<li>Three</ul>".to_string(), 0);
+    assert_eq!(tag_names(&node.children), vec!["li", "li", "li"]);
+    for child in &node.children {
+        assert_eq!(child.children.len(), 1);
+    }
+}
+
+#[test]
+fn it_closes_paragraphs_before_blocks() {
+    let node = get_nth_child("<div><p>One<p>Two<div>Three</div></div>".to_string(), 0);
+    assert_eq!(tag_names(&node.children), vec!["p", "p", "div"]);
+}
+
+#[test]
+fn it_closes_paragraphs_inside_list_items() {
+    let node = get_nth_child("<ul><li><p>One<li><p>Two</ul>".to_string(), 0);
+    assert_eq!(tag_names(&node.children), vec!["li", "li"]);
+    assert_eq!(tag_names(&node.children[0].children), vec!["p"]);
+}
+
+#[test]
+fn it_closes_table_cells_and_rows_implicitly() {
+    let node = get_nth_child(
+        "<table><tbody><tr><td>a<td>b<tr><th>c<td>d</table>".to_string(),
+        0,
+    );
+    let tbody = &node.children[0];
+    assert_eq!(tag_names(&tbody.children), vec!["tr", "tr"]);
+    assert_eq!(tag_names(&tbody.children[0].children), vec!["td", "td"]);
+    assert_eq!(tag_names(&tbody.children[1].children), vec!["th", "td"]);
+}
+
+#[test]
+fn it_closes_definitions_and_options_implicitly() {
+    let node = get_nth_child("<dl><dt>a<dd>b<dt>c</dl>".to_string(), 0);
+    assert_eq!(tag_names(&node.children), vec!["dt", "dd", "dt"]);
+
+    let node = get_nth_child(
+        "<select><optgroup><option>a<option>b<optgroup><option>c</select>".to_string(),
+        0,
+    );
+    assert_eq!(tag_names(&node.children), vec!["optgroup", "optgroup"]);
+    assert_eq!(
+        tag_names(&node.children[0].children),
+        vec!["option", "option"]
+    );
+}
+
+#[test]
+fn it_closes_optional_end_tags_at_end_of_file() {
+    let (nodes, warnings) = parse_with_warnings("<ul><li>One<li>Two".to_string());
+    assert_eq!(tag_names(&nodes[0].children), vec!["li", "li"]);
+    assert_eq!(warnings.len(), 1, "Only the missing </ul> is reported");
+}
+
+#[test]
+fn it_synthesizes_html_head_and_body() {
+    let (document, warnings) = parse_document("<!DOCTYPE html><title>x</title><p>y".to_string());
+    assert_eq!(warnings.len(), 0);
+    let nodes = &document.children;
+    assert_eq!(tag_names(nodes), vec!["html"]);
+    assert_eq!(tag_names(&nodes[1].children), vec!["head", "body"]);
+    assert_eq!(tag_names(&nodes[1].children[0].children), vec!["title"]);
+    assert_eq!(tag_names(&nodes[1].children[1].children), vec!["p"]);
+}
+
+#[test]
+fn it_keeps_doctype_identifiers() {
+    let (document, _) = parse_document(
+        "<!DOCTYPE html PUBLIC \"-//W3C//DTD HTML 4.01//EN\" \"http://www.w3.org/TR/html4/strict.dtd\">"
+            .to_string(),
+    );
+    match document.children[0].node_type {
+        NodeType::DocType(ref e) => {
+            assert_eq!(e.name, "html");
+            assert_eq!(e.public_id, "-//W3C//DTD HTML 4.01//EN");
+            assert_eq!(e.system_id, "http://www.w3.org/TR/html4/strict.dtd");
+            assert!(!e.force_quirks);
+        }
+        _ => panic!("Wrong node type"),
+    }
+}
+
+#[test]
+fn it_decides_the_quirks_mode_from_the_doctype() {
+    let quirks_mode = |source: &str| match parse_document(source.to_string()).0.node_type {
+        NodeType::Document(document) => document.quirks_mode,
+        _ => panic!("Wrong node type"),
+    };
+    assert_eq!(quirks_mode("<!DOCTYPE html>"), QuirksMode::NoQuirks);
+    assert_eq!(quirks_mode("<p>"), QuirksMode::Quirks);
+    assert_eq!(quirks_mode("<!DOCTYPE html PUBLIC"), QuirksMode::Quirks);
+    let transitional = "<!DOCTYPE HTML PUBLIC \"-//W3C//DTD HTML 4.01 Transitional//EN\"";
+    assert_eq!(
+        quirks_mode(&format!("{}>", transitional)),
+        QuirksMode::Quirks
+    );
+    assert_eq!(
+        quirks_mode(&format!(
+            "{} \"http://www.w3.org/TR/html4/loose.dtd\">",
+            transitional
+        )),
+        QuirksMode::LimitedQuirks
+    );
+}
+
+#[test]
+fn it_reports_a_missing_doctype() {
+    let (document, warnings) = parse_document("<p>x</p>".to_string());
+    assert_eq!(tag_names(&document.children), vec!["html"]);
+    assert_eq!(warnings.len(), 1);
+}
+
+#[test]
+fn it_returns_a_document_for_empty_input() {
+    let (document, _) = parse_document(String::new());
+    match document.node_type {
+        NodeType::Document(_) => {}
+        _ => panic!("Wrong node type"),
+    }
+    let html = document_element(document);
+    assert_eq!(tag_names(&html.children), vec!["head", "body"]);
+}
+
+#[test]
+fn it_wraps_bare_text_in_body() {
+    let (document, _) = parse_document("hello".to_string());
+    let body = document_element(document).children.pop().unwrap();
+    match &body.node_type {
+        NodeType::Element(e) => assert_eq!(e.tag_name, "body"),
+        _ => panic!("Wrong node type"),
+    }
+    match &body.children[0].node_type {
+        NodeType::Text(c) => assert_eq!(c, "hello"),
+        _ => panic!("Wrong node type"),
+    }
+}
+
+#[test]
+fn it_keeps_everything_after_the_doctype() {
+    let (document, _) = parse_document("<!DOCTYPE html><!-- a --><p>b</p>".to_string());
+    assert_eq!(document.children.len(), 3);
+    let body = document_element(document).children.pop().unwrap();
+    assert_eq!(tag_names(&body.children), vec!["p"]);
+}
+
+#[test]
+fn it_reparents_misnested_formatting_elements() {
+    let (nodes, warnings) = parse_with_warnings("<b>1<i>2</b>3</i>".to_string());
+    assert_eq!(tag_names(&nodes), vec!["b", "i"]);
+    assert_eq!(tag_names(&nodes[0].children), vec!["i"]);
+    match &nodes[1].children[0].node_type {
+        NodeType::Text(c) => assert_eq!(c, "3"),
+        _ => panic!("Wrong node type"),
+    }
+    assert_eq!(warnings.len(), 1);
+}
+
+#[test]
+fn it_runs_the_adoption_agency_for_blocks_inside_formatting() {
+    let (nodes, _) = parse_with_warnings("<a>1<p>2</a>3</p>".to_string());
+    assert_eq!(tag_names(&nodes), vec!["a", "p"]);
+    assert_eq!(tag_names(&nodes[1].children), vec!["a"]);
+    match &nodes[1].children[1].node_type {
+        NodeType::Text(c) => assert_eq!(c, "3"),
+        _ => panic!("Wrong node type"),
+    }
+}
+
+#[test]
+fn it_foster_parents_stray_table_content() {
+    let (nodes, warnings) =
+        parse_with_warnings("<table>text<div>x</div><tr><td>a</td></tr></table>".to_string());
+    assert_eq!(tag_names(&nodes), vec!["div", "table"]);
+    match &nodes[0].node_type {
+        NodeType::Text(c) => assert_eq!(c, "text"),
+        _ => panic!("Wrong node type"),
+    }
+    assert_eq!(tag_names(&nodes[2].children), vec!["tbody"]);
+    assert!(!warnings.is_empty());
+}
+
+#[test]
+fn it_records_node_spans() {
+    let node = get_nth_child("<p id=\"a\">Hi &amp; bye</p>".to_string(), 0);
+    assert_eq!(offsets(node.span()), (15, 41));
+    assert_eq!(offsets(node.children[0].span()), (25, 37));
+}
+
+#[test]
+fn it_records_lines_and_columns() {
+    let (nodes, _) = parse_with_warnings("\n<div>\n  <b>x</b>\n</div>".to_string());
+    let b = &nodes[0].children[1];
+    assert_eq!(b.span().start.line(), 3);
+    assert_eq!(b.span().start.column(), 2);
+    assert_eq!(b.span().end.line(), 3);
+    assert_eq!(b.span().end.column(), 10);
+}
+
+#[test]
+fn it_extends_implicitly_closed_elements_to_their_contents() {
+    let (document, _) = parse_document("<!DOCTYPE html><p>1<p>22".to_string());
+    assert_eq!(offsets(document.span()), (0, 24));
+    let body = document_element(document).children.pop().unwrap();
+    assert_eq!(offsets(body.span()), (15, 24));
+    assert_eq!(offsets(body.children[0].span()), (15, 19));
+    assert_eq!(offsets(body.children[1].span()), (19, 24));
+}
+
+#[test]
+fn it_records_attribute_spans() {
+    let node = get_nth_child("<input id=\"a\" disabled value=x>".to_string(), 0);
+    let id = node.attribute_span("id").unwrap();
+    assert_eq!(offsets(id.name), (22, 24));
+    assert_eq!(offsets(id.value), (26, 27));
+    let disabled = node.attribute_span("disabled").unwrap();
+    assert_eq!(offsets(disabled.name), (29, 37));
+    assert_eq!(offsets(disabled.value), (37, 37));
+    let value = node.attribute_span("value").unwrap();
+    assert_eq!(offsets(value.value), (44, 45));
+    assert!(node.attribute_span("type").is_none());
+}
+
+#[test]
+fn it_records_spans_of_attributes_merged_onto_body() {
+    let document = html_parser::parse("<body id=a><body id=b class=c>".to_string(), &mut vec![]);
+    let body = &document.children[0].children[1];
+    match body.node_type {
+        NodeType::Element(ref e) => assert_eq!(e.attributes.get("id").unwrap(), "a"),
+        _ => panic!("Wrong node type"),
+    }
+    assert_eq!(offsets(body.attribute_span("id").unwrap().name), (6, 8));
+    let class = body.attribute_span("class").unwrap();
+    assert_eq!(offsets(class.name), (22, 27));
+    assert_eq!(offsets(class.value), (28, 29));
+}
+
+#[test]
+fn it_keeps_attributes_in_source_order() {
+    let document = html_parser::parse(
+        "<html z=1 a=2><body><p id=x c=3 b=4 id=y></p><html a=5 m=6>".to_string(),
+        &mut vec![],
+    );
+    let names = |node: &Node| match &node.node_type {
+        NodeType::Element(e) => e
+            .attributes
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<String>>(),
+        _ => panic!("Wrong node type"),
+    };
+    let html = &document.children[0];
+    assert_eq!(names(html), vec!["z=1", "a=2", "m=6"]);
+    let p = &html.children[1].children[0];
+    assert_eq!(names(p), vec!["id=x", "c=3", "b=4"]);
+    assert!(p
+        .to_string()
+        .starts_with("p {id: \"x\" @ 1:23-1:27, c: \"3\" @ 1:28-1:31, b: \"4\" @ 1:32-1:35}"));
+}
+
+fn offsets(span: Span) -> (usize, usize) {
+    (span.start.offset(), span.end.offset())
+}
+
+#[test]
+fn it_streams_input_split_anywhere() {
+    let source =
+        "<!DOCTYPE html><p class=\"x\">caf\u{e9} &amp; cr\u{e8}me &notit;</p>\r\n<!-- \u{2603} -->";
+    let (document, warnings) = parse_document(source.to_string());
+    let mut parser = html_parser::create_streaming();
+    for byte in source.as_bytes() {
+        parser.feed(&[*byte]);
+    }
+    parser.finish();
+    let mut streamed_warnings = vec![];
+    let streamed = parser.into_document(&mut streamed_warnings);
+    assert_eq!(streamed.to_string(), document.to_string());
+    assert_eq!(streamed_warnings, warnings);
+}
+
+#[test]
+fn it_sniffs_the_encoding_of_streamed_input() {
+    let mut parser = html_parser::create_streaming();
+    parser.feed(b"<!DOCTYPE html><meta charset=windows-1252><p>\x93hi\x94");
+    assert!(parser.encoding().is_none());
+    parser.finish();
+    assert_eq!(
+        parser.encoding(),
+        Some((Encoding::Windows1252, Confidence::Tentative))
+    );
+    let body = document_element(parser.into_document(&mut vec![]))
+        .children
+        .pop()
+        .unwrap();
+    match &body.children[0].children[0].node_type {
+        NodeType::Text(c) => assert_eq!(c, "\u{201C}hi\u{201D}"),
+        _ => panic!("Wrong node type"),
+    }
+}
+
+#[test]
+fn it_streams_to_the_encoding_of_the_first_1024_bytes() {
+    let mut ascii = b"<p>".to_vec();
+    ascii.resize(1100, b'a');
+    let mut utf8 = b"<p>caf\xC3\xA9".to_vec();
+    utf8.resize(1100, b'a');
+    for prefix in [ascii, utf8] {
+        let mut source = prefix.clone();
+        source.extend_from_slice(b"caf\xE9");
+        let (text, encoding, confidence) = encoding::decode_html(&source, &mut vec![]);
+        assert_eq!(encoding, Encoding::Utf8);
+        assert!(text.ends_with("caf\u{FFFD}"));
+        let (document, _) = parse_document(text);
+        for split in [1023, 1024, 1025, prefix.len()] {
+            let mut parser = html_parser::create_streaming();
+            parser.feed(&source[..split]);
+            parser.feed(&source[split..]);
+            parser.finish();
+            assert_eq!(parser.encoding(), Some((encoding, confidence)));
+            let streamed = parser.into_document(&mut vec![]);
+            assert_eq!(streamed.to_string(), document.to_string());
+        }
+    }
+}
+
+#[test]
+fn it_returns_elements_as_they_are_closed() {
+    let mut parser = html_parser::create_streaming();
+    parser.feed(format!("<!DOCTYPE html><!--{}-->", " ".repeat(1024)).as_bytes());
+    parser.feed(b"<ul><li>a<li>b</ul>");
+    assert!(parser.take_completed().is_empty());
+    parser.feed(b"<p>");
+    parser.feed(" ".repeat(64).as_bytes());
+    let completed = parser.take_completed();
+    let nodes: Vec<NodeRef> = completed.iter().map(|&id| parser.node(id)).collect();
+    assert_eq!(ref_tag_names(&nodes), vec!["head", "li", "li", "ul"]);
+    assert_eq!(ref_tag_names(&nodes[3].children()), vec!["li", "li"]);
+    assert!(parser.take_completed().is_empty());
+}
+
+#[test]
+fn it_yields_events_without_building_a_tree() {
+    let events: Vec<(Event, (usize, usize))> =
+        html_parser::events("<!DOCTYPE html><a href=x>hi</a><!--c-->".to_string())
+            .map(|(event, span)| (event, offsets(span)))
+            .collect();
+    assert_eq!(events.len(), 5);
+    match &events[0] {
+        (Event::Doctype(doctype), (0, 15)) => assert_eq!(doctype.name, Some("html".to_string())),
+        event => panic!("Unexpected event {:?}", event),
+    }
+    match &events[1] {
+        (Event::StartTag(tag), (15, 25)) => {
+            assert_eq!(tag.name, "a");
+            assert_eq!(tag.attribute("href"), Some("x"));
+            assert_eq!(offsets(tag.attributes[0].value_span), (23, 24));
+        }
+        event => panic!("Unexpected event {:?}", event),
+    }
+    assert_eq!(events[2], (Event::Text("hi".to_string()), (25, 27)));
+    match &events[3] {
+        (Event::EndTag(tag), (27, 31)) => assert_eq!(tag.name, "a"),
+        event => panic!("Unexpected event {:?}", event),
+    }
+    assert_eq!(events[4], (Event::Comment("c".to_string()), (31, 39)));
+}
+
+#[test]
+fn it_yields_raw_text_and_cdata_events() {
+    let events: Vec<Event> = html_parser::events(
+        "<script><b></script><![CDATA[x]]><svg><![CDATA[<y>]]></svg>".to_string(),
+    )
+    .map(|(event, _)| event)
+    .filter(|event| matches!(event, Event::Text(_) | Event::Comment(_) | Event::CData(_)))
+    .collect();
+    assert_eq!(
+        events,
+        vec![
+            Event::Text("<b>".to_string()),
+            Event::Comment("[CDATA[x]]".to_string()),
+            Event::CData("<y>".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn it_parses_inline_svg_and_mathml() {
+    let (document, warnings) = parse_document(
+        "<!DOCTYPE html><svg viewbox='0 0 1 1'><foreignobject><p>x</p></foreignobject>\
+         <use xlink:href=#a /><![CDATA[<z>]]></svg><math><mi>y</mi></math>"
+            .to_string(),
+    );
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    let body = document_element(document).children.pop().unwrap();
+    let svg = &body.children[0];
+    let element = |node: &Node| match &node.node_type {
+        NodeType::Element(e) => (e.namespace, e.tag_name.clone()),
+        _ => panic!("Wrong node type"),
+    };
+    assert_eq!(element(svg), (Namespace::Svg, "svg".to_string()));
+    match &svg.node_type {
+        NodeType::Element(e) => assert!(e.attributes.contains_key("viewBox")),
+        _ => panic!("Wrong node type"),
+    }
+    let foreign_object = &svg.children[0];
+    assert_eq!(
+        element(foreign_object),
+        (Namespace::Svg, "foreignObject".to_string())
+    );
+    assert_eq!(
+        element(&foreign_object.children[0]),
+        (Namespace::Html, "p".to_string())
+    );
+    let use_element = &svg.children[1];
+    assert_eq!(element(use_element), (Namespace::Svg, "use".to_string()));
+    assert!(use_element.children.is_empty());
+    assert!(use_element.attribute_span("xlink:href").is_some());
+    match &svg.children[2].node_type {
+        NodeType::Text(text) => assert_eq!(text, "<z>"),
+        _ => panic!("Wrong node type"),
+    }
+    let math = &body.children[1];
+    assert_eq!(element(math), (Namespace::MathMl, "math".to_string()));
+    assert_eq!(
+        element(&math.children[0]),
+        (Namespace::MathMl, "mi".to_string())
+    );
+}
+
+#[test]
+fn it_warns_about_an_unsupported_encoding_when_streaming() {
+    let mut parser = html_parser::create_streaming();
+    parser.feed(b"<!DOCTYPE html><meta http-equiv=content-type ");
+    parser.feed(b"content='text/html; charset=euc-jp'><meta charset=shift_jis>");
+    parser.finish();
     let mut warnings = vec![];
-    let nodes = html_parser::parse(text, &mut warnings);
+    parser.into_document(&mut warnings);
+    let codes: Vec<_> = warnings.iter().map(|warning| warning.code).collect();
+    assert_eq!(codes, ["unsupported-encoding"]);
+    assert!(warnings[0].message.contains("EUC-JP"));
+    assert_eq!(warnings[0].span.start.offset(), 54);
+}
+
+#[test]
+fn it_breaks_out_of_foreign_content_on_html_elements() {
+    let (document, warnings) = parse_document("<!DOCTYPE html><svg><g><p>x".to_string());
+    assert_eq!(warnings[0].code, "unexpected-start-tag");
+    let body = document_element(document).children.pop().unwrap();
+    assert_eq!(tag_names(&body.children), vec!["svg", "p"]);
+}
+
+#[test]
+fn it_parses_fragments_in_context() {
+    let mut warnings = vec![];
+    let cells =
+        html_parser::parse_fragment("<td>a</td><td>b".to_string(), "tr", &mut warnings).unwrap();
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    assert_eq!(tag_names(&cells), vec!["td", "td"]);
+    assert_eq!(offsets(cells[1].span()), (10, 15));
+
+    let options =
+        html_parser::parse_fragment("<option>x<option>y<p>".to_string(), "select", &mut vec![])
+            .unwrap();
+    assert_eq!(tag_names(&options), vec!["option", "option"]);
+    assert!(options[1].children.len() == 1);
+
+    // Without a table around them, cells are dropped.
+    let text = html_parser::parse_fragment("<td>a</td>".to_string(), "div", &mut vec![]).unwrap();
+    match &text[0].node_type {
+        NodeType::Text(c) => assert_eq!(c, "a"),
+        _ => panic!("Wrong node type"),
+    }
+}
+
+#[test]
+fn it_parses_fragments_in_raw_text_and_foreign_contexts() {
+    let text = html_parser::parse_fragment("a<b></textarea>".to_string(), "textarea", &mut vec![])
+        .unwrap();
+    assert_eq!(text.len(), 1);
+    match &text[0].node_type {
+        NodeType::Text(c) => assert_eq!(c, "a<b>"),
+        _ => panic!("Wrong node type"),
+    }
+
+    let shapes =
+        html_parser::parse_fragment("<circle/><p>".to_string(), "svg", &mut vec![]).unwrap();
+    match &shapes[1].node_type {
+        NodeType::Element(e) => {
+            assert_eq!((e.namespace, e.tag_name.as_str()), (Namespace::Svg, "p"))
+        }
+        _ => panic!("Wrong node type"),
+    }
+}
+
+#[test]
+fn it_rejects_invalid_fragment_context_names() {
+    for name in ["", "foo bar", "p/", "a>b", "\ttd"] {
+        let error = html_parser::parse_fragment("x".to_string(), name, &mut vec![]).unwrap_err();
+        assert_eq!(error.code, "invalid-context-element");
+        assert_eq!(
+            error.message,
+            format!("'{}' is not a valid element name", name)
+        );
+    }
+}
+
+#[test]
+fn it_ignores_the_case_of_fragment_contexts() {
+    let cells = html_parser::parse_fragment("<td>a<td>b".to_string(), "TR", &mut vec![]).unwrap();
+    assert_eq!(tag_names(&cells), vec!["td", "td"]);
+
+    let text = html_parser::parse_fragment("a<b>".to_string(), "TextArea", &mut vec![]).unwrap();
+    match &text[0].node_type {
+        NodeType::Text(c) => assert_eq!(c, "a<b>"),
+        _ => panic!("Wrong node type"),
+    }
+
+    let shapes = html_parser::parse_fragment("<p>".to_string(), "SVG", &mut vec![]).unwrap();
+    match &shapes[0].node_type {
+        NodeType::Element(e) => assert_eq!(e.namespace, Namespace::Svg),
+        _ => panic!("Wrong node type"),
+    }
+}
+
+fn tag_names(nodes: &[Node]) -> Vec<String> {
+    nodes
+        .iter()
+        .filter_map(|node| match &node.node_type {
+            NodeType::Element(e) => Some(e.tag_name.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn ref_tag_names(nodes: &[NodeRef]) -> Vec<String> {
+    nodes
+        .iter()
+        .filter_map(|node| match node.node_type() {
+            NodeType::Element(e) => Some(e.tag_name.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn parse_document(text: String) -> (Node, Vec<Diagnostic>) {
+    let mut warnings = vec![];
+    let document = html_parser::parse(text, &mut warnings);
+    (document, warnings)
+}
+
+fn document_element(document: Node) -> Node {
+    document
+        .children
+        .into_iter()
+        .find(|node| match &node.node_type {
+            NodeType::Element(e) => e.tag_name == "html",
+            _ => false,
+        })
+        .expect("Document element")
+}
+
+// Parses the text as the contents of a standards mode document body.
+fn parse_with_warnings(text: String) -> (Vec<Node>, Vec<Diagnostic>) {
+    let (document, warnings) = parse_document(String::from("<!DOCTYPE html>") + text.as_str());
+    let body = document_element(document).children.pop().unwrap();
+    (body.children, warnings)
+}
+
+fn get_head(text: String) -> Node {
+    let (document, warnings) = parse_document(String::from("<!DOCTYPE html>") + text.as_str());
+    assert_eq!(warnings.len(), 0, "No warnings expected");
+    document_element(document).children.remove(0)
+}
+
+fn get_nth_child(text: String, pos: usize) -> Node {
+    let (nodes, warnings) = parse_with_warnings(text);
     for warn in &warnings {
         println!("Warn: {}", warn)
     }
     assert_eq!(warnings.len(), 0, "No warnings expected");
-    let node = nodes[pos].clone();
-    return node;
+    nodes[pos].clone()
 }
 
 fn get_files_in_dir(dir: PathBuf) -> Vec<PathBuf> {
@@ -217,7 +1094,7 @@ fn get_files_in_dir(dir: PathBuf) -> Vec<PathBuf> {
             }
         }
     }
-    return paths;
+    paths
 }
 
 #[test]
@@ -238,6 +1115,8 @@ fn it_parses_docs() {
         for warn in &warnings {
             println!("  {}", warn);
         }
-        assert_eq!(0, warnings.len(), "there should be no warnings");
+        // Some examples are fragments written without a doctype.
+        warnings.retain(|w| w.code != "missing-doctype");
+        assert_eq!(0, warnings.len(), "there should be no other warnings");
     }
 }
@@ -0,0 +1,1896 @@
+use parser::diagnostic::{self, Diagnostic};
+use parser::entities;
+use parser::parser::{self, ParserPosition, Span};
+use std::collections::VecDeque;
+use std::mem;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attribute {
+    pub name: String,
+    pub value: String,
+    pub name_span: Span,
+    pub value_span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tag {
+    pub name: String,
+    pub attributes: Vec<Attribute>,
+    pub self_closing: bool,
+    pub span: Span,
+}
+
+impl Tag {
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|attribute| attribute.name == name)
+            .map(|attribute| attribute.value.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Doctype {
+    pub name: Option<String>,
+    pub public_id: Option<String>,
+    pub system_id: Option<String>,
+    pub force_quirks: bool,
+}
+
+/// A `<?target data?>` processing instruction, only produced in XML
+/// compatibility mode.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessingInstruction {
+    pub target: String,
+    pub data: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Doctype(Doctype),
+    StartTag(Tag),
+    EndTag(Tag),
+    Comment(String),
+    Characters(String),
+    CData(String),
+    ProcessingInstruction(ProcessingInstruction),
+    Eof,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum State {
+    Data,
+    Rcdata,
+    Rawtext,
+    ScriptData,
+    Plaintext,
+    TagOpen,
+    EndTagOpen,
+    TagName,
+    RcdataLessThanSign,
+    RcdataEndTagOpen,
+    RcdataEndTagName,
+    RawtextLessThanSign,
+    RawtextEndTagOpen,
+    RawtextEndTagName,
+    ScriptDataLessThanSign,
+    ScriptDataEndTagOpen,
+    ScriptDataEndTagName,
+    ScriptDataEscapeStart,
+    ScriptDataEscapeStartDash,
+    ScriptDataEscaped,
+    ScriptDataEscapedDash,
+    ScriptDataEscapedDashDash,
+    ScriptDataEscapedLessThanSign,
+    ScriptDataEscapedEndTagOpen,
+    ScriptDataEscapedEndTagName,
+    ScriptDataDoubleEscapeStart,
+    ScriptDataDoubleEscaped,
+    ScriptDataDoubleEscapedDash,
+    ScriptDataDoubleEscapedDashDash,
+    ScriptDataDoubleEscapedLessThanSign,
+    ScriptDataDoubleEscapeEnd,
+    BeforeAttributeName,
+    AttributeName,
+    AfterAttributeName,
+    BeforeAttributeValue,
+    AttributeValueDoubleQuoted,
+    AttributeValueSingleQuoted,
+    AttributeValueUnquoted,
+    AfterAttributeValueQuoted,
+    SelfClosingStartTag,
+    BogusComment,
+    ProcessingInstruction,
+    ProcessingInstructionQuestionMark,
+    MarkupDeclarationOpen,
+    CommentStart,
+    CommentStartDash,
+    Comment,
+    CommentLessThanSign,
+    CommentLessThanSignBang,
+    CommentLessThanSignBangDash,
+    CommentLessThanSignBangDashDash,
+    CommentEndDash,
+    CommentEnd,
+    CommentEndBang,
+    Doctype,
+    BeforeDoctypeName,
+    DoctypeName,
+    AfterDoctypeName,
+    AfterDoctypePublicKeyword,
+    BeforeDoctypePublicIdentifier,
+    DoctypePublicIdentifierDoubleQuoted,
+    DoctypePublicIdentifierSingleQuoted,
+    AfterDoctypePublicIdentifier,
+    BetweenDoctypePublicAndSystemIdentifiers,
+    AfterDoctypeSystemKeyword,
+    BeforeDoctypeSystemIdentifier,
+    DoctypeSystemIdentifierDoubleQuoted,
+    DoctypeSystemIdentifierSingleQuoted,
+    AfterDoctypeSystemIdentifier,
+    BogusDoctype,
+    CdataSection,
+    CdataSectionBracket,
+    CdataSectionEnd,
+    CharacterReference,
+    NamedCharacterReference,
+    AmbiguousAmpersand,
+    NumericCharacterReference,
+    HexadecimalCharacterReferenceStart,
+    DecimalCharacterReferenceStart,
+    HexadecimalCharacterReference,
+    DecimalCharacterReference,
+    NumericCharacterReferenceEnd,
+}
+
+/// Splits HTML source into tokens following the WHATWG tokenization
+/// algorithm. The tree builder switches `state` for raw text elements.
+pub struct Tokenizer {
+    parser: parser::Parser,
+    state: State,
+    return_state: State,
+    tokens: VecDeque<(Token, Span)>,
+    characters: String,
+    characters_span: Span,
+    characters_verbatim: bool,
+    // Everything before the text cursor has been emitted or belongs to a
+    // token which has been emitted.
+    text_cursor: ParserPosition,
+    current_char: Option<char>,
+    current_char_start: ParserPosition,
+    reconsume: bool,
+    current_tag: Tag,
+    current_tag_is_end: bool,
+    current_attribute: Option<Attribute>,
+    current_attribute_is_duplicate: bool,
+    current_comment: String,
+    current_cdata: String,
+    current_doctype: Doctype,
+    temporary_buffer: String,
+    last_start_tag_name: Option<String>,
+    character_reference_code: u32,
+    allow_cdata: bool,
+    xml_compatible: bool,
+    errors: Vec<Diagnostic>,
+    finished: bool,
+}
+
+// Enough lookahead for the longest named character reference, so a step
+// never sees input cut off in the middle of a chunk.
+const LOOKAHEAD: usize = 64;
+
+pub fn create(html: String) -> Tokenizer {
+    with_parser(parser::create(html))
+}
+
+/// Creates a tokenizer whose input arrives through `feed` and `finish`.
+pub fn create_streaming() -> Tokenizer {
+    with_parser(parser::create_streaming())
+}
+
+fn with_parser(parser: parser::Parser) -> Tokenizer {
+    Tokenizer {
+        parser,
+        state: State::Data,
+        return_state: State::Data,
+        tokens: VecDeque::new(),
+        characters: String::new(),
+        characters_span: Span::default(),
+        characters_verbatim: true,
+        text_cursor: ParserPosition::default(),
+        current_char: None,
+        current_char_start: ParserPosition::default(),
+        reconsume: false,
+        current_tag: new_tag(),
+        current_tag_is_end: false,
+        current_attribute: None,
+        current_attribute_is_duplicate: false,
+        current_comment: String::new(),
+        current_cdata: String::new(),
+        current_doctype: new_doctype(),
+        temporary_buffer: String::new(),
+        last_start_tag_name: None,
+        character_reference_code: 0,
+        allow_cdata: false,
+        xml_compatible: false,
+        errors: vec![],
+        finished: false,
+    }
+}
+
+fn new_tag() -> Tag {
+    Tag {
+        name: String::new(),
+        attributes: vec![],
+        self_closing: false,
+        span: Span::default(),
+    }
+}
+
+fn new_doctype() -> Doctype {
+    Doctype {
+        name: None,
+        public_id: None,
+        system_id: None,
+        force_quirks: false,
+    }
+}
+
+impl Tokenizer {
+    pub fn set_state(&mut self, state: State) {
+        self.state = state;
+    }
+
+    pub fn position(&self) -> ParserPosition {
+        self.parser.position()
+    }
+
+    /// CDATA sections are only recognised in foreign content, which the
+    /// caller tracks.
+    pub fn set_allow_cdata(&mut self, allow: bool) {
+        self.allow_cdata = allow;
+    }
+
+    /// In XML compatibility mode `<?target data?>` is read as a processing
+    /// instruction rather than a bogus comment.
+    pub fn set_xml_compatible(&mut self, xml_compatible: bool) {
+        self.xml_compatible = xml_compatible;
+    }
+
+    pub fn set_last_start_tag_name(&mut self, name: &str) {
+        self.last_start_tag_name = Some(String::from(name));
+    }
+
+    pub fn take_errors(&mut self) -> Vec<Diagnostic> {
+        mem::take(&mut self.errors)
+    }
+
+    pub fn feed(&mut self, text: &str) {
+        let cursor = self.text_cursor;
+        self.parser.discard_before(cursor);
+        self.parser.push_str(text);
+    }
+
+    pub fn finish(&mut self) {
+        self.parser.finish();
+    }
+
+    /// Returns the next token with the source range it was read from, or
+    /// None when more input has to be fed first. Character tokens are only
+    /// split where the text no longer matches the source, so a span as long
+    /// as its text maps onto it character by character.
+    pub fn next_token(&mut self) -> Option<(Token, Span)> {
+        loop {
+            if let Some(token) = self.tokens.pop_front() {
+                return Some(token);
+            }
+            if self.finished {
+                return Some((Token::Eof, Span::at(self.parser.position())));
+            }
+            if !self.parser.is_complete() && self.parser.remaining().len() < LOOKAHEAD {
+                return None;
+            }
+            self.step();
+        }
+    }
+
+    // Errors point at the current input character unless given a span.
+    fn error(&mut self, code: &'static str) {
+        let span = Span {
+            start: self.current_char_start,
+            end: self.parser.position(),
+        };
+        self.error_at(code, span);
+    }
+
+    fn error_at(&mut self, code: &'static str, span: Span) {
+        let message = diagnostic::describe_code(code);
+        self.errors.push(diagnostic::error(code, span, &message));
+    }
+
+    fn next_input(&mut self) -> Option<char> {
+        if self.reconsume {
+            self.reconsume = false;
+            return self.current_char;
+        }
+        self.current_char_start = self.parser.position();
+        if self.parser.eof() {
+            self.current_char = None;
+            return None;
+        }
+        let mut c = self.parser.consume_char();
+        if c == '\r' {
+            if self.parser.starts_with("\n") {
+                self.parser.consume_char();
+            }
+            c = '\n';
+        }
+        self.current_char = Some(c);
+        Some(c)
+    }
+
+    // The position of the next character the state machine will see.
+    fn input_position(&self) -> ParserPosition {
+        if self.reconsume {
+            self.current_char_start
+        } else {
+            self.parser.position()
+        }
+    }
+
+    fn reconsume_in(&mut self, state: State) {
+        self.reconsume = true;
+        self.state = state;
+    }
+
+    // Lookahead always starts after the current input character.
+    fn next_input_is(&self, text: &str) -> bool {
+        self.parser.starts_with(text)
+    }
+
+    fn next_input_is_ignore_case(&self, text: &str) -> bool {
+        match self.parser.remaining().get(..text.len()) {
+            Some(next) => next.eq_ignore_ascii_case(text),
+            None => false,
+        }
+    }
+
+    // Processing instruction targets are XML names.
+    fn next_input_is_name_start(&self) -> bool {
+        match self.parser.remaining().chars().next() {
+            Some(c) => c.is_alphabetic() || c == '_' || c == ':',
+            None => false,
+        }
+    }
+
+    fn skip_input(&mut self, length: usize) {
+        self.parser.consume_next_n_chars(length);
+    }
+
+    // Characters are emitted in source order, so each one is matched against
+    // the source at the text cursor to find its span.
+    fn emit_char(&mut self, c: char) {
+        let start = self.text_cursor;
+        let (end, verbatim) = {
+            let mut source = self.parser.text_from(start).chars();
+            match source.next() {
+                Some('\r') => {
+                    let mut end = start.advance('\r');
+                    if source.next() == Some('\n') {
+                        end = end.advance('\n');
+                    }
+                    (end, false)
+                }
+                Some(s) => (start.advance(s), s == c),
+                None => (start, false),
+            }
+        };
+        self.push_characters(c.encode_utf8(&mut [0; 4]), Span { start, end }, verbatim);
+    }
+
+    fn emit_str(&mut self, text: &str) {
+        for c in text.chars() {
+            self.emit_char(c);
+        }
+    }
+
+    // Emits the decoded value of a character reference, which spans from the
+    // ampersand to the end of the reference.
+    fn emit_reference(&mut self, value: &str) {
+        let span = Span {
+            start: self.text_cursor,
+            end: self.input_position(),
+        };
+        self.push_characters(value, span, false);
+    }
+
+    fn push_characters(&mut self, text: &str, span: Span, verbatim: bool) {
+        let contiguous = self.characters_span.end == span.start;
+        if !(verbatim && self.characters_verbatim && contiguous) {
+            self.flush_characters();
+        }
+        if self.characters.is_empty() {
+            self.characters_span = span;
+            self.characters_verbatim = verbatim;
+        }
+        self.characters.push_str(text);
+        self.characters_span.end = span.end;
+        self.text_cursor = span.end;
+    }
+
+    fn flush_characters(&mut self) {
+        if !self.characters.is_empty() {
+            let characters = mem::take(&mut self.characters);
+            self.tokens
+                .push_back((Token::Characters(characters), self.characters_span));
+        }
+    }
+
+    fn emit(&mut self, token: Token) {
+        self.flush_characters();
+        let span = Span {
+            start: self.text_cursor,
+            end: self.parser.position(),
+        };
+        self.tokens.push_back((token, span));
+        self.text_cursor = span.end;
+    }
+
+    // Markup which produces no token is skipped over by the text cursor.
+    fn skip_text(&mut self) {
+        self.text_cursor = self.parser.position();
+    }
+
+    fn emit_current_cdata(&mut self) {
+        let text = mem::take(&mut self.current_cdata);
+        self.emit(Token::CData(text));
+    }
+
+    // The instruction's text is collected in the comment buffer; its target
+    // runs up to the first whitespace.
+    fn emit_current_processing_instruction(&mut self) {
+        let text = mem::take(&mut self.current_comment);
+        let (target, data) = match text.find(is_whitespace) {
+            Some(index) => (
+                &text[..index],
+                text[index..].trim_start_matches(is_whitespace),
+            ),
+            None => (text.as_str(), ""),
+        };
+        self.emit(Token::ProcessingInstruction(ProcessingInstruction {
+            target: String::from(target),
+            data: String::from(data),
+        }));
+    }
+
+    fn emit_eof(&mut self) {
+        self.emit(Token::Eof);
+        self.finished = true;
+    }
+
+    fn emit_current_tag(&mut self) {
+        self.finish_attribute();
+        let mut tag = mem::replace(&mut self.current_tag, new_tag());
+        tag.span = Span {
+            start: self.text_cursor,
+            end: self.parser.position(),
+        };
+        if self.current_tag_is_end {
+            if !tag.attributes.is_empty() {
+                self.error_at("end-tag-with-attributes", tag.span);
+            }
+            if tag.self_closing {
+                self.error_at("end-tag-with-trailing-solidus", tag.span);
+            }
+            self.emit(Token::EndTag(tag));
+        } else {
+            self.last_start_tag_name = Some(tag.name.clone());
+            self.emit(Token::StartTag(tag));
+        }
+    }
+
+    fn emit_current_comment(&mut self) {
+        let comment = mem::take(&mut self.current_comment);
+        self.emit(Token::Comment(comment));
+    }
+
+    fn emit_current_doctype(&mut self) {
+        let doctype = mem::replace(&mut self.current_doctype, new_doctype());
+        self.emit(Token::Doctype(doctype));
+    }
+
+    fn start_tag(&mut self, is_end: bool) {
+        self.current_tag = new_tag();
+        self.current_tag_is_end = is_end;
+        self.current_attribute = None;
+    }
+
+    fn start_attribute(&mut self) {
+        self.finish_attribute();
+        let start = Span::at(self.current_char_start);
+        self.current_attribute = Some(Attribute {
+            name: String::new(),
+            value: String::new(),
+            name_span: start,
+            value_span: start,
+        });
+        self.current_attribute_is_duplicate = false;
+    }
+
+    // Checked when leaving the attribute name state so the duplicate can be
+    // dropped once its value has been consumed.
+    fn check_duplicate_attribute(&mut self) {
+        let kept = match self.current_attribute {
+            Some(ref attribute) => self
+                .current_tag
+                .attributes
+                .iter()
+                .find(|existing| existing.name == attribute.name),
+            None => None,
+        };
+        if let Some(kept) = kept {
+            let span = self.current_attribute.as_ref().unwrap().name_span;
+            let message = format!(
+                "Duplicate attribute '{}', keeping the one at {}",
+                kept.name, kept.name_span
+            );
+            self.errors
+                .push(diagnostic::error("duplicate-attribute", span, &message));
+            self.current_attribute_is_duplicate = true;
+        }
+        // Attributes without a value get an empty value span after the name.
+        if let Some(ref mut attribute) = self.current_attribute {
+            attribute.value_span = Span::at(attribute.name_span.end);
+        }
+    }
+
+    fn start_attribute_value(&mut self, start: ParserPosition) {
+        if let Some(ref mut attribute) = self.current_attribute {
+            attribute.value_span = Span::at(start);
+        }
+    }
+
+    fn end_attribute_value(&mut self) {
+        if let Some(ref mut attribute) = self.current_attribute {
+            attribute.value_span.end = self.current_char_start;
+        }
+    }
+
+    fn finish_attribute(&mut self) {
+        if let Some(attribute) = self.current_attribute.take() {
+            if !self.current_attribute_is_duplicate {
+                self.current_tag.attributes.push(attribute);
+            }
+        }
+    }
+
+    fn push_attribute_name(&mut self, c: char) {
+        if let Some(ref mut attribute) = self.current_attribute {
+            attribute.name.push(c);
+            attribute.name_span.end = self.parser.position();
+        }
+    }
+
+    fn push_attribute_value(&mut self, c: char) {
+        if let Some(ref mut attribute) = self.current_attribute {
+            attribute.value.push(c);
+        }
+    }
+
+    fn is_appropriate_end_tag(&self) -> bool {
+        match self.last_start_tag_name {
+            Some(ref name) => *name == self.current_tag.name,
+            None => false,
+        }
+    }
+
+    fn consuming_in_attribute(&self) -> bool {
+        matches!(
+            self.return_state,
+            State::AttributeValueDoubleQuoted
+                | State::AttributeValueSingleQuoted
+                | State::AttributeValueUnquoted
+        )
+    }
+
+    fn flush_temporary_buffer_as_reference(&mut self) {
+        let buffer = mem::take(&mut self.temporary_buffer);
+        if self.consuming_in_attribute() {
+            if let Some(ref mut attribute) = self.current_attribute {
+                attribute.value.push_str(buffer.as_str());
+            }
+        } else {
+            self.emit_str(buffer.as_str());
+        }
+    }
+
+    fn flush_decoded_reference(&mut self, value: &str) {
+        if self.consuming_in_attribute() {
+            if let Some(ref mut attribute) = self.current_attribute {
+                attribute.value.push_str(value);
+            }
+        } else {
+            self.emit_reference(value);
+        }
+    }
+
+    // Shared by the RCDATA, RAWTEXT and script data end tag name states:
+    // anything which is not the appropriate end tag is emitted as text.
+    fn end_tag_name(&mut self, fallback: State) {
+        let c = self.next_input();
+        match c {
+            Some('\t') | Some('\n') | Some('\x0C') | Some(' ') if self.is_appropriate_end_tag() => {
+                self.state = State::BeforeAttributeName;
+            }
+            Some('/') if self.is_appropriate_end_tag() => {
+                self.state = State::SelfClosingStartTag;
+            }
+            Some('>') if self.is_appropriate_end_tag() => {
+                self.state = State::Data;
+                self.emit_current_tag();
+            }
+            Some(c) if c.is_ascii_alphabetic() => {
+                self.current_tag.name.push(c.to_ascii_lowercase());
+                self.temporary_buffer.push(c);
+            }
+            _ => {
+                self.emit_str("</");
+                let buffer = mem::take(&mut self.temporary_buffer);
+                self.emit_str(buffer.as_str());
+                self.reconsume_in(fallback);
+            }
+        }
+    }
+
+    fn step(&mut self) {
+        match self.state {
+            State::Data => match self.next_input() {
+                Some('&') => {
+                    self.return_state = State::Data;
+                    self.state = State::CharacterReference;
+                }
+                Some('<') => self.state = State::TagOpen,
+                Some('\0') => {
+                    self.error("unexpected-null-character");
+                    self.emit_char('\0');
+                }
+                Some(c) => self.emit_char(c),
+                None => self.emit_eof(),
+            },
+            State::Rcdata => match self.next_input() {
+                Some('&') => {
+                    self.return_state = State::Rcdata;
+                    self.state = State::CharacterReference;
+                }
+                Some('<') => self.state = State::RcdataLessThanSign,
+                Some('\0') => {
+                    self.error("unexpected-null-character");
+                    self.emit_char('\u{FFFD}');
+                }
+                Some(c) => self.emit_char(c),
+                None => self.emit_eof(),
+            },
+            State::Rawtext => match self.next_input() {
+                Some('<') => self.state = State::RawtextLessThanSign,
+                Some('\0') => {
+                    self.error("unexpected-null-character");
+                    self.emit_char('\u{FFFD}');
+                }
+                Some(c) => self.emit_char(c),
+                None => self.emit_eof(),
+            },
+            State::ScriptData => match self.next_input() {
+                Some('<') => self.state = State::ScriptDataLessThanSign,
+                Some('\0') => {
+                    self.error("unexpected-null-character");
+                    self.emit_char('\u{FFFD}');
+                }
+                Some(c) => self.emit_char(c),
+                None => self.emit_eof(),
+            },
+            State::Plaintext => match self.next_input() {
+                Some('\0') => {
+                    self.error("unexpected-null-character");
+                    self.emit_char('\u{FFFD}');
+                }
+                Some(c) => self.emit_char(c),
+                None => self.emit_eof(),
+            },
+            State::TagOpen => match self.next_input() {
+                Some('!') => self.state = State::MarkupDeclarationOpen,
+                Some('/') => self.state = State::EndTagOpen,
+                Some(c) if c.is_ascii_alphabetic() => {
+                    self.start_tag(false);
+                    self.reconsume_in(State::TagName);
+                }
+                Some('?') if self.xml_compatible && self.next_input_is_name_start() => {
+                    self.current_comment = String::new();
+                    self.state = State::ProcessingInstruction;
+                }
+                Some('?') => {
+                    self.error("unexpected-question-mark-instead-of-tag-name");
+                    self.current_comment = String::new();
+                    self.reconsume_in(State::BogusComment);
+                }
+                None => {
+                    self.error("eof-before-tag-name");
+                    self.emit_char('<');
+                    self.emit_eof();
+                }
+                Some(_) => {
+                    self.error("invalid-first-character-of-tag-name");
+                    self.emit_char('<');
+                    self.reconsume_in(State::Data);
+                }
+            },
+            State::EndTagOpen => match self.next_input() {
+                Some(c) if c.is_ascii_alphabetic() => {
+                    self.start_tag(true);
+                    self.reconsume_in(State::TagName);
+                }
+                Some('>') => {
+                    self.error("missing-end-tag-name");
+                    self.skip_text();
+                    self.state = State::Data;
+                }
+                None => {
+                    self.error("eof-before-tag-name");
+                    self.emit_str("</");
+                    self.emit_eof();
+                }
+                Some(_) => {
+                    self.error("invalid-first-character-of-tag-name");
+                    self.current_comment = String::new();
+                    self.reconsume_in(State::BogusComment);
+                }
+            },
+            State::TagName => match self.next_input() {
+                Some('\t') | Some('\n') | Some('\x0C') | Some(' ') => {
+                    self.state = State::BeforeAttributeName;
+                }
+                Some('/') => self.state = State::SelfClosingStartTag,
+                Some('>') => {
+                    self.state = State::Data;
+                    self.emit_current_tag();
+                }
+                Some('\0') => {
+                    self.error("unexpected-null-character");
+                    self.current_tag.name.push('\u{FFFD}');
+                }
+                Some(c) => self.current_tag.name.push(c.to_ascii_lowercase()),
+                None => {
+                    self.error("eof-in-tag");
+                    self.emit_eof();
+                }
+            },
+            State::RcdataLessThanSign => match self.next_input() {
+                Some('/') => {
+                    self.temporary_buffer = String::new();
+                    self.state = State::RcdataEndTagOpen;
+                }
+                _ => {
+                    self.emit_char('<');
+                    self.reconsume_in(State::Rcdata);
+                }
+            },
+            State::RcdataEndTagOpen => match self.next_input() {
+                Some(c) if c.is_ascii_alphabetic() => {
+                    self.start_tag(true);
+                    self.reconsume_in(State::RcdataEndTagName);
+                }
+                _ => {
+                    self.emit_str("</");
+                    self.reconsume_in(State::Rcdata);
+                }
+            },
+            State::RcdataEndTagName => self.end_tag_name(State::Rcdata),
+            State::RawtextLessThanSign => match self.next_input() {
+                Some('/') => {
+                    self.temporary_buffer = String::new();
+                    self.state = State::RawtextEndTagOpen;
+                }
+                _ => {
+                    self.emit_char('<');
+                    self.reconsume_in(State::Rawtext);
+                }
+            },
+            State::RawtextEndTagOpen => match self.next_input() {
+                Some(c) if c.is_ascii_alphabetic() => {
+                    self.start_tag(true);
+                    self.reconsume_in(State::RawtextEndTagName);
+                }
+                _ => {
+                    self.emit_str("</");
+                    self.reconsume_in(State::Rawtext);
+                }
+            },
+            State::RawtextEndTagName => self.end_tag_name(State::Rawtext),
+            State::ScriptDataLessThanSign => match self.next_input() {
+                Some('/') => {
+                    self.temporary_buffer = String::new();
+                    self.state = State::ScriptDataEndTagOpen;
+                }
+                Some('!') => {
+                    self.state = State::ScriptDataEscapeStart;
+                    self.emit_str("<!");
+                }
+                _ => {
+                    self.emit_char('<');
+                    self.reconsume_in(State::ScriptData);
+                }
+            },
+            State::ScriptDataEndTagOpen => match self.next_input() {
+                Some(c) if c.is_ascii_alphabetic() => {
+                    self.start_tag(true);
+                    self.reconsume_in(State::ScriptDataEndTagName);
+                }
+                _ => {
+                    self.emit_str("</");
+                    self.reconsume_in(State::ScriptData);
+                }
+            },
+            State::ScriptDataEndTagName => self.end_tag_name(State::ScriptData),
+            State::ScriptDataEscapeStart => match self.next_input() {
+                Some('-') => {
+                    self.state = State::ScriptDataEscapeStartDash;
+                    self.emit_char('-');
+                }
+                _ => self.reconsume_in(State::ScriptData),
+            },
+            State::ScriptDataEscapeStartDash => match self.next_input() {
+                Some('-') => {
+                    self.state = State::ScriptDataEscapedDashDash;
+                    self.emit_char('-');
+                }
+                _ => self.reconsume_in(State::ScriptData),
+            },
+            State::ScriptDataEscaped => match self.next_input() {
+                Some('-') => {
+                    self.state = State::ScriptDataEscapedDash;
+                    self.emit_char('-');
+                }
+                Some('<') => self.state = State::ScriptDataEscapedLessThanSign,
+                Some('\0') => {
+                    self.error("unexpected-null-character");
+                    self.emit_char('\u{FFFD}');
+                }
+                Some(c) => self.emit_char(c),
+                None => {
+                    self.error("eof-in-script-html-comment-like-text");
+                    self.emit_eof();
+                }
+            },
+            State::ScriptDataEscapedDash => match self.next_input() {
+                Some('-') => {
+                    self.state = State::ScriptDataEscapedDashDash;
+                    self.emit_char('-');
+                }
+                Some('<') => self.state = State::ScriptDataEscapedLessThanSign,
+                Some('\0') => {
+                    self.error("unexpected-null-character");
+                    self.state = State::ScriptDataEscaped;
+                    self.emit_char('\u{FFFD}');
+                }
+                Some(c) => {
+                    self.state = State::ScriptDataEscaped;
+                    self.emit_char(c);
+                }
+                None => {
+                    self.error("eof-in-script-html-comment-like-text");
+                    self.emit_eof();
+                }
+            },
+            State::ScriptDataEscapedDashDash => match self.next_input() {
+                Some('-') => self.emit_char('-'),
+                Some('<') => self.state = State::ScriptDataEscapedLessThanSign,
+                Some('>') => {
+                    self.state = State::ScriptData;
+                    self.emit_char('>');
+                }
+                Some('\0') => {
+                    self.error("unexpected-null-character");
+                    self.state = State::ScriptDataEscaped;
+                    self.emit_char('\u{FFFD}');
+                }
+                Some(c) => {
+                    self.state = State::ScriptDataEscaped;
+                    self.emit_char(c);
+                }
+                None => {
+                    self.error("eof-in-script-html-comment-like-text");
+                    self.emit_eof();
+                }
+            },
+            State::ScriptDataEscapedLessThanSign => match self.next_input() {
+                Some('/') => {
+                    self.temporary_buffer = String::new();
+                    self.state = State::ScriptDataEscapedEndTagOpen;
+                }
+                Some(c) if c.is_ascii_alphabetic() => {
+                    self.temporary_buffer = String::new();
+                    self.emit_char('<');
+                    self.reconsume_in(State::ScriptDataDoubleEscapeStart);
+                }
+                _ => {
+                    self.emit_char('<');
+                    self.reconsume_in(State::ScriptDataEscaped);
+                }
+            },
+            State::ScriptDataEscapedEndTagOpen => match self.next_input() {
+                Some(c) if c.is_ascii_alphabetic() => {
+                    self.start_tag(true);
+                    self.reconsume_in(State::ScriptDataEscapedEndTagName);
+                }
+                _ => {
+                    self.emit_str("</");
+                    self.reconsume_in(State::ScriptDataEscaped);
+                }
+            },
+            State::ScriptDataEscapedEndTagName => self.end_tag_name(State::ScriptDataEscaped),
+            State::ScriptDataDoubleEscapeStart => match self.next_input() {
+                Some(c) if is_whitespace(c) || c == '/' || c == '>' => {
+                    self.state = if self.temporary_buffer == "script" {
+                        State::ScriptDataDoubleEscaped
+                    } else {
+                        State::ScriptDataEscaped
+                    };
+                    self.emit_char(c);
+                }
+                Some(c) if c.is_ascii_alphabetic() => {
+                    self.temporary_buffer.push(c.to_ascii_lowercase());
+                    self.emit_char(c);
+                }
+                _ => self.reconsume_in(State::ScriptDataEscaped),
+            },
+            State::ScriptDataDoubleEscaped => match self.next_input() {
+                Some('-') => {
+                    self.state = State::ScriptDataDoubleEscapedDash;
+                    self.emit_char('-');
+                }
+                Some('<') => {
+                    self.state = State::ScriptDataDoubleEscapedLessThanSign;
+                    self.emit_char('<');
+                }
+                Some('\0') => {
+                    self.error("unexpected-null-character");
+                    self.emit_char('\u{FFFD}');
+                }
+                Some(c) => self.emit_char(c),
+                None => {
+                    self.error("eof-in-script-html-comment-like-text");
+                    self.emit_eof();
+                }
+            },
+            State::ScriptDataDoubleEscapedDash => match self.next_input() {
+                Some('-') => {
+                    self.state = State::ScriptDataDoubleEscapedDashDash;
+                    self.emit_char('-');
+                }
+                Some('<') => {
+                    self.state = State::ScriptDataDoubleEscapedLessThanSign;
+                    self.emit_char('<');
+                }
+                Some('\0') => {
+                    self.error("unexpected-null-character");
+                    self.state = State::ScriptDataDoubleEscaped;
+                    self.emit_char('\u{FFFD}');
+                }
+                Some(c) => {
+                    self.state = State::ScriptDataDoubleEscaped;
+                    self.emit_char(c);
+                }
+                None => {
+                    self.error("eof-in-script-html-comment-like-text");
+                    self.emit_eof();
+                }
+            },
+            State::ScriptDataDoubleEscapedDashDash => match self.next_input() {
+                Some('-') => self.emit_char('-'),
+                Some('<') => {
+                    self.state = State::ScriptDataDoubleEscapedLessThanSign;
+                    self.emit_char('<');
+                }
+                Some('>') => {
+                    self.state = State::ScriptData;
+                    self.emit_char('>');
+                }
+                Some('\0') => {
+                    self.error("unexpected-null-character");
+                    self.state = State::ScriptDataDoubleEscaped;
+                    self.emit_char('\u{FFFD}');
+                }
+                Some(c) => {
+                    self.state = State::ScriptDataDoubleEscaped;
+                    self.emit_char(c);
+                }
+                None => {
+                    self.error("eof-in-script-html-comment-like-text");
+                    self.emit_eof();
+                }
+            },
+            State::ScriptDataDoubleEscapedLessThanSign => match self.next_input() {
+                Some('/') => {
+                    self.temporary_buffer = String::new();
+                    self.state = State::ScriptDataDoubleEscapeEnd;
+                    self.emit_char('/');
+                }
+                _ => self.reconsume_in(State::ScriptDataDoubleEscaped),
+            },
+            State::ScriptDataDoubleEscapeEnd => match self.next_input() {
+                Some(c) if is_whitespace(c) || c == '/' || c == '>' => {
+                    self.state = if self.temporary_buffer == "script" {
+                        State::ScriptDataEscaped
+                    } else {
+                        State::ScriptDataDoubleEscaped
+                    };
+                    self.emit_char(c);
+                }
+                Some(c) if c.is_ascii_alphabetic() => {
+                    self.temporary_buffer.push(c.to_ascii_lowercase());
+                    self.emit_char(c);
+                }
+                _ => self.reconsume_in(State::ScriptDataDoubleEscaped),
+            },
+            State::BeforeAttributeName => match self.next_input() {
+                Some('\t') | Some('\n') | Some('\x0C') | Some(' ') => {}
+                Some('/') | Some('>') | None => self.reconsume_in(State::AfterAttributeName),
+                Some('=') => {
+                    self.error("unexpected-equals-sign-before-attribute-name");
+                    self.start_attribute();
+                    self.push_attribute_name('=');
+                    self.state = State::AttributeName;
+                }
+                Some(_) => {
+                    self.start_attribute();
+                    self.reconsume_in(State::AttributeName);
+                }
+            },
+            State::AttributeName => match self.next_input() {
+                Some('\t') | Some('\n') | Some('\x0C') | Some(' ') | Some('/') | Some('>')
+                | None => {
+                    self.check_duplicate_attribute();
+                    self.reconsume_in(State::AfterAttributeName);
+                }
+                Some('=') => {
+                    self.check_duplicate_attribute();
+                    self.state = State::BeforeAttributeValue;
+                }
+                Some('\0') => {
+                    self.error("unexpected-null-character");
+                    self.push_attribute_name('\u{FFFD}');
+                }
+                Some(c) => {
+                    if c == '"' || c == '\'' || c == '<' {
+                        self.error("unexpected-character-in-attribute-name");
+                    }
+                    self.push_attribute_name(c.to_ascii_lowercase());
+                }
+            },
+            State::AfterAttributeName => match self.next_input() {
+                Some('\t') | Some('\n') | Some('\x0C') | Some(' ') => {}
+                Some('/') => self.state = State::SelfClosingStartTag,
+                Some('=') => self.state = State::BeforeAttributeValue,
+                Some('>') => {
+                    self.state = State::Data;
+                    self.emit_current_tag();
+                }
+                None => {
+                    self.error("eof-in-tag");
+                    self.emit_eof();
+                }
+                Some(_) => {
+                    self.start_attribute();
+                    self.reconsume_in(State::AttributeName);
+                }
+            },
+            State::BeforeAttributeValue => match self.next_input() {
+                Some('\t') | Some('\n') | Some('\x0C') | Some(' ') => {}
+                Some('"') => {
+                    let start = self.parser.position();
+                    self.start_attribute_value(start);
+                    self.state = State::AttributeValueDoubleQuoted;
+                }
+                Some('\'') => {
+                    let start = self.parser.position();
+                    self.start_attribute_value(start);
+                    self.state = State::AttributeValueSingleQuoted;
+                }
+                Some('>') => {
+                    self.error("missing-attribute-value");
+                    self.state = State::Data;
+                    self.emit_current_tag();
+                }
+                _ => {
+                    let start = self.current_char_start;
+                    self.start_attribute_value(start);
+                    self.reconsume_in(State::AttributeValueUnquoted);
+                }
+            },
+            State::AttributeValueDoubleQuoted => self.quoted_attribute_value('"'),
+            State::AttributeValueSingleQuoted => self.quoted_attribute_value('\''),
+            State::AttributeValueUnquoted => match self.next_input() {
+                Some('\t') | Some('\n') | Some('\x0C') | Some(' ') => {
+                    self.end_attribute_value();
+                    self.state = State::BeforeAttributeName;
+                }
+                Some('&') => {
+                    self.return_state = State::AttributeValueUnquoted;
+                    self.state = State::CharacterReference;
+                }
+                Some('>') => {
+                    self.end_attribute_value();
+                    self.state = State::Data;
+                    self.emit_current_tag();
+                }
+                Some('\0') => {
+                    self.error("unexpected-null-character");
+                    self.push_attribute_value('\u{FFFD}');
+                }
+                Some(c) => {
+                    if c == '"' || c == '\'' || c == '<' || c == '=' || c == '`' {
+                        self.error("unexpected-character-in-unquoted-attribute-value");
+                    }
+                    self.push_attribute_value(c);
+                }
+                None => {
+                    self.error("eof-in-tag");
+                    self.emit_eof();
+                }
+            },
+            State::AfterAttributeValueQuoted => match self.next_input() {
+                Some('\t') | Some('\n') | Some('\x0C') | Some(' ') => {
+                    self.state = State::BeforeAttributeName;
+                }
+                Some('/') => self.state = State::SelfClosingStartTag,
+                Some('>') => {
+                    self.state = State::Data;
+                    self.emit_current_tag();
+                }
+                None => {
+                    self.error("eof-in-tag");
+                    self.emit_eof();
+                }
+                Some(_) => {
+                    self.error("missing-whitespace-between-attributes");
+                    self.reconsume_in(State::BeforeAttributeName);
+                }
+            },
+            State::SelfClosingStartTag => match self.next_input() {
+                Some('>') => {
+                    self.current_tag.self_closing = true;
+                    self.state = State::Data;
+                    self.emit_current_tag();
+                }
+                None => {
+                    self.error("eof-in-tag");
+                    self.emit_eof();
+                }
+                Some(_) => {
+                    self.error("unexpected-solidus-in-tag");
+                    self.reconsume_in(State::BeforeAttributeName);
+                }
+            },
+            State::BogusComment => match self.next_input() {
+                Some('>') => {
+                    self.state = State::Data;
+                    self.emit_current_comment();
+                }
+                None => {
+                    self.emit_current_comment();
+                    self.emit_eof();
+                }
+                Some('\0') => {
+                    self.error("unexpected-null-character");
+                    self.current_comment.push('\u{FFFD}');
+                }
+                Some(c) => self.current_comment.push(c),
+            },
+            State::ProcessingInstruction => match self.next_input() {
+                Some('?') => self.state = State::ProcessingInstructionQuestionMark,
+                None => {
+                    self.error("eof-in-processing-instruction");
+                    self.emit_current_processing_instruction();
+                    self.emit_eof();
+                }
+                Some('\0') => {
+                    self.error("unexpected-null-character");
+                    self.current_comment.push('\u{FFFD}');
+                }
+                Some(c) => self.current_comment.push(c),
+            },
+            State::ProcessingInstructionQuestionMark => match self.next_input() {
+                Some('>') => {
+                    self.state = State::Data;
+                    self.emit_current_processing_instruction();
+                }
+                Some('?') => self.current_comment.push('?'),
+                _ => {
+                    self.current_comment.push('?');
+                    self.reconsume_in(State::ProcessingInstruction);
+                }
+            },
+            State::MarkupDeclarationOpen => {
+                if self.next_input_is("--") {
+                    self.skip_input(2);
+                    self.current_comment = String::new();
+                    self.state = State::CommentStart;
+                } else if self.next_input_is_ignore_case("DOCTYPE") {
+                    self.skip_input(7);
+                    self.state = State::Doctype;
+                } else if self.next_input_is("[CDATA[") {
+                    self.skip_input(7);
+                    if self.allow_cdata {
+                        self.current_cdata = String::new();
+                        self.state = State::CdataSection;
+                    } else {
+                        self.error("cdata-in-html-content");
+                        self.current_comment = String::from("[CDATA[");
+                        self.state = State::BogusComment;
+                    }
+                } else {
+                    self.error("incorrectly-opened-comment");
+                    self.current_comment = String::new();
+                    self.state = State::BogusComment;
+                }
+            }
+            State::CommentStart => match self.next_input() {
+                Some('-') => self.state = State::CommentStartDash,
+                Some('>') => {
+                    self.error("abrupt-closing-of-empty-comment");
+                    self.state = State::Data;
+                    self.emit_current_comment();
+                }
+                _ => self.reconsume_in(State::Comment),
+            },
+            State::CommentStartDash => match self.next_input() {
+                Some('-') => self.state = State::CommentEnd,
+                Some('>') => {
+                    self.error("abrupt-closing-of-empty-comment");
+                    self.state = State::Data;
+                    self.emit_current_comment();
+                }
+                None => {
+                    self.error("eof-in-comment");
+                    self.emit_current_comment();
+                    self.emit_eof();
+                }
+                Some(_) => {
+                    self.current_comment.push('-');
+                    self.reconsume_in(State::Comment);
+                }
+            },
+            State::Comment => match self.next_input() {
+                Some('<') => {
+                    self.current_comment.push('<');
+                    self.state = State::CommentLessThanSign;
+                }
+                Some('-') => self.state = State::CommentEndDash,
+                Some('\0') => {
+                    self.error("unexpected-null-character");
+                    self.current_comment.push('\u{FFFD}');
+                }
+                Some(c) => self.current_comment.push(c),
+                None => {
+                    self.error("eof-in-comment");
+                    self.emit_current_comment();
+                    self.emit_eof();
+                }
+            },
+            State::CommentLessThanSign => match self.next_input() {
+                Some('!') => {
+                    self.current_comment.push('!');
+                    self.state = State::CommentLessThanSignBang;
+                }
+                Some('<') => self.current_comment.push('<'),
+                _ => self.reconsume_in(State::Comment),
+            },
+            State::CommentLessThanSignBang => match self.next_input() {
+                Some('-') => self.state = State::CommentLessThanSignBangDash,
+                _ => self.reconsume_in(State::Comment),
+            },
+            State::CommentLessThanSignBangDash => match self.next_input() {
+                Some('-') => self.state = State::CommentLessThanSignBangDashDash,
+                _ => self.reconsume_in(State::CommentEndDash),
+            },
+            State::CommentLessThanSignBangDashDash => match self.next_input() {
+                Some('>') | None => self.reconsume_in(State::CommentEnd),
+                _ => {
+                    self.error("nested-comment");
+                    self.reconsume_in(State::CommentEnd);
+                }
+            },
+            State::CommentEndDash => match self.next_input() {
+                Some('-') => self.state = State::CommentEnd,
+                None => {
+                    self.error("eof-in-comment");
+                    self.emit_current_comment();
+                    self.emit_eof();
+                }
+                Some(_) => {
+                    self.current_comment.push('-');
+                    self.reconsume_in(State::Comment);
+                }
+            },
+            State::CommentEnd => match self.next_input() {
+                Some('>') => {
+                    self.state = State::Data;
+                    self.emit_current_comment();
+                }
+                Some('!') => self.state = State::CommentEndBang,
+                Some('-') => self.current_comment.push('-'),
+                None => {
+                    self.error("eof-in-comment");
+                    self.emit_current_comment();
+                    self.emit_eof();
+                }
+                Some(_) => {
+                    self.current_comment.push_str("--");
+                    self.reconsume_in(State::Comment);
+                }
+            },
+            State::CommentEndBang => match self.next_input() {
+                Some('-') => {
+                    self.current_comment.push_str("--!");
+                    self.state = State::CommentEndDash;
+                }
+                Some('>') => {
+                    self.error("incorrectly-closed-comment");
+                    self.state = State::Data;
+                    self.emit_current_comment();
+                }
+                None => {
+                    self.error("eof-in-comment");
+                    self.emit_current_comment();
+                    self.emit_eof();
+                }
+                Some(_) => {
+                    self.current_comment.push_str("--!");
+                    self.reconsume_in(State::Comment);
+                }
+            },
+            State::Doctype => match self.next_input() {
+                Some('\t') | Some('\n') | Some('\x0C') | Some(' ') => {
+                    self.state = State::BeforeDoctypeName;
+                }
+                Some('>') => self.reconsume_in(State::BeforeDoctypeName),
+                None => {
+                    self.error("eof-in-doctype");
+                    self.current_doctype = new_doctype();
+                    self.current_doctype.force_quirks = true;
+                    self.emit_current_doctype();
+                    self.emit_eof();
+                }
+                Some(_) => {
+                    self.error("missing-whitespace-before-doctype-name");
+                    self.reconsume_in(State::BeforeDoctypeName);
+                }
+            },
+            State::BeforeDoctypeName => match self.next_input() {
+                Some('\t') | Some('\n') | Some('\x0C') | Some(' ') => {}
+                Some('\0') => {
+                    self.error("unexpected-null-character");
+                    self.current_doctype = new_doctype();
+                    self.current_doctype.name = Some(String::from("\u{FFFD}"));
+                    self.state = State::DoctypeName;
+                }
+                Some('>') => {
+                    self.error("missing-doctype-name");
+                    self.current_doctype = new_doctype();
+                    self.current_doctype.force_quirks = true;
+                    self.state = State::Data;
+                    self.emit_current_doctype();
+                }
+                None => {
+                    self.error("eof-in-doctype");
+                    self.current_doctype = new_doctype();
+                    self.current_doctype.force_quirks = true;
+                    self.emit_current_doctype();
+                    self.emit_eof();
+                }
+                Some(c) => {
+                    self.current_doctype = new_doctype();
+                    self.current_doctype.name = Some(c.to_ascii_lowercase().to_string());
+                    self.state = State::DoctypeName;
+                }
+            },
+            State::DoctypeName => match self.next_input() {
+                Some('\t') | Some('\n') | Some('\x0C') | Some(' ') => {
+                    self.state = State::AfterDoctypeName;
+                }
+                Some('>') => {
+                    self.state = State::Data;
+                    self.emit_current_doctype();
+                }
+                Some('\0') => {
+                    self.error("unexpected-null-character");
+                    push_optional(&mut self.current_doctype.name, '\u{FFFD}');
+                }
+                Some(c) => push_optional(&mut self.current_doctype.name, c.to_ascii_lowercase()),
+                None => self.eof_in_doctype(),
+            },
+            State::AfterDoctypeName => {
+                // The keyword check looks at the current character as well as
+                // the ones following it.
+                match self.next_input() {
+                    Some('\t') | Some('\n') | Some('\x0C') | Some(' ') => {}
+                    Some('>') => {
+                        self.state = State::Data;
+                        self.emit_current_doctype();
+                    }
+                    None => self.eof_in_doctype(),
+                    Some(c) => {
+                        let keyword_rest = |keyword: &str| {
+                            c.eq_ignore_ascii_case(&keyword.chars().next().unwrap())
+                                && self.next_input_is_ignore_case(&keyword[1..])
+                        };
+                        if keyword_rest("PUBLIC") {
+                            self.skip_input(5);
+                            self.state = State::AfterDoctypePublicKeyword;
+                        } else if keyword_rest("SYSTEM") {
+                            self.skip_input(5);
+                            self.state = State::AfterDoctypeSystemKeyword;
+                        } else {
+                            self.error("invalid-character-sequence-after-doctype-name");
+                            self.current_doctype.force_quirks = true;
+                            self.reconsume_in(State::BogusDoctype);
+                        }
+                    }
+                }
+            }
+            State::AfterDoctypePublicKeyword => match self.next_input() {
+                Some('\t') | Some('\n') | Some('\x0C') | Some(' ') => {
+                    self.state = State::BeforeDoctypePublicIdentifier;
+                }
+                Some('"') => {
+                    self.error("missing-whitespace-after-doctype-public-keyword");
+                    self.current_doctype.public_id = Some(String::new());
+                    self.state = State::DoctypePublicIdentifierDoubleQuoted;
+                }
+                Some('\'') => {
+                    self.error("missing-whitespace-after-doctype-public-keyword");
+                    self.current_doctype.public_id = Some(String::new());
+                    self.state = State::DoctypePublicIdentifierSingleQuoted;
+                }
+                Some('>') => {
+                    self.error("missing-doctype-public-identifier");
+                    self.current_doctype.force_quirks = true;
+                    self.state = State::Data;
+                    self.emit_current_doctype();
+                }
+                None => self.eof_in_doctype(),
+                Some(_) => {
+                    self.error("missing-quote-before-doctype-public-identifier");
+                    self.current_doctype.force_quirks = true;
+                    self.reconsume_in(State::BogusDoctype);
+                }
+            },
+            State::BeforeDoctypePublicIdentifier => match self.next_input() {
+                Some('\t') | Some('\n') | Some('\x0C') | Some(' ') => {}
+                Some('"') => {
+                    self.current_doctype.public_id = Some(String::new());
+                    self.state = State::DoctypePublicIdentifierDoubleQuoted;
+                }
+                Some('\'') => {
+                    self.current_doctype.public_id = Some(String::new());
+                    self.state = State::DoctypePublicIdentifierSingleQuoted;
+                }
+                Some('>') => {
+                    self.error("missing-doctype-public-identifier");
+                    self.current_doctype.force_quirks = true;
+                    self.state = State::Data;
+                    self.emit_current_doctype();
+                }
+                None => self.eof_in_doctype(),
+                Some(_) => {
+                    self.error("missing-quote-before-doctype-public-identifier");
+                    self.current_doctype.force_quirks = true;
+                    self.reconsume_in(State::BogusDoctype);
+                }
+            },
+            State::DoctypePublicIdentifierDoubleQuoted => self.doctype_identifier('"', true),
+            State::DoctypePublicIdentifierSingleQuoted => self.doctype_identifier('\'', true),
+            State::AfterDoctypePublicIdentifier => match self.next_input() {
+                Some('\t') | Some('\n') | Some('\x0C') | Some(' ') => {
+                    self.state = State::BetweenDoctypePublicAndSystemIdentifiers;
+                }
+                Some('>') => {
+                    self.state = State::Data;
+                    self.emit_current_doctype();
+                }
+                Some('"') => {
+                    self.error("missing-whitespace-between-doctype-public-and-system-identifiers");
+                    self.current_doctype.system_id = Some(String::new());
+                    self.state = State::DoctypeSystemIdentifierDoubleQuoted;
+                }
+                Some('\'') => {
+                    self.error("missing-whitespace-between-doctype-public-and-system-identifiers");
+                    self.current_doctype.system_id = Some(String::new());
+                    self.state = State::DoctypeSystemIdentifierSingleQuoted;
+                }
+                None => self.eof_in_doctype(),
+                Some(_) => {
+                    self.error("missing-quote-before-doctype-system-identifier");
+                    self.current_doctype.force_quirks = true;
+                    self.reconsume_in(State::BogusDoctype);
+                }
+            },
+            State::BetweenDoctypePublicAndSystemIdentifiers => match self.next_input() {
+                Some('\t') | Some('\n') | Some('\x0C') | Some(' ') => {}
+                Some('>') => {
+                    self.state = State::Data;
+                    self.emit_current_doctype();
+                }
+                Some('"') => {
+                    self.current_doctype.system_id = Some(String::new());
+                    self.state = State::DoctypeSystemIdentifierDoubleQuoted;
+                }
+                Some('\'') => {
+                    self.current_doctype.system_id = Some(String::new());
+                    self.state = State::DoctypeSystemIdentifierSingleQuoted;
+                }
+                None => self.eof_in_doctype(),
+                Some(_) => {
+                    self.error("missing-quote-before-doctype-system-identifier");
+                    self.current_doctype.force_quirks = true;
+                    self.reconsume_in(State::BogusDoctype);
+                }
+            },
+            State::AfterDoctypeSystemKeyword => match self.next_input() {
+                Some('\t') | Some('\n') | Some('\x0C') | Some(' ') => {
+                    self.state = State::BeforeDoctypeSystemIdentifier;
+                }
+                Some('"') => {
+                    self.error("missing-whitespace-after-doctype-system-keyword");
+                    self.current_doctype.system_id = Some(String::new());
+                    self.state = State::DoctypeSystemIdentifierDoubleQuoted;
+                }
+                Some('\'') => {
+                    self.error("missing-whitespace-after-doctype-system-keyword");
+                    self.current_doctype.system_id = Some(String::new());
+                    self.state = State::DoctypeSystemIdentifierSingleQuoted;
+                }
+                Some('>') => {
+                    self.error("missing-doctype-system-identifier");
+                    self.current_doctype.force_quirks = true;
+                    self.state = State::Data;
+                    self.emit_current_doctype();
+                }
+                None => self.eof_in_doctype(),
+                Some(_) => {
+                    self.error("missing-quote-before-doctype-system-identifier");
+                    self.current_doctype.force_quirks = true;
+                    self.reconsume_in(State::BogusDoctype);
+                }
+            },
+            State::BeforeDoctypeSystemIdentifier => match self.next_input() {
+                Some('\t') | Some('\n') | Some('\x0C') | Some(' ') => {}
+                Some('"') => {
+                    self.current_doctype.system_id = Some(String::new());
+                    self.state = State::DoctypeSystemIdentifierDoubleQuoted;
+                }
+                Some('\'') => {
+                    self.current_doctype.system_id = Some(String::new());
+                    self.state = State::DoctypeSystemIdentifierSingleQuoted;
+                }
+                Some('>') => {
+                    self.error("missing-doctype-system-identifier");
+                    self.current_doctype.force_quirks = true;
+                    self.state = State::Data;
+                    self.emit_current_doctype();
+                }
+                None => self.eof_in_doctype(),
+                Some(_) => {
+                    self.error("missing-quote-before-doctype-system-identifier");
+                    self.current_doctype.force_quirks = true;
+                    self.reconsume_in(State::BogusDoctype);
+                }
+            },
+            State::DoctypeSystemIdentifierDoubleQuoted => self.doctype_identifier('"', false),
+            State::DoctypeSystemIdentifierSingleQuoted => self.doctype_identifier('\'', false),
+            State::AfterDoctypeSystemIdentifier => match self.next_input() {
+                Some('\t') | Some('\n') | Some('\x0C') | Some(' ') => {}
+                Some('>') => {
+                    self.state = State::Data;
+                    self.emit_current_doctype();
+                }
+                None => self.eof_in_doctype(),
+                Some(_) => {
+                    self.error("unexpected-character-after-doctype-system-identifier");
+                    self.reconsume_in(State::BogusDoctype);
+                }
+            },
+            State::BogusDoctype => match self.next_input() {
+                Some('>') => {
+                    self.state = State::Data;
+                    self.emit_current_doctype();
+                }
+                Some('\0') => self.error("unexpected-null-character"),
+                Some(_) => {}
+                None => {
+                    self.emit_current_doctype();
+                    self.emit_eof();
+                }
+            },
+            State::CdataSection => match self.next_input() {
+                Some(']') => self.state = State::CdataSectionBracket,
+                Some(c) => self.current_cdata.push(c),
+                None => {
+                    self.error("eof-in-cdata");
+                    self.emit_current_cdata();
+                    self.emit_eof();
+                }
+            },
+            State::CdataSectionBracket => match self.next_input() {
+                Some(']') => self.state = State::CdataSectionEnd,
+                _ => {
+                    self.current_cdata.push(']');
+                    self.reconsume_in(State::CdataSection);
+                }
+            },
+            State::CdataSectionEnd => match self.next_input() {
+                Some(']') => self.current_cdata.push(']'),
+                Some('>') => {
+                    self.emit_current_cdata();
+                    self.state = State::Data;
+                }
+                _ => {
+                    self.current_cdata.push_str("]]");
+                    self.reconsume_in(State::CdataSection);
+                }
+            },
+            State::CharacterReference => {
+                // Only peek here so the named reference lookup can see the
+                // whole candidate name in the remaining input.
+                self.temporary_buffer = String::from("&");
+                match self.parser.remaining().chars().next() {
+                    Some(c) if c.is_ascii_alphanumeric() => {
+                        self.state = State::NamedCharacterReference;
+                    }
+                    Some('#') => {
+                        self.next_input();
+                        self.temporary_buffer.push('#');
+                        self.state = State::NumericCharacterReference;
+                    }
+                    _ => {
+                        self.flush_temporary_buffer_as_reference();
+                        self.state = self.return_state;
+                    }
+                }
+            }
+            State::NamedCharacterReference => self.named_character_reference(),
+            State::AmbiguousAmpersand => match self.next_input() {
+                Some(c) if c.is_ascii_alphanumeric() => {
+                    if self.consuming_in_attribute() {
+                        self.push_attribute_value(c);
+                    } else {
+                        self.emit_char(c);
+                    }
+                }
+                Some(';') => {
+                    self.error("unknown-named-character-reference");
+                    let return_state = self.return_state;
+                    self.reconsume_in(return_state);
+                }
+                _ => {
+                    let return_state = self.return_state;
+                    self.reconsume_in(return_state);
+                }
+            },
+            State::NumericCharacterReference => {
+                self.character_reference_code = 0;
+                match self.next_input() {
+                    Some(c) if c == 'x' || c == 'X' => {
+                        self.temporary_buffer.push(c);
+                        self.state = State::HexadecimalCharacterReferenceStart;
+                    }
+                    _ => self.reconsume_in(State::DecimalCharacterReferenceStart),
+                }
+            }
+            State::HexadecimalCharacterReferenceStart => match self.next_input() {
+                Some(c) if c.is_ascii_hexdigit() => {
+                    self.reconsume_in(State::HexadecimalCharacterReference);
+                }
+                _ => self.absence_of_digits(),
+            },
+            State::DecimalCharacterReferenceStart => match self.next_input() {
+                Some(c) if c.is_ascii_digit() => {
+                    self.reconsume_in(State::DecimalCharacterReference);
+                }
+                _ => self.absence_of_digits(),
+            },
+            State::HexadecimalCharacterReference => match self.next_input() {
+                Some(c) if c.is_ascii_hexdigit() => {
+                    self.add_to_character_reference_code(c.to_digit(16).unwrap(), 16);
+                }
+                Some(';') => self.state = State::NumericCharacterReferenceEnd,
+                _ => {
+                    self.error("missing-semicolon-after-character-reference");
+                    self.reconsume_in(State::NumericCharacterReferenceEnd);
+                }
+            },
+            State::DecimalCharacterReference => match self.next_input() {
+                Some(c) if c.is_ascii_digit() => {
+                    self.add_to_character_reference_code(c.to_digit(10).unwrap(), 10);
+                }
+                Some(';') => self.state = State::NumericCharacterReferenceEnd,
+                _ => {
+                    self.error("missing-semicolon-after-character-reference");
+                    self.reconsume_in(State::NumericCharacterReferenceEnd);
+                }
+            },
+            State::NumericCharacterReferenceEnd => self.numeric_character_reference_end(),
+        }
+    }
+
+    fn eof_in_doctype(&mut self) {
+        self.error("eof-in-doctype");
+        self.current_doctype.force_quirks = true;
+        self.emit_current_doctype();
+        self.emit_eof();
+    }
+
+    fn quoted_attribute_value(&mut self, quote: char) {
+        match self.next_input() {
+            Some(c) if c == quote => {
+                self.end_attribute_value();
+                self.state = State::AfterAttributeValueQuoted;
+            }
+            Some('&') => {
+                self.return_state = self.state;
+                self.state = State::CharacterReference;
+            }
+            Some('\0') => {
+                self.error("unexpected-null-character");
+                self.push_attribute_value('\u{FFFD}');
+            }
+            Some(c) => self.push_attribute_value(c),
+            None => {
+                self.error("eof-in-tag");
+                self.emit_eof();
+            }
+        }
+    }
+
+    fn doctype_identifier(&mut self, quote: char, public: bool) {
+        let after_state = if public {
+            State::AfterDoctypePublicIdentifier
+        } else {
+            State::AfterDoctypeSystemIdentifier
+        };
+        let c = self.next_input();
+        let identifier = if public {
+            &mut self.current_doctype.public_id
+        } else {
+            &mut self.current_doctype.system_id
+        };
+        match c {
+            Some(c) if c == quote => self.state = after_state,
+            Some('\0') => {
+                push_optional(identifier, '\u{FFFD}');
+                self.error("unexpected-null-character");
+            }
+            Some('>') => {
+                self.error(if public {
+                    "abrupt-doctype-public-identifier"
+                } else {
+                    "abrupt-doctype-system-identifier"
+                });
+                self.current_doctype.force_quirks = true;
+                self.state = State::Data;
+                self.emit_current_doctype();
+            }
+            Some(c) => push_optional(identifier, c),
+            None => self.eof_in_doctype(),
+        }
+    }
+
+    fn named_character_reference(&mut self) {
+        let (found, next_char) = {
+            let rest = self.parser.remaining();
+            let word_len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric())
+                .unwrap_or(rest.len());
+            let candidate_len = if rest[word_len..].starts_with(';') {
+                word_len + 1
+            } else {
+                word_len
+            };
+            let found = entities::longest_match(&rest[..candidate_len]);
+            let next_char = found.and_then(|(name, _)| rest[name.len()..].chars().next());
+            (found, next_char)
+        };
+
+        match found {
+            Some((name, value)) => {
+                self.skip_input(name.len());
+                self.temporary_buffer.push_str(name);
+                // Legacy references without a semicolon are left alone inside
+                // attribute values when they look like part of a query string.
+                let continues_word =
+                    next_char.is_some_and(|c| c == '=' || c.is_ascii_alphanumeric());
+                if !name.ends_with(';') && self.consuming_in_attribute() && continues_word {
+                    self.flush_temporary_buffer_as_reference();
+                } else {
+                    if !name.ends_with(';') {
+                        self.error("missing-semicolon-after-character-reference");
+                    }
+                    self.temporary_buffer.clear();
+                    self.flush_decoded_reference(value);
+                }
+                self.state = self.return_state;
+            }
+            None => {
+                self.flush_temporary_buffer_as_reference();
+                self.state = State::AmbiguousAmpersand;
+            }
+        }
+    }
+
+    fn absence_of_digits(&mut self) {
+        self.error("absence-of-digits-in-numeric-character-reference");
+        self.flush_temporary_buffer_as_reference();
+        let return_state = self.return_state;
+        self.reconsume_in(return_state);
+    }
+
+    fn add_to_character_reference_code(&mut self, digit: u32, radix: u32) {
+        // Saturate rather than overflow; anything this large is out of range.
+        self.character_reference_code = self
+            .character_reference_code
+            .saturating_mul(radix)
+            .saturating_add(digit);
+    }
+
+    fn numeric_character_reference_end(&mut self) {
+        let code = self.character_reference_code;
+        let character = match code {
+            0 => {
+                self.error("null-character-reference");
+                '\u{FFFD}'
+            }
+            0xD800..=0xDFFF => {
+                self.error("surrogate-character-reference");
+                '\u{FFFD}'
+            }
+            _ if code > 0x10FFFF => {
+                self.error("character-reference-outside-unicode-range");
+                '\u{FFFD}'
+            }
+            _ => {
+                if is_noncharacter(code) {
+                    self.error("noncharacter-character-reference");
+                } else if code == 0x0D || (is_control(code) && !is_whitespace_code(code)) {
+                    self.error("control-character-reference");
+                }
+                let remapped = entities::windows_1252_replacement(code).unwrap_or(code);
+                std::char::from_u32(remapped).unwrap_or('\u{FFFD}')
+            }
+        };
+        self.temporary_buffer.clear();
+        self.flush_decoded_reference(character.encode_utf8(&mut [0; 4]));
+        self.state = self.return_state;
+    }
+}
+
+fn push_optional(value: &mut Option<String>, c: char) {
+    match *value {
+        Some(ref mut text) => text.push(c),
+        None => *value = Some(c.to_string()),
+    }
+}
+
+pub fn is_whitespace(c: char) -> bool {
+    matches!(c, '\t' | '\n' | '\x0C' | '\r' | ' ')
+}
+
+fn is_noncharacter(code: u32) -> bool {
+    (0xFDD0..=0xFDEF).contains(&code) || (code & 0xFFFE) == 0xFFFE
+}
+
+fn is_control(code: u32) -> bool {
+    code <= 0x1F || (0x7F..=0x9F).contains(&code)
+}
+
+fn is_whitespace_code(code: u32) -> bool {
+    matches!(code, 0x09 | 0x0A | 0x0C | 0x0D | 0x20)
+}
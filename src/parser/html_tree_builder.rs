@@ -0,0 +1,3063 @@
+use dom::*;
+use parser::diagnostic::{self, Diagnostic};
+use parser::html_tokenizer::{
+    is_whitespace, Attribute, Doctype, ProcessingInstruction, State, Tag, Token, Tokenizer,
+};
+use parser::Span;
+use std::mem;
+
+pub type NodeId = usize;
+
+struct TreeNode {
+    data: NodeType,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    span: Span,
+    // Elements remember the tag they were created from so the active
+    // formatting elements can be recreated.
+    tag: Option<Tag>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum InsertionMode {
+    Initial,
+    BeforeHtml,
+    BeforeHead,
+    InHead,
+    InHeadNoscript,
+    AfterHead,
+    InBody,
+    Text,
+    InTable,
+    InTableText,
+    InCaption,
+    InColumnGroup,
+    InTableBody,
+    InRow,
+    InCell,
+    InSelect,
+    InSelectInTable,
+    InTemplate,
+    AfterBody,
+    InFrameset,
+    AfterFrameset,
+    AfterAfterBody,
+    AfterAfterFrameset,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scope {
+    Default,
+    ListItem,
+    Button,
+    Table,
+    Select,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum FormattingEntry {
+    Marker,
+    Element(NodeId),
+}
+
+// Character tokens are processed one at a time since most insertion modes
+// treat whitespace differently from other characters.
+#[derive(Debug, Clone, PartialEq)]
+enum TreeToken {
+    Doctype(Doctype),
+    StartTag(Tag),
+    EndTag(Tag),
+    Comment(Comment),
+    Character(char),
+    Eof,
+}
+
+// Processing instructions are inserted wherever a comment would be.
+#[derive(Debug, Clone, PartialEq)]
+enum Comment {
+    Text(String),
+    ProcessingInstruction(ProcessingInstruction),
+}
+
+impl Comment {
+    fn into_node_type(self) -> NodeType {
+        match self {
+            Comment::Text(data) => comment(data).node_type,
+            Comment::ProcessingInstruction(instruction) => {
+                processing_instruction(instruction.target, instruction.data).node_type
+            }
+        }
+    }
+}
+
+/// Builds a DOM from tokens following the WHATWG tree construction stage.
+pub struct TreeBuilder {
+    tokenizer: Tokenizer,
+    warnings: Vec<Diagnostic>,
+    nodes: Vec<TreeNode>,
+    mode: InsertionMode,
+    original_mode: InsertionMode,
+    template_modes: Vec<InsertionMode>,
+    open_elements: Vec<NodeId>,
+    active_formatting: Vec<FormattingEntry>,
+    head_element: Option<NodeId>,
+    form_element: Option<NodeId>,
+    frameset_ok: bool,
+    scripting: bool,
+    foster_parenting: bool,
+    pending_table_characters: String,
+    pending_table_span: Span,
+    // The span of the token being processed, or of the current character
+    // when a character token is processed one character at a time.
+    span: Span,
+    quirks_mode: QuirksMode,
+    ignore_line_feed: bool,
+    self_closing_acknowledged: bool,
+    stopped: bool,
+    // Elements popped off the stack of open elements since the last call to
+    // take_completed.
+    completed: Vec<NodeId>,
+    // The element a fragment is parsed into. It is not part of the tree.
+    context: Option<NodeId>,
+}
+
+const DOCUMENT: NodeId = 0;
+
+/// A node in a tree which is still being built, looked at without copying
+/// it out.
+#[derive(Clone, Copy)]
+pub struct NodeRef<'a> {
+    builder: &'a TreeBuilder,
+    id: NodeId,
+}
+
+impl<'a> NodeRef<'a> {
+    pub fn node_type(&self) -> &'a NodeType {
+        &self.builder.nodes[self.id].data
+    }
+
+    pub fn span(&self) -> Span {
+        self.builder.nodes[self.id].span
+    }
+
+    pub fn children(&self) -> Vec<NodeRef<'a>> {
+        let builder = self.builder;
+        builder.nodes[self.id]
+            .children
+            .iter()
+            .map(|&id| NodeRef { builder, id })
+            .collect()
+    }
+}
+
+pub fn build(tokenizer: Tokenizer, scripting: bool, warnings: &mut Vec<Diagnostic>) -> Node {
+    let mut builder = create(tokenizer, scripting);
+    builder.run();
+    builder.into_document(warnings)
+}
+
+pub fn create(tokenizer: Tokenizer, scripting: bool) -> TreeBuilder {
+    TreeBuilder {
+        tokenizer,
+        warnings: vec![],
+        nodes: vec![TreeNode {
+            data: document(QuirksMode::NoQuirks, vec![]).node_type,
+            parent: None,
+            children: vec![],
+            span: Span::default(),
+            tag: None,
+        }],
+        mode: InsertionMode::Initial,
+        original_mode: InsertionMode::Initial,
+        template_modes: vec![],
+        open_elements: vec![],
+        active_formatting: vec![],
+        head_element: None,
+        form_element: None,
+        frameset_ok: true,
+        scripting,
+        foster_parenting: false,
+        pending_table_characters: String::new(),
+        pending_table_span: Span::default(),
+        span: Span::default(),
+        quirks_mode: QuirksMode::NoQuirks,
+        ignore_line_feed: false,
+        self_closing_acknowledged: false,
+        stopped: false,
+        completed: vec![],
+        context: None,
+    }
+}
+
+/// Creates a builder for the children of a context element, following the
+/// fragment parsing algorithm. The fragment is built under an html element,
+/// which `into_fragment` leaves out.
+pub fn create_fragment(
+    mut tokenizer: Tokenizer,
+    scripting: bool,
+    namespace: Namespace,
+    context_name: &str,
+) -> TreeBuilder {
+    if namespace == Namespace::Html {
+        let state = match context_name {
+            "title" | "textarea" => State::Rcdata,
+            "style" | "xmp" | "iframe" | "noembed" | "noframes" => State::Rawtext,
+            "noscript" if scripting => State::Rawtext,
+            "script" => State::ScriptData,
+            "plaintext" => State::Plaintext,
+            _ => State::Data,
+        };
+        tokenizer.set_state(state);
+    }
+    tokenizer.set_last_start_tag_name(context_name);
+    let mut builder = create(tokenizer, scripting);
+    let context = builder.create_element(builder.synthetic_tag(context_name), namespace);
+    let root = builder.create_element(builder.synthetic_tag("html"), Namespace::Html);
+    builder.append(DOCUMENT, root);
+    builder.open_elements.push(root);
+    builder.context = Some(context);
+    if builder.is_element(context, "template") {
+        builder.template_modes.push(InsertionMode::InTemplate);
+    }
+    builder.reset_insertion_mode();
+    builder
+}
+
+impl TreeBuilder {
+    pub fn feed(&mut self, text: &str) {
+        self.tokenizer.feed(text);
+        self.run();
+    }
+
+    pub fn finish(&mut self) {
+        self.tokenizer.finish();
+        self.run();
+    }
+
+    /// Returns the elements closed since the last call, in the order they
+    /// were closed. `node` looks them up.
+    pub fn take_completed(&mut self) -> Vec<NodeId> {
+        mem::take(&mut self.completed)
+    }
+
+    /// A node of the tree built so far.
+    pub fn node(&self, id: NodeId) -> NodeRef<'_> {
+        NodeRef { builder: self, id }
+    }
+
+    // Processes tokens until the input runs out or the end of file token has
+    // been handled.
+    fn run(&mut self) {
+        while !self.stopped {
+            // CDATA sections are only allowed in foreign content.
+            let foreign = !self.open_elements.is_empty()
+                && !self.is_html_element(self.adjusted_current_node());
+            self.tokenizer.set_allow_cdata(foreign);
+            let (token, span) = match self.tokenizer.next_token() {
+                Some(token) => token,
+                None => break,
+            };
+            for error in self.tokenizer.take_errors() {
+                self.warnings.push(error);
+            }
+            self.span = span;
+            let first_completed = self.completed.len();
+            let end_tag_name = match token {
+                Token::EndTag(ref tag) => Some(tag.name.clone()),
+                _ => None,
+            };
+            match token {
+                Token::Characters(text) | Token::CData(text) => {
+                    // Text which matches the source byte for byte gets a
+                    // span per character; anything else was decoded from a
+                    // single reference or newline and shares its span.
+                    let verbatim = span.end.offset() - span.start.offset() == text.len();
+                    let mut position = span.start;
+                    for c in text.chars() {
+                        if verbatim {
+                            self.span = Span {
+                                start: position,
+                                end: position.advance(c),
+                            };
+                            position = self.span.end;
+                        }
+                        self.process_token(TreeToken::Character(c));
+                    }
+                }
+                Token::Doctype(doctype) => self.process_token(TreeToken::Doctype(doctype)),
+                Token::StartTag(tag) => {
+                    let self_closing = tag.self_closing;
+                    self.self_closing_acknowledged = false;
+                    self.process_token(TreeToken::StartTag(tag));
+                    if self_closing && !self.self_closing_acknowledged {
+                        self.error(
+                            "non-void-html-element-start-tag-with-trailing-solidus",
+                            "Non-void element start tag with trailing solidus",
+                        );
+                    }
+                }
+                Token::EndTag(tag) => self.process_token(TreeToken::EndTag(tag)),
+                Token::Comment(text) => self.process_token(TreeToken::Comment(Comment::Text(text))),
+                Token::ProcessingInstruction(instruction) => self.process_token(
+                    TreeToken::Comment(Comment::ProcessingInstruction(instruction)),
+                ),
+                Token::Eof => {
+                    self.process_token(TreeToken::Eof);
+                    self.stopped = true;
+                }
+            }
+            for index in first_completed..self.completed.len() {
+                let node = self.completed[index];
+                // An element closed by its own end tag ends with it.
+                if let Some(ref name) = end_tag_name {
+                    if self.local_name_is(node, name) {
+                        self.nodes[node].span.end = span.end;
+                    }
+                }
+                // Anything else ends with its contents, which were closed
+                // before it.
+                let end = self.nodes[node]
+                    .children
+                    .iter()
+                    .map(|&child| self.nodes[child].span.end)
+                    .max_by_key(|end| end.offset());
+                if let Some(end) = end {
+                    if end.offset() > self.nodes[node].span.end.offset() {
+                        self.nodes[node].span.end = end;
+                    }
+                }
+            }
+        }
+    }
+
+    // Tree construction errors point at the current token.
+    fn error(&mut self, code: &'static str, message: &str) {
+        let span = self.span;
+        self.warnings.push(diagnostic::error(code, span, message));
+    }
+
+    fn process_token(&mut self, token: TreeToken) {
+        if self.ignore_line_feed {
+            self.ignore_line_feed = false;
+            if token == TreeToken::Character('\n') {
+                return;
+            }
+        }
+        if self.is_foreign_content(&token) {
+            self.in_foreign_content(token);
+        } else {
+            let mode = self.mode;
+            self.process_in_mode(mode, token);
+        }
+    }
+
+    // Tokens go to the rules for foreign content while the current node is a
+    // foreign element, unless it is an integration point for them.
+    fn is_foreign_content(&self, token: &TreeToken) -> bool {
+        if self.open_elements.is_empty() {
+            return false;
+        }
+        let node = self.adjusted_current_node();
+        if self.is_html_element(node) {
+            return false;
+        }
+        let text_integration_point = self.is_mathml_text_integration_point(node);
+        let html_integration_point = self.is_html_integration_point(node);
+        match *token {
+            TreeToken::StartTag(ref tag) if text_integration_point => {
+                tag.name == "mglyph" || tag.name == "malignmark"
+            }
+            TreeToken::StartTag(ref tag) if tag.name == "svg" => {
+                !html_integration_point
+                    && !self.is_foreign_element(node, Namespace::MathMl, &["annotation-xml"])
+            }
+            TreeToken::StartTag(_) | TreeToken::Character(_) => {
+                !text_integration_point && !html_integration_point
+            }
+            TreeToken::Eof => false,
+            _ => true,
+        }
+    }
+
+    fn process_in_mode(&mut self, mode: InsertionMode, token: TreeToken) {
+        match mode {
+            InsertionMode::Initial => self.initial(token),
+            InsertionMode::BeforeHtml => self.before_html(token),
+            InsertionMode::BeforeHead => self.before_head(token),
+            InsertionMode::InHead => self.in_head(token),
+            InsertionMode::InHeadNoscript => self.in_head_noscript(token),
+            InsertionMode::AfterHead => self.after_head(token),
+            InsertionMode::InBody => self.in_body(token),
+            InsertionMode::Text => self.text(token),
+            InsertionMode::InTable => self.in_table(token),
+            InsertionMode::InTableText => self.in_table_text(token),
+            InsertionMode::InCaption => self.in_caption(token),
+            InsertionMode::InColumnGroup => self.in_column_group(token),
+            InsertionMode::InTableBody => self.in_table_body(token),
+            InsertionMode::InRow => self.in_row(token),
+            InsertionMode::InCell => self.in_cell(token),
+            InsertionMode::InSelect => self.in_select(token),
+            InsertionMode::InSelectInTable => self.in_select_in_table(token),
+            InsertionMode::InTemplate => self.in_template(token),
+            InsertionMode::AfterBody => self.after_body(token),
+            InsertionMode::InFrameset => self.in_frameset(token),
+            InsertionMode::AfterFrameset => self.after_frameset(token),
+            InsertionMode::AfterAfterBody => self.after_after_body(token),
+            InsertionMode::AfterAfterFrameset => self.after_after_frameset(token),
+        }
+    }
+
+    fn reprocess(&mut self, mode: InsertionMode, token: TreeToken) {
+        self.mode = mode;
+        self.process_in_mode(mode, token);
+    }
+
+    fn initial(&mut self, token: TreeToken) {
+        match token {
+            TreeToken::Character(c) if is_whitespace(c) => {}
+            TreeToken::Comment(comment) => self.append_comment_to(DOCUMENT, comment),
+            TreeToken::Doctype(token) => {
+                let is_html = token.name.as_ref().is_some_and(|name| name == "html");
+                let legacy_compat = token
+                    .system_id
+                    .as_ref()
+                    .is_none_or(|system_id| system_id == "about:legacy-compat");
+                if !is_html || token.public_id.is_some() || !legacy_compat {
+                    self.error("unexpected-doctype", "Unexpected doctype");
+                }
+                self.quirks_mode = quirks_mode_for(&token);
+                let node = self.new_node(
+                    doctype(
+                        token.name.unwrap_or_default(),
+                        token.public_id.unwrap_or_default(),
+                        token.system_id.unwrap_or_default(),
+                        token.force_quirks,
+                    )
+                    .node_type,
+                );
+                self.append(DOCUMENT, node);
+                self.mode = InsertionMode::BeforeHtml;
+            }
+            token => {
+                self.error("missing-doctype", "Expected doctype");
+                self.quirks_mode = QuirksMode::Quirks;
+                self.reprocess(InsertionMode::BeforeHtml, token);
+            }
+        }
+    }
+
+    fn before_html(&mut self, token: TreeToken) {
+        match token {
+            TreeToken::Doctype(_) => self.error("unexpected-doctype", "Unexpected doctype"),
+            TreeToken::Comment(comment) => self.append_comment_to(DOCUMENT, comment),
+            TreeToken::Character(c) if is_whitespace(c) => {}
+            TreeToken::StartTag(tag) if tag.name == "html" => {
+                let node = self.create_element(tag, Namespace::Html);
+                self.append(DOCUMENT, node);
+                self.open_elements.push(node);
+                self.mode = InsertionMode::BeforeHead;
+            }
+            TreeToken::EndTag(ref tag)
+                if !is_one_of(&tag.name, &["head", "body", "html", "br"]) =>
+            {
+                self.error("unexpected-end-tag", "Unexpected end tag");
+            }
+            token => {
+                let node = self.create_element(self.synthetic_tag("html"), Namespace::Html);
+                self.append(DOCUMENT, node);
+                self.open_elements.push(node);
+                self.reprocess(InsertionMode::BeforeHead, token);
+            }
+        }
+    }
+
+    fn before_head(&mut self, token: TreeToken) {
+        match token {
+            TreeToken::Character(c) if is_whitespace(c) => {}
+            TreeToken::Comment(comment) => self.insert_comment(comment),
+            TreeToken::Doctype(_) => self.error("unexpected-doctype", "Unexpected doctype"),
+            TreeToken::StartTag(ref tag) if tag.name == "html" => self.in_body(token),
+            TreeToken::StartTag(tag) if tag.name == "head" => {
+                let head = self.insert_html_element(tag);
+                self.head_element = Some(head);
+                self.mode = InsertionMode::InHead;
+            }
+            TreeToken::EndTag(ref tag)
+                if !is_one_of(&tag.name, &["head", "body", "html", "br"]) =>
+            {
+                self.error("unexpected-end-tag", "Unexpected end tag");
+            }
+            token => {
+                let head = self.insert_html_element(self.synthetic_tag("head"));
+                self.head_element = Some(head);
+                self.reprocess(InsertionMode::InHead, token);
+            }
+        }
+    }
+
+    fn in_head(&mut self, token: TreeToken) {
+        match token {
+            TreeToken::Character(c) if is_whitespace(c) => self.insert_character(c),
+            TreeToken::Comment(comment) => self.insert_comment(comment),
+            TreeToken::Doctype(_) => self.error("unexpected-doctype", "Unexpected doctype"),
+            TreeToken::StartTag(ref tag) if tag.name == "html" => self.in_body(token),
+            TreeToken::StartTag(tag)
+                if is_one_of(&tag.name, &["base", "basefont", "bgsound", "link", "meta"]) =>
+            {
+                self.insert_html_element(tag);
+                self.pop();
+                self.self_closing_acknowledged = true;
+            }
+            TreeToken::StartTag(tag) if tag.name == "title" => {
+                self.parse_generic_text(tag, State::Rcdata);
+            }
+            TreeToken::StartTag(tag)
+                if (tag.name == "noscript" && self.scripting)
+                    || is_one_of(&tag.name, &["noframes", "style"]) =>
+            {
+                self.parse_generic_text(tag, State::Rawtext);
+            }
+            TreeToken::StartTag(tag) if tag.name == "noscript" => {
+                self.insert_html_element(tag);
+                self.mode = InsertionMode::InHeadNoscript;
+            }
+            TreeToken::StartTag(tag) if tag.name == "script" => {
+                self.parse_generic_text(tag, State::ScriptData);
+            }
+            TreeToken::EndTag(ref tag) if tag.name == "head" => {
+                self.pop();
+                self.mode = InsertionMode::AfterHead;
+            }
+            TreeToken::StartTag(tag) if tag.name == "template" => {
+                self.insert_html_element(tag);
+                self.active_formatting.push(FormattingEntry::Marker);
+                self.frameset_ok = false;
+                self.mode = InsertionMode::InTemplate;
+                self.template_modes.push(InsertionMode::InTemplate);
+            }
+            TreeToken::EndTag(ref tag) if tag.name == "template" => {
+                if !self.has_open_element("template") {
+                    self.error("unexpected-end-tag", "Unexpected end tag");
+                    return;
+                }
+                self.generate_all_implied_end_tags();
+                if !self.current_node_is("template") {
+                    self.error("unexpected-end-tag", "Unexpected end tag");
+                }
+                self.pop_until("template");
+                self.clear_active_formatting_to_last_marker();
+                self.template_modes.pop();
+                self.reset_insertion_mode();
+            }
+            TreeToken::StartTag(ref tag) if tag.name == "head" => {
+                self.error("unexpected-start-tag", "Unexpected start tag");
+            }
+            TreeToken::EndTag(ref tag) if !is_one_of(&tag.name, &["body", "html", "br"]) => {
+                self.error("unexpected-end-tag", "Unexpected end tag");
+            }
+            token => {
+                self.pop();
+                self.reprocess(InsertionMode::AfterHead, token);
+            }
+        }
+    }
+
+    fn in_head_noscript(&mut self, token: TreeToken) {
+        match token {
+            TreeToken::Doctype(_) => self.error("unexpected-doctype", "Unexpected doctype"),
+            TreeToken::StartTag(ref tag) if tag.name == "html" => self.in_body(token),
+            TreeToken::EndTag(ref tag) if tag.name == "noscript" => {
+                self.pop();
+                self.mode = InsertionMode::InHead;
+            }
+            TreeToken::Character(c) if is_whitespace(c) => self.in_head(token),
+            TreeToken::Comment(_) => self.in_head(token),
+            TreeToken::StartTag(ref tag)
+                if is_one_of(
+                    &tag.name,
+                    &["basefont", "bgsound", "link", "meta", "noframes", "style"],
+                ) =>
+            {
+                self.in_head(token)
+            }
+            TreeToken::StartTag(ref tag) if is_one_of(&tag.name, &["head", "noscript"]) => {
+                self.error("unexpected-start-tag", "Unexpected start tag");
+            }
+            TreeToken::EndTag(ref tag) if tag.name != "br" => {
+                self.error("unexpected-end-tag", "Unexpected end tag");
+            }
+            token => {
+                self.error("unexpected-token", "Unexpected token in noscript");
+                self.pop();
+                self.reprocess(InsertionMode::InHead, token);
+            }
+        }
+    }
+
+    fn after_head(&mut self, token: TreeToken) {
+        match token {
+            TreeToken::Character(c) if is_whitespace(c) => self.insert_character(c),
+            TreeToken::Comment(comment) => self.insert_comment(comment),
+            TreeToken::Doctype(_) => self.error("unexpected-doctype", "Unexpected doctype"),
+            TreeToken::StartTag(ref tag) if tag.name == "html" => self.in_body(token),
+            TreeToken::StartTag(tag) if tag.name == "body" => {
+                self.insert_html_element(tag);
+                self.frameset_ok = false;
+                self.mode = InsertionMode::InBody;
+            }
+            TreeToken::StartTag(tag) if tag.name == "frameset" => {
+                self.insert_html_element(tag);
+                self.mode = InsertionMode::InFrameset;
+            }
+            TreeToken::StartTag(ref tag)
+                if is_one_of(
+                    &tag.name,
+                    &[
+                        "base", "basefont", "bgsound", "link", "meta", "noframes", "script",
+                        "style", "template", "title",
+                    ],
+                ) =>
+            {
+                self.error("unexpected-start-tag", "Unexpected start tag after head");
+                let head = self.head_element.expect("head element exists after head");
+                self.open_elements.push(head);
+                self.in_head(token);
+                self.open_elements.retain(|&node| node != head);
+            }
+            TreeToken::EndTag(ref tag) if tag.name == "template" => self.in_head(token),
+            TreeToken::StartTag(ref tag) if tag.name == "head" => {
+                self.error("unexpected-start-tag", "Unexpected start tag");
+            }
+            TreeToken::EndTag(ref tag) if !is_one_of(&tag.name, &["body", "html", "br"]) => {
+                self.error("unexpected-end-tag", "Unexpected end tag");
+            }
+            token => {
+                self.insert_html_element(self.synthetic_tag("body"));
+                self.reprocess(InsertionMode::InBody, token);
+            }
+        }
+    }
+
+    fn in_body(&mut self, token: TreeToken) {
+        match token {
+            TreeToken::Character('\0') => {
+                self.error("unexpected-null-character", "Unexpected null character")
+            }
+            TreeToken::Character(c) => {
+                self.reconstruct_active_formatting();
+                self.insert_character(c);
+                if !is_whitespace(c) {
+                    self.frameset_ok = false;
+                }
+            }
+            TreeToken::Comment(comment) => self.insert_comment(comment),
+            TreeToken::Doctype(_) => self.error("unexpected-doctype", "Unexpected doctype"),
+            TreeToken::StartTag(tag) if tag.name == "html" => {
+                self.error("unexpected-start-tag", "Unexpected html start tag");
+                if !self.has_open_element("template") {
+                    let html = self.open_elements[0];
+                    self.add_missing_attributes(html, tag.attributes);
+                }
+            }
+            TreeToken::StartTag(ref tag)
+                if is_one_of(
+                    &tag.name,
+                    &[
+                        "base", "basefont", "bgsound", "link", "meta", "noframes", "script",
+                        "style", "template", "title",
+                    ],
+                ) =>
+            {
+                self.in_head(token)
+            }
+            TreeToken::EndTag(ref tag) if tag.name == "template" => self.in_head(token),
+            TreeToken::StartTag(tag) if tag.name == "body" => {
+                self.error("unexpected-start-tag", "Unexpected body start tag");
+                if self.open_elements.len() == 1
+                    || !self.is_element(self.open_elements[1], "body")
+                    || self.has_open_element("template")
+                {
+                    return;
+                }
+                self.frameset_ok = false;
+                let body = self.open_elements[1];
+                self.add_missing_attributes(body, tag.attributes);
+            }
+            TreeToken::StartTag(tag) if tag.name == "frameset" => {
+                self.error("unexpected-start-tag", "Unexpected frameset start tag");
+                if self.open_elements.len() == 1
+                    || !self.is_element(self.open_elements[1], "body")
+                    || !self.frameset_ok
+                {
+                    return;
+                }
+                let body = self.open_elements[1];
+                self.detach(body);
+                self.pop_to(1);
+                self.insert_html_element(tag);
+                self.mode = InsertionMode::InFrameset;
+            }
+            TreeToken::Eof => {
+                if !self.template_modes.is_empty() {
+                    self.in_template(TreeToken::Eof);
+                    return;
+                }
+                self.check_unclosed_elements_at_end();
+                self.stopped = true;
+            }
+            TreeToken::EndTag(ref tag) if tag.name == "body" => {
+                if !self.in_scope(&["body"], Scope::Default) {
+                    self.error("unexpected-end-tag", "Unexpected body end tag");
+                    return;
+                }
+                self.check_unclosed_elements_at_end();
+                self.mode = InsertionMode::AfterBody;
+            }
+            TreeToken::EndTag(ref tag) if tag.name == "html" => {
+                if !self.in_scope(&["body"], Scope::Default) {
+                    self.error("unexpected-end-tag", "Unexpected html end tag");
+                    return;
+                }
+                self.check_unclosed_elements_at_end();
+                self.reprocess(InsertionMode::AfterBody, token);
+            }
+            TreeToken::StartTag(tag)
+                if is_one_of(
+                    &tag.name,
+                    &[
+                        "address",
+                        "article",
+                        "aside",
+                        "blockquote",
+                        "center",
+                        "details",
+                        "dialog",
+                        "dir",
+                        "div",
+                        "dl",
+                        "fieldset",
+                        "figcaption",
+                        "figure",
+                        "footer",
+                        "header",
+                        "hgroup",
+                        "main",
+                        "menu",
+                        "nav",
+                        "ol",
+                        "p",
+                        "search",
+                        "section",
+                        "summary",
+                        "ul",
+                    ],
+                ) =>
+            {
+                self.close_p_element_in_button_scope();
+                self.insert_html_element(tag);
+            }
+            TreeToken::StartTag(tag) if is_heading(&tag.name) => {
+                self.close_p_element_in_button_scope();
+                if self.is_element_one_of(self.current_node(), HEADINGS) {
+                    self.error("unexpected-start-tag", "Unexpected nested heading");
+                    self.pop();
+                }
+                self.insert_html_element(tag);
+            }
+            TreeToken::StartTag(tag) if is_one_of(&tag.name, &["pre", "listing"]) => {
+                self.close_p_element_in_button_scope();
+                self.insert_html_element(tag);
+                self.ignore_line_feed = true;
+                self.frameset_ok = false;
+            }
+            TreeToken::StartTag(tag) if tag.name == "form" => {
+                let has_template = self.has_open_element("template");
+                if self.form_element.is_some() && !has_template {
+                    self.error("unexpected-start-tag", "Unexpected nested form");
+                    return;
+                }
+                self.close_p_element_in_button_scope();
+                let form = self.insert_html_element(tag);
+                if !has_template {
+                    self.form_element = Some(form);
+                }
+            }
+            TreeToken::StartTag(tag) if tag.name == "li" => {
+                self.frameset_ok = false;
+                self.close_list_item(&["li"]);
+                self.close_p_element_in_button_scope();
+                self.insert_html_element(tag);
+            }
+            TreeToken::StartTag(tag) if is_one_of(&tag.name, &["dd", "dt"]) => {
+                self.frameset_ok = false;
+                self.close_list_item(&["dd", "dt"]);
+                self.close_p_element_in_button_scope();
+                self.insert_html_element(tag);
+            }
+            TreeToken::StartTag(tag) if tag.name == "plaintext" => {
+                self.close_p_element_in_button_scope();
+                self.insert_html_element(tag);
+                self.tokenizer.set_state(State::Plaintext);
+            }
+            TreeToken::StartTag(tag) if tag.name == "button" => {
+                if self.in_scope(&["button"], Scope::Default) {
+                    self.error("unexpected-start-tag", "Unexpected nested button");
+                    self.generate_implied_end_tags(None);
+                    self.pop_until("button");
+                }
+                self.reconstruct_active_formatting();
+                self.insert_html_element(tag);
+                self.frameset_ok = false;
+            }
+            TreeToken::EndTag(tag)
+                if is_one_of(
+                    &tag.name,
+                    &[
+                        "address",
+                        "article",
+                        "aside",
+                        "blockquote",
+                        "button",
+                        "center",
+                        "details",
+                        "dialog",
+                        "dir",
+                        "div",
+                        "dl",
+                        "fieldset",
+                        "figcaption",
+                        "figure",
+                        "footer",
+                        "header",
+                        "hgroup",
+                        "listing",
+                        "main",
+                        "menu",
+                        "nav",
+                        "ol",
+                        "pre",
+                        "search",
+                        "section",
+                        "summary",
+                        "ul",
+                    ],
+                ) =>
+            {
+                if !self.in_scope(&[tag.name.as_str()], Scope::Default) {
+                    self.error("unexpected-end-tag", "Unexpected end tag");
+                    return;
+                }
+                self.generate_implied_end_tags(None);
+                if !self.current_node_is(&tag.name) {
+                    self.error("unexpected-end-tag", "Unexpected end tag");
+                }
+                self.pop_until(&tag.name);
+            }
+            TreeToken::EndTag(ref tag) if tag.name == "form" => {
+                if !self.has_open_element("template") {
+                    let form = self.form_element.take();
+                    let form = match form {
+                        Some(form) if self.node_in_scope(form) => form,
+                        _ => {
+                            self.error("unexpected-end-tag", "Unexpected form end tag");
+                            return;
+                        }
+                    };
+                    self.generate_implied_end_tags(None);
+                    if self.current_node() != form {
+                        self.error("unexpected-end-tag", "Unexpected form end tag");
+                    }
+                    self.remove_open_element(form);
+                } else {
+                    if !self.in_scope(&["form"], Scope::Default) {
+                        self.error("unexpected-end-tag", "Unexpected form end tag");
+                        return;
+                    }
+                    self.generate_implied_end_tags(None);
+                    if !self.current_node_is("form") {
+                        self.error("unexpected-end-tag", "Unexpected form end tag");
+                    }
+                    self.pop_until("form");
+                }
+            }
+            TreeToken::EndTag(ref tag) if tag.name == "p" => {
+                if !self.in_scope(&["p"], Scope::Button) {
+                    self.error("unexpected-end-tag", "Unexpected p end tag");
+                    self.insert_html_element(self.synthetic_tag("p"));
+                }
+                self.close_p_element();
+            }
+            TreeToken::EndTag(ref tag) if tag.name == "li" => {
+                if !self.in_scope(&["li"], Scope::ListItem) {
+                    self.error("unexpected-end-tag", "Unexpected li end tag");
+                    return;
+                }
+                self.generate_implied_end_tags(Some("li"));
+                if !self.current_node_is("li") {
+                    self.error("unexpected-end-tag", "Unexpected li end tag");
+                }
+                self.pop_until("li");
+            }
+            TreeToken::EndTag(tag) if is_one_of(&tag.name, &["dd", "dt"]) => {
+                if !self.in_scope(&[tag.name.as_str()], Scope::Default) {
+                    self.error("unexpected-end-tag", "Unexpected end tag");
+                    return;
+                }
+                self.generate_implied_end_tags(Some(&tag.name));
+                if !self.current_node_is(&tag.name) {
+                    self.error("unexpected-end-tag", "Unexpected end tag");
+                }
+                self.pop_until(&tag.name);
+            }
+            TreeToken::EndTag(tag) if is_heading(&tag.name) => {
+                if !self.in_scope(HEADINGS, Scope::Default) {
+                    self.error("unexpected-end-tag", "Unexpected heading end tag");
+                    return;
+                }
+                self.generate_implied_end_tags(None);
+                if !self.current_node_is(&tag.name) {
+                    self.error("unexpected-end-tag", "Unexpected heading end tag");
+                }
+                self.pop_until_one_of(HEADINGS);
+            }
+            TreeToken::StartTag(tag) if tag.name == "a" => {
+                let open_a = self.active_formatting_element_after_last_marker("a");
+                if let Some(open_a) = open_a {
+                    self.error("unexpected-start-tag", "Unexpected nested a");
+                    self.adoption_agency("a");
+                    self.remove_from_active_formatting(open_a);
+                    self.remove_open_element(open_a);
+                }
+                self.reconstruct_active_formatting();
+                let node = self.insert_html_element(tag);
+                self.push_active_formatting(node);
+            }
+            TreeToken::StartTag(tag) if is_one_of(&tag.name, FORMATTING_ELEMENTS) => {
+                self.reconstruct_active_formatting();
+                let node = self.insert_html_element(tag);
+                self.push_active_formatting(node);
+            }
+            TreeToken::StartTag(tag) if tag.name == "nobr" => {
+                self.reconstruct_active_formatting();
+                if self.in_scope(&["nobr"], Scope::Default) {
+                    self.error("unexpected-start-tag", "Unexpected nested nobr");
+                    self.adoption_agency("nobr");
+                    self.reconstruct_active_formatting();
+                }
+                let node = self.insert_html_element(tag);
+                self.push_active_formatting(node);
+            }
+            TreeToken::EndTag(tag)
+                if tag.name == "a"
+                    || tag.name == "nobr"
+                    || is_one_of(&tag.name, FORMATTING_ELEMENTS) =>
+            {
+                if !self.adoption_agency(&tag.name) {
+                    self.any_other_end_tag(&tag.name);
+                }
+            }
+            TreeToken::StartTag(tag) if is_one_of(&tag.name, &["applet", "marquee", "object"]) => {
+                self.reconstruct_active_formatting();
+                self.insert_html_element(tag);
+                self.active_formatting.push(FormattingEntry::Marker);
+                self.frameset_ok = false;
+            }
+            TreeToken::EndTag(tag) if is_one_of(&tag.name, &["applet", "marquee", "object"]) => {
+                if !self.in_scope(&[tag.name.as_str()], Scope::Default) {
+                    self.error("unexpected-end-tag", "Unexpected end tag");
+                    return;
+                }
+                self.generate_implied_end_tags(None);
+                if !self.current_node_is(&tag.name) {
+                    self.error("unexpected-end-tag", "Unexpected end tag");
+                }
+                self.pop_until(&tag.name);
+                self.clear_active_formatting_to_last_marker();
+            }
+            TreeToken::StartTag(tag) if tag.name == "table" => {
+                if self.quirks_mode != QuirksMode::Quirks {
+                    self.close_p_element_in_button_scope();
+                }
+                self.insert_html_element(tag);
+                self.frameset_ok = false;
+                self.mode = InsertionMode::InTable;
+            }
+            TreeToken::EndTag(ref tag) if tag.name == "br" => {
+                self.error("unexpected-end-tag", "Unexpected br end tag");
+                let mut br = self.synthetic_tag("br");
+                br.span = self.span;
+                self.in_body(TreeToken::StartTag(br));
+            }
+            TreeToken::StartTag(tag)
+                if is_one_of(&tag.name, &["area", "br", "embed", "img", "keygen", "wbr"]) =>
+            {
+                self.reconstruct_active_formatting();
+                self.insert_html_element(tag);
+                self.pop();
+                self.self_closing_acknowledged = true;
+                self.frameset_ok = false;
+            }
+            TreeToken::StartTag(tag) if tag.name == "input" => {
+                self.reconstruct_active_formatting();
+                let hidden = is_hidden_input(&tag);
+                self.insert_html_element(tag);
+                self.pop();
+                self.self_closing_acknowledged = true;
+                if !hidden {
+                    self.frameset_ok = false;
+                }
+            }
+            TreeToken::StartTag(tag) if is_one_of(&tag.name, &["param", "source", "track"]) => {
+                self.insert_html_element(tag);
+                self.pop();
+                self.self_closing_acknowledged = true;
+            }
+            TreeToken::StartTag(tag) if tag.name == "hr" => {
+                self.close_p_element_in_button_scope();
+                self.insert_html_element(tag);
+                self.pop();
+                self.self_closing_acknowledged = true;
+                self.frameset_ok = false;
+            }
+            TreeToken::StartTag(mut tag) if tag.name == "image" => {
+                self.error("unexpected-start-tag", "Unexpected image start tag");
+                tag.name = String::from("img");
+                self.in_body(TreeToken::StartTag(tag));
+            }
+            TreeToken::StartTag(tag) if tag.name == "textarea" => {
+                self.insert_html_element(tag);
+                self.ignore_line_feed = true;
+                self.tokenizer.set_state(State::Rcdata);
+                self.original_mode = self.mode;
+                self.frameset_ok = false;
+                self.mode = InsertionMode::Text;
+            }
+            TreeToken::StartTag(tag) if tag.name == "xmp" => {
+                self.close_p_element_in_button_scope();
+                self.reconstruct_active_formatting();
+                self.frameset_ok = false;
+                self.parse_generic_text(tag, State::Rawtext);
+            }
+            TreeToken::StartTag(tag) if tag.name == "iframe" => {
+                self.frameset_ok = false;
+                self.parse_generic_text(tag, State::Rawtext);
+            }
+            TreeToken::StartTag(tag)
+                if tag.name == "noembed" || (tag.name == "noscript" && self.scripting) =>
+            {
+                self.parse_generic_text(tag, State::Rawtext);
+            }
+            TreeToken::StartTag(tag) if tag.name == "select" => {
+                self.reconstruct_active_formatting();
+                self.insert_html_element(tag);
+                self.frameset_ok = false;
+                self.mode = match self.mode {
+                    InsertionMode::InTable
+                    | InsertionMode::InCaption
+                    | InsertionMode::InTableBody
+                    | InsertionMode::InRow
+                    | InsertionMode::InCell => InsertionMode::InSelectInTable,
+                    _ => InsertionMode::InSelect,
+                };
+            }
+            TreeToken::StartTag(tag) if is_one_of(&tag.name, &["optgroup", "option"]) => {
+                if self.current_node_is("option") {
+                    self.pop();
+                }
+                self.reconstruct_active_formatting();
+                self.insert_html_element(tag);
+            }
+            TreeToken::StartTag(tag) if is_one_of(&tag.name, &["rb", "rtc"]) => {
+                if self.in_scope(&["ruby"], Scope::Default) {
+                    self.generate_implied_end_tags(None);
+                    if !self.current_node_is("ruby") {
+                        self.error("unexpected-start-tag", "Unexpected ruby annotation");
+                    }
+                }
+                self.insert_html_element(tag);
+            }
+            TreeToken::StartTag(tag) if is_one_of(&tag.name, &["rp", "rt"]) => {
+                if self.in_scope(&["ruby"], Scope::Default) {
+                    self.generate_implied_end_tags(Some("rtc"));
+                    if !self.current_node_is("rtc") && !self.current_node_is("ruby") {
+                        self.error("unexpected-start-tag", "Unexpected ruby annotation");
+                    }
+                }
+                self.insert_html_element(tag);
+            }
+            TreeToken::StartTag(tag) if tag.name == "math" || tag.name == "svg" => {
+                self.reconstruct_active_formatting();
+                let namespace = if tag.name == "math" {
+                    Namespace::MathMl
+                } else {
+                    Namespace::Svg
+                };
+                self.insert_foreign_element(tag, namespace);
+            }
+            TreeToken::StartTag(ref tag)
+                if is_one_of(
+                    &tag.name,
+                    &[
+                        "caption", "col", "colgroup", "frame", "head", "tbody", "td", "tfoot",
+                        "th", "thead", "tr",
+                    ],
+                ) =>
+            {
+                self.error("unexpected-start-tag", "Unexpected start tag");
+            }
+            TreeToken::StartTag(tag) => {
+                self.reconstruct_active_formatting();
+                self.insert_html_element(tag);
+            }
+            TreeToken::EndTag(tag) => self.any_other_end_tag(&tag.name),
+        }
+    }
+
+    fn in_foreign_content(&mut self, token: TreeToken) {
+        match token {
+            TreeToken::Character('\0') => {
+                self.error("unexpected-null-character", "Unexpected null character");
+                self.insert_character('\u{FFFD}');
+            }
+            TreeToken::Character(c) => {
+                self.insert_character(c);
+                if !is_whitespace(c) {
+                    self.frameset_ok = false;
+                }
+            }
+            TreeToken::Comment(comment) => self.insert_comment(comment),
+            TreeToken::Doctype(_) => self.error("unexpected-doctype", "Unexpected doctype"),
+            TreeToken::StartTag(ref tag)
+                if !self.at_foreign_context()
+                    && (is_one_of(&tag.name, BREAKOUT_ELEMENTS)
+                        || (tag.name == "font"
+                            && ["color", "face", "size"]
+                                .iter()
+                                .any(|name| tag.attribute(name).is_some()))) =>
+            {
+                self.error(
+                    "unexpected-start-tag",
+                    "Unexpected HTML start tag in foreign content",
+                );
+                self.break_out_of_foreign_content(token);
+            }
+            TreeToken::EndTag(ref tag)
+                if !self.at_foreign_context() && (tag.name == "br" || tag.name == "p") =>
+            {
+                self.error(
+                    "unexpected-end-tag",
+                    "Unexpected HTML end tag in foreign content",
+                );
+                self.break_out_of_foreign_content(token);
+            }
+            TreeToken::StartTag(tag) => {
+                let namespace = self.namespace(self.adjusted_current_node());
+                self.insert_foreign_element(tag, namespace);
+            }
+            TreeToken::EndTag(tag) => {
+                let mut index = self.open_elements.len() - 1;
+                if !self.local_name_is(self.open_elements[index], &tag.name) {
+                    self.error("unexpected-end-tag", "Unexpected end tag");
+                }
+                while index > 0 {
+                    let node = self.open_elements[index];
+                    if self.local_name_is(node, &tag.name) {
+                        self.pop_to(index);
+                        return;
+                    }
+                    index -= 1;
+                    if self.is_html_element(self.open_elements[index]) {
+                        let mode = self.mode;
+                        self.process_in_mode(mode, TreeToken::EndTag(tag));
+                        return;
+                    }
+                }
+            }
+            TreeToken::Eof => {
+                let mode = self.mode;
+                self.process_in_mode(mode, token);
+            }
+        }
+    }
+
+    // Whether breaking out of foreign content would pop everything down to a
+    // foreign context element.
+    fn at_foreign_context(&self) -> bool {
+        let context = match self.context {
+            Some(context) => context,
+            None => return false,
+        };
+        !self.is_html_element(context)
+            && self.open_elements[1..].iter().all(|&node| {
+                !self.is_html_element(node)
+                    && !self.is_mathml_text_integration_point(node)
+                    && !self.is_html_integration_point(node)
+            })
+    }
+
+    // HTML elements close the foreign elements they appear in.
+    fn break_out_of_foreign_content(&mut self, token: TreeToken) {
+        loop {
+            let node = self.current_node();
+            if self.is_html_element(node)
+                || self.is_mathml_text_integration_point(node)
+                || self.is_html_integration_point(node)
+            {
+                break;
+            }
+            self.pop();
+        }
+        let mode = self.mode;
+        self.process_in_mode(mode, token);
+    }
+
+    fn any_other_end_tag(&mut self, name: &str) {
+        let mut index = self.open_elements.len();
+        while index > 0 {
+            index -= 1;
+            let node = self.open_elements[index];
+            if self.is_element(node, name) {
+                self.generate_implied_end_tags(Some(name));
+                if node != self.current_node() {
+                    self.error("unexpected-end-tag", "Unexpected end tag");
+                }
+                self.pop_to(index);
+                return;
+            }
+            if self.is_special(node) {
+                self.error("unexpected-end-tag", "Unexpected end tag");
+                return;
+            }
+        }
+    }
+
+    fn text(&mut self, token: TreeToken) {
+        match token {
+            TreeToken::Character(c) => self.insert_character(c),
+            TreeToken::Eof => {
+                self.error("unexpected-eof", "Unexpected end of file in text");
+                self.pop();
+                let mode = self.original_mode;
+                self.reprocess(mode, token);
+            }
+            _ => {
+                self.pop();
+                self.mode = self.original_mode;
+            }
+        }
+    }
+
+    fn in_table(&mut self, token: TreeToken) {
+        match token {
+            TreeToken::Character(_)
+                if self.is_element_one_of(
+                    self.current_node(),
+                    &["table", "tbody", "template", "tfoot", "thead", "tr"],
+                ) =>
+            {
+                self.pending_table_characters = String::new();
+                self.original_mode = self.mode;
+                self.reprocess(InsertionMode::InTableText, token);
+            }
+            TreeToken::Comment(comment) => self.insert_comment(comment),
+            TreeToken::Doctype(_) => self.error("unexpected-doctype", "Unexpected doctype"),
+            TreeToken::StartTag(tag) if tag.name == "caption" => {
+                self.clear_stack_back_to(&["table", "template", "html"]);
+                self.active_formatting.push(FormattingEntry::Marker);
+                self.insert_html_element(tag);
+                self.mode = InsertionMode::InCaption;
+            }
+            TreeToken::StartTag(tag) if tag.name == "colgroup" => {
+                self.clear_stack_back_to(&["table", "template", "html"]);
+                self.insert_html_element(tag);
+                self.mode = InsertionMode::InColumnGroup;
+            }
+            TreeToken::StartTag(ref tag) if tag.name == "col" => {
+                self.clear_stack_back_to(&["table", "template", "html"]);
+                self.insert_html_element(self.synthetic_tag("colgroup"));
+                self.reprocess(InsertionMode::InColumnGroup, token);
+            }
+            TreeToken::StartTag(tag) if is_one_of(&tag.name, &["tbody", "tfoot", "thead"]) => {
+                self.clear_stack_back_to(&["table", "template", "html"]);
+                self.insert_html_element(tag);
+                self.mode = InsertionMode::InTableBody;
+            }
+            TreeToken::StartTag(ref tag) if is_one_of(&tag.name, &["td", "th", "tr"]) => {
+                self.clear_stack_back_to(&["table", "template", "html"]);
+                self.insert_html_element(self.synthetic_tag("tbody"));
+                self.reprocess(InsertionMode::InTableBody, token);
+            }
+            TreeToken::StartTag(ref tag) if tag.name == "table" => {
+                self.error("unexpected-start-tag", "Unexpected nested table");
+                if !self.in_scope(&["table"], Scope::Table) {
+                    return;
+                }
+                self.pop_until("table");
+                self.reset_insertion_mode();
+                let mode = self.mode;
+                self.reprocess(mode, token);
+            }
+            TreeToken::EndTag(ref tag) if tag.name == "table" => {
+                if !self.in_scope(&["table"], Scope::Table) {
+                    self.error("unexpected-end-tag", "Unexpected table end tag");
+                    return;
+                }
+                self.pop_until("table");
+                self.reset_insertion_mode();
+            }
+            TreeToken::EndTag(ref tag)
+                if is_one_of(
+                    &tag.name,
+                    &[
+                        "body", "caption", "col", "colgroup", "html", "tbody", "td", "tfoot", "th",
+                        "thead", "tr",
+                    ],
+                ) =>
+            {
+                self.error("unexpected-end-tag", "Unexpected end tag in table");
+            }
+            TreeToken::StartTag(ref tag)
+                if is_one_of(&tag.name, &["style", "script", "template"]) =>
+            {
+                self.in_head(token)
+            }
+            TreeToken::EndTag(ref tag) if tag.name == "template" => self.in_head(token),
+            TreeToken::StartTag(tag) if tag.name == "input" && is_hidden_input(&tag) => {
+                self.error("unexpected-start-tag", "Unexpected hidden input in table");
+                self.insert_html_element(tag);
+                self.pop();
+                self.self_closing_acknowledged = true;
+            }
+            TreeToken::StartTag(tag) if tag.name == "form" => {
+                self.error("unexpected-start-tag", "Unexpected form in table");
+                if self.has_open_element("template") || self.form_element.is_some() {
+                    return;
+                }
+                let form = self.insert_html_element(tag);
+                self.form_element = Some(form);
+                self.pop();
+            }
+            TreeToken::Eof => self.in_body(token),
+            token => {
+                self.error("unexpected-token", "Unexpected token in table");
+                self.foster_parenting = true;
+                self.in_body(token);
+                self.foster_parenting = false;
+            }
+        }
+    }
+
+    fn in_table_text(&mut self, token: TreeToken) {
+        match token {
+            TreeToken::Character('\0') => {
+                self.error("unexpected-null-character", "Unexpected null character")
+            }
+            TreeToken::Character(c) => {
+                if self.pending_table_characters.is_empty() {
+                    self.pending_table_span = self.span;
+                }
+                self.pending_table_characters.push(c);
+                self.pending_table_span.end = self.span.end;
+            }
+            token => {
+                let pending = mem::take(&mut self.pending_table_characters);
+                let span = mem::replace(&mut self.span, self.pending_table_span);
+                if pending.chars().any(|c| !is_whitespace(c)) {
+                    self.error("unexpected-character", "Unexpected characters in table");
+                    for c in pending.chars() {
+                        self.foster_parenting = true;
+                        self.in_body(TreeToken::Character(c));
+                        self.foster_parenting = false;
+                    }
+                } else {
+                    for c in pending.chars() {
+                        self.insert_character(c);
+                    }
+                }
+                self.span = span;
+                let mode = self.original_mode;
+                self.reprocess(mode, token);
+            }
+        }
+    }
+
+    fn in_caption(&mut self, token: TreeToken) {
+        match token {
+            TreeToken::EndTag(ref tag) if tag.name == "caption" => {
+                self.close_caption();
+            }
+            TreeToken::StartTag(ref tag)
+                if is_one_of(
+                    &tag.name,
+                    &[
+                        "caption", "col", "colgroup", "tbody", "td", "tfoot", "th", "thead", "tr",
+                    ],
+                ) =>
+            {
+                if self.close_caption() {
+                    self.reprocess(InsertionMode::InTable, token);
+                }
+            }
+            TreeToken::EndTag(ref tag) if tag.name == "table" => {
+                if self.close_caption() {
+                    self.reprocess(InsertionMode::InTable, token);
+                }
+            }
+            TreeToken::EndTag(ref tag)
+                if is_one_of(
+                    &tag.name,
+                    &[
+                        "body", "col", "colgroup", "html", "tbody", "td", "tfoot", "th", "thead",
+                        "tr",
+                    ],
+                ) =>
+            {
+                self.error("unexpected-end-tag", "Unexpected end tag in caption");
+            }
+            token => self.in_body(token),
+        }
+    }
+
+    fn close_caption(&mut self) -> bool {
+        if !self.in_scope(&["caption"], Scope::Table) {
+            self.error("unexpected-end-tag", "Unexpected caption end");
+            return false;
+        }
+        self.generate_implied_end_tags(None);
+        if !self.current_node_is("caption") {
+            self.error("unexpected-end-tag", "Unexpected caption end");
+        }
+        self.pop_until("caption");
+        self.clear_active_formatting_to_last_marker();
+        self.mode = InsertionMode::InTable;
+        true
+    }
+
+    fn in_column_group(&mut self, token: TreeToken) {
+        match token {
+            TreeToken::Character(c) if is_whitespace(c) => self.insert_character(c),
+            TreeToken::Comment(comment) => self.insert_comment(comment),
+            TreeToken::Doctype(_) => self.error("unexpected-doctype", "Unexpected doctype"),
+            TreeToken::StartTag(ref tag) if tag.name == "html" => self.in_body(token),
+            TreeToken::StartTag(tag) if tag.name == "col" => {
+                self.insert_html_element(tag);
+                self.pop();
+                self.self_closing_acknowledged = true;
+            }
+            TreeToken::EndTag(ref tag) if tag.name == "colgroup" => {
+                if !self.current_node_is("colgroup") {
+                    self.error("unexpected-end-tag", "Unexpected colgroup end tag");
+                    return;
+                }
+                self.pop();
+                self.mode = InsertionMode::InTable;
+            }
+            TreeToken::EndTag(ref tag) if tag.name == "col" => {
+                self.error("unexpected-end-tag", "Unexpected col end tag");
+            }
+            TreeToken::StartTag(ref tag) if tag.name == "template" => self.in_head(token),
+            TreeToken::EndTag(ref tag) if tag.name == "template" => self.in_head(token),
+            TreeToken::Eof => self.in_body(token),
+            token => {
+                if !self.current_node_is("colgroup") {
+                    self.error("unexpected-token", "Unexpected token in column group");
+                    return;
+                }
+                self.pop();
+                self.reprocess(InsertionMode::InTable, token);
+            }
+        }
+    }
+
+    fn in_table_body(&mut self, token: TreeToken) {
+        match token {
+            TreeToken::StartTag(tag) if tag.name == "tr" => {
+                self.clear_stack_back_to(&["tbody", "tfoot", "thead", "template", "html"]);
+                self.insert_html_element(tag);
+                self.mode = InsertionMode::InRow;
+            }
+            TreeToken::StartTag(ref tag) if is_one_of(&tag.name, &["th", "td"]) => {
+                self.error("unexpected-start-tag", "Unexpected cell outside of row");
+                self.clear_stack_back_to(&["tbody", "tfoot", "thead", "template", "html"]);
+                self.insert_html_element(self.synthetic_tag("tr"));
+                self.reprocess(InsertionMode::InRow, token);
+            }
+            TreeToken::EndTag(tag) if is_one_of(&tag.name, &["tbody", "tfoot", "thead"]) => {
+                if !self.in_scope(&[tag.name.as_str()], Scope::Table) {
+                    self.error("unexpected-end-tag", "Unexpected end tag in table body");
+                    return;
+                }
+                self.clear_stack_back_to(&["tbody", "tfoot", "thead", "template", "html"]);
+                self.pop();
+                self.mode = InsertionMode::InTable;
+            }
+            TreeToken::StartTag(ref tag)
+                if is_one_of(
+                    &tag.name,
+                    &["caption", "col", "colgroup", "tbody", "tfoot", "thead"],
+                ) =>
+            {
+                self.close_table_body(token);
+            }
+            TreeToken::EndTag(ref tag) if tag.name == "table" => self.close_table_body(token),
+            TreeToken::EndTag(ref tag)
+                if is_one_of(
+                    &tag.name,
+                    &[
+                        "body", "caption", "col", "colgroup", "html", "td", "th", "tr",
+                    ],
+                ) =>
+            {
+                self.error("unexpected-end-tag", "Unexpected end tag in table body");
+            }
+            token => self.in_table(token),
+        }
+    }
+
+    fn close_table_body(&mut self, token: TreeToken) {
+        if !self.in_scope(&["tbody", "thead", "tfoot"], Scope::Table) {
+            self.error("unexpected-token", "Unexpected token in table body");
+            return;
+        }
+        self.clear_stack_back_to(&["tbody", "tfoot", "thead", "template", "html"]);
+        self.pop();
+        self.reprocess(InsertionMode::InTable, token);
+    }
+
+    fn in_row(&mut self, token: TreeToken) {
+        match token {
+            TreeToken::StartTag(tag) if is_one_of(&tag.name, &["th", "td"]) => {
+                self.clear_stack_back_to(&["tr", "template", "html"]);
+                self.insert_html_element(tag);
+                self.mode = InsertionMode::InCell;
+                self.active_formatting.push(FormattingEntry::Marker);
+            }
+            TreeToken::EndTag(ref tag) if tag.name == "tr" => {
+                self.close_row();
+            }
+            TreeToken::StartTag(ref tag)
+                if is_one_of(
+                    &tag.name,
+                    &[
+                        "caption", "col", "colgroup", "tbody", "tfoot", "thead", "tr",
+                    ],
+                ) =>
+            {
+                if self.close_row() {
+                    self.reprocess(InsertionMode::InTableBody, token);
+                }
+            }
+            TreeToken::EndTag(ref tag) if tag.name == "table" => {
+                if self.close_row() {
+                    self.reprocess(InsertionMode::InTableBody, token);
+                }
+            }
+            TreeToken::EndTag(ref tag) if is_one_of(&tag.name, &["tbody", "tfoot", "thead"]) => {
+                if !self.in_scope(&[tag.name.as_str()], Scope::Table) {
+                    self.error("unexpected-end-tag", "Unexpected end tag in row");
+                    return;
+                }
+                if self.close_row() {
+                    self.reprocess(InsertionMode::InTableBody, token);
+                }
+            }
+            TreeToken::EndTag(ref tag)
+                if is_one_of(
+                    &tag.name,
+                    &["body", "caption", "col", "colgroup", "html", "td", "th"],
+                ) =>
+            {
+                self.error("unexpected-end-tag", "Unexpected end tag in row");
+            }
+            token => self.in_table(token),
+        }
+    }
+
+    fn close_row(&mut self) -> bool {
+        if !self.in_scope(&["tr"], Scope::Table) {
+            self.error("unexpected-end-tag", "Unexpected row end");
+            return false;
+        }
+        self.clear_stack_back_to(&["tr", "template", "html"]);
+        self.pop();
+        self.mode = InsertionMode::InTableBody;
+        true
+    }
+
+    fn in_cell(&mut self, token: TreeToken) {
+        match token {
+            TreeToken::EndTag(tag) if is_one_of(&tag.name, &["td", "th"]) => {
+                if !self.in_scope(&[tag.name.as_str()], Scope::Table) {
+                    self.error("unexpected-end-tag", "Unexpected cell end tag");
+                    return;
+                }
+                self.generate_implied_end_tags(None);
+                if !self.current_node_is(&tag.name) {
+                    self.error("unexpected-end-tag", "Unexpected cell end tag");
+                }
+                self.pop_until(&tag.name);
+                self.clear_active_formatting_to_last_marker();
+                self.mode = InsertionMode::InRow;
+            }
+            TreeToken::StartTag(ref tag)
+                if is_one_of(
+                    &tag.name,
+                    &[
+                        "caption", "col", "colgroup", "tbody", "td", "tfoot", "th", "thead", "tr",
+                    ],
+                ) =>
+            {
+                if !self.in_scope(&["td", "th"], Scope::Table) {
+                    self.error("unexpected-start-tag", "Unexpected start tag in cell");
+                    return;
+                }
+                self.close_cell();
+                let mode = self.mode;
+                self.reprocess(mode, token);
+            }
+            TreeToken::EndTag(ref tag)
+                if is_one_of(&tag.name, &["body", "caption", "col", "colgroup", "html"]) =>
+            {
+                self.error("unexpected-end-tag", "Unexpected end tag in cell");
+            }
+            TreeToken::EndTag(ref tag)
+                if is_one_of(&tag.name, &["table", "tbody", "tfoot", "thead", "tr"]) =>
+            {
+                if !self.in_scope(&[tag.name.as_str()], Scope::Table) {
+                    self.error("unexpected-end-tag", "Unexpected end tag in cell");
+                    return;
+                }
+                self.close_cell();
+                let mode = self.mode;
+                self.reprocess(mode, token);
+            }
+            token => self.in_body(token),
+        }
+    }
+
+    fn close_cell(&mut self) {
+        self.generate_implied_end_tags(None);
+        if !self.current_node_is("td") && !self.current_node_is("th") {
+            self.error("unexpected-end-tag", "Unexpected cell end");
+        }
+        self.pop_until_one_of(&["td", "th"]);
+        self.clear_active_formatting_to_last_marker();
+        self.mode = InsertionMode::InRow;
+    }
+
+    fn in_select(&mut self, token: TreeToken) {
+        match token {
+            TreeToken::Character('\0') => {
+                self.error("unexpected-null-character", "Unexpected null character")
+            }
+            TreeToken::Character(c) => self.insert_character(c),
+            TreeToken::Comment(comment) => self.insert_comment(comment),
+            TreeToken::Doctype(_) => self.error("unexpected-doctype", "Unexpected doctype"),
+            TreeToken::StartTag(ref tag) if tag.name == "html" => self.in_body(token),
+            TreeToken::StartTag(tag) if tag.name == "option" => {
+                if self.current_node_is("option") {
+                    self.pop();
+                }
+                self.insert_html_element(tag);
+            }
+            TreeToken::StartTag(tag) if tag.name == "optgroup" => {
+                if self.current_node_is("option") {
+                    self.pop();
+                }
+                if self.current_node_is("optgroup") {
+                    self.pop();
+                }
+                self.insert_html_element(tag);
+            }
+            TreeToken::StartTag(tag) if tag.name == "hr" => {
+                if self.current_node_is("option") {
+                    self.pop();
+                }
+                if self.current_node_is("optgroup") {
+                    self.pop();
+                }
+                self.insert_html_element(tag);
+                self.pop();
+                self.self_closing_acknowledged = true;
+            }
+            TreeToken::EndTag(ref tag) if tag.name == "optgroup" => {
+                let length = self.open_elements.len();
+                if self.current_node_is("option")
+                    && length > 1
+                    && self.is_element(self.open_elements[length - 2], "optgroup")
+                {
+                    self.pop();
+                }
+                if self.current_node_is("optgroup") {
+                    self.pop();
+                } else {
+                    self.error("unexpected-end-tag", "Unexpected optgroup end tag");
+                }
+            }
+            TreeToken::EndTag(ref tag) if tag.name == "option" => {
+                if self.current_node_is("option") {
+                    self.pop();
+                } else {
+                    self.error("unexpected-end-tag", "Unexpected option end tag");
+                }
+            }
+            TreeToken::EndTag(ref tag) if tag.name == "select" => {
+                if !self.in_scope(&["select"], Scope::Select) {
+                    self.error("unexpected-end-tag", "Unexpected select end tag");
+                    return;
+                }
+                self.pop_until("select");
+                self.reset_insertion_mode();
+            }
+            TreeToken::StartTag(ref tag) if tag.name == "select" => {
+                self.error("unexpected-start-tag", "Unexpected nested select");
+                if !self.in_scope(&["select"], Scope::Select) {
+                    return;
+                }
+                self.pop_until("select");
+                self.reset_insertion_mode();
+            }
+            TreeToken::StartTag(ref tag)
+                if is_one_of(&tag.name, &["input", "keygen", "textarea"]) =>
+            {
+                self.error("unexpected-start-tag", "Unexpected start tag in select");
+                if !self.in_scope(&["select"], Scope::Select) {
+                    return;
+                }
+                self.pop_until("select");
+                self.reset_insertion_mode();
+                let mode = self.mode;
+                self.reprocess(mode, token);
+            }
+            TreeToken::StartTag(ref tag) if is_one_of(&tag.name, &["script", "template"]) => {
+                self.in_head(token)
+            }
+            TreeToken::EndTag(ref tag) if tag.name == "template" => self.in_head(token),
+            TreeToken::Eof => self.in_body(token),
+            _ => self.error("unexpected-token", "Unexpected token in select"),
+        }
+    }
+
+    fn in_select_in_table(&mut self, token: TreeToken) {
+        const TABLE_ELEMENTS: &[&str] = &[
+            "caption", "table", "tbody", "tfoot", "thead", "tr", "td", "th",
+        ];
+        match token {
+            TreeToken::StartTag(ref tag) if is_one_of(&tag.name, TABLE_ELEMENTS) => {
+                self.error(
+                    "unexpected-start-tag",
+                    "Unexpected table start tag in select",
+                );
+                self.pop_until("select");
+                self.reset_insertion_mode();
+                let mode = self.mode;
+                self.reprocess(mode, token);
+            }
+            TreeToken::EndTag(ref tag) if is_one_of(&tag.name, TABLE_ELEMENTS) => {
+                self.error("unexpected-end-tag", "Unexpected table end tag in select");
+                if !self.in_scope(&[tag.name.as_str()], Scope::Table) {
+                    return;
+                }
+                self.pop_until("select");
+                self.reset_insertion_mode();
+                let mode = self.mode;
+                self.reprocess(mode, token);
+            }
+            token => self.in_select(token),
+        }
+    }
+
+    fn in_template(&mut self, token: TreeToken) {
+        match token {
+            TreeToken::Character(_) | TreeToken::Comment(_) | TreeToken::Doctype(_) => {
+                self.in_body(token)
+            }
+            TreeToken::StartTag(ref tag)
+                if is_one_of(
+                    &tag.name,
+                    &[
+                        "base", "basefont", "bgsound", "link", "meta", "noframes", "script",
+                        "style", "template", "title",
+                    ],
+                ) =>
+            {
+                self.in_head(token)
+            }
+            TreeToken::EndTag(ref tag) if tag.name == "template" => self.in_head(token),
+            TreeToken::StartTag(ref tag) => {
+                let mode = match tag.name.as_str() {
+                    "caption" | "colgroup" | "tbody" | "tfoot" | "thead" => InsertionMode::InTable,
+                    "col" => InsertionMode::InColumnGroup,
+                    "tr" => InsertionMode::InTableBody,
+                    "td" | "th" => InsertionMode::InRow,
+                    _ => InsertionMode::InBody,
+                };
+                self.template_modes.pop();
+                self.template_modes.push(mode);
+                self.reprocess(mode, token);
+            }
+            TreeToken::EndTag(_) => {
+                self.error("unexpected-end-tag", "Unexpected end tag in template")
+            }
+            TreeToken::Eof => {
+                if !self.has_open_element("template") {
+                    self.stopped = true;
+                    return;
+                }
+                self.error("unexpected-eof", "Unexpected end of file in template");
+                self.pop_until("template");
+                self.clear_active_formatting_to_last_marker();
+                self.template_modes.pop();
+                self.reset_insertion_mode();
+                let mode = self.mode;
+                self.reprocess(mode, token);
+            }
+        }
+    }
+
+    fn after_body(&mut self, token: TreeToken) {
+        match token {
+            TreeToken::Character(c) if is_whitespace(c) => self.in_body(token),
+            TreeToken::Comment(comment) => {
+                let html = self.open_elements[0];
+                self.append_comment_to(html, comment);
+            }
+            TreeToken::Doctype(_) => self.error("unexpected-doctype", "Unexpected doctype"),
+            TreeToken::StartTag(ref tag) if tag.name == "html" => self.in_body(token),
+            TreeToken::EndTag(ref tag) if tag.name == "html" => {
+                if self.context.is_some() {
+                    self.error("unexpected-end-tag", "Unexpected html end tag");
+                    return;
+                }
+                self.mode = InsertionMode::AfterAfterBody;
+            }
+            TreeToken::Eof => self.stopped = true,
+            token => {
+                self.error("unexpected-token", "Unexpected token after body");
+                self.reprocess(InsertionMode::InBody, token);
+            }
+        }
+    }
+
+    fn in_frameset(&mut self, token: TreeToken) {
+        match token {
+            TreeToken::Character(c) if is_whitespace(c) => self.insert_character(c),
+            TreeToken::Comment(comment) => self.insert_comment(comment),
+            TreeToken::Doctype(_) => self.error("unexpected-doctype", "Unexpected doctype"),
+            TreeToken::StartTag(ref tag) if tag.name == "html" => self.in_body(token),
+            TreeToken::StartTag(tag) if tag.name == "frameset" => {
+                self.insert_html_element(tag);
+            }
+            TreeToken::EndTag(ref tag) if tag.name == "frameset" => {
+                if self.open_elements.len() == 1 {
+                    self.error("unexpected-end-tag", "Unexpected frameset end tag");
+                    return;
+                }
+                self.pop();
+                if self.context.is_none() && !self.current_node_is("frameset") {
+                    self.mode = InsertionMode::AfterFrameset;
+                }
+            }
+            TreeToken::StartTag(tag) if tag.name == "frame" => {
+                self.insert_html_element(tag);
+                self.pop();
+                self.self_closing_acknowledged = true;
+            }
+            TreeToken::StartTag(ref tag) if tag.name == "noframes" => self.in_head(token),
+            TreeToken::Eof => {
+                if self.open_elements.len() != 1 {
+                    self.error("unexpected-eof", "Unexpected end of file in frameset");
+                }
+                self.stopped = true;
+            }
+            _ => self.error("unexpected-token", "Unexpected token in frameset"),
+        }
+    }
+
+    fn after_frameset(&mut self, token: TreeToken) {
+        match token {
+            TreeToken::Character(c) if is_whitespace(c) => self.insert_character(c),
+            TreeToken::Comment(comment) => self.insert_comment(comment),
+            TreeToken::Doctype(_) => self.error("unexpected-doctype", "Unexpected doctype"),
+            TreeToken::StartTag(ref tag) if tag.name == "html" => self.in_body(token),
+            TreeToken::EndTag(ref tag) if tag.name == "html" => {
+                self.mode = InsertionMode::AfterAfterFrameset;
+            }
+            TreeToken::StartTag(ref tag) if tag.name == "noframes" => self.in_head(token),
+            TreeToken::Eof => self.stopped = true,
+            _ => self.error("unexpected-token", "Unexpected token after frameset"),
+        }
+    }
+
+    fn after_after_body(&mut self, token: TreeToken) {
+        match token {
+            TreeToken::Comment(comment) => self.append_comment_to(DOCUMENT, comment),
+            TreeToken::Doctype(_) => self.in_body(token),
+            TreeToken::Character(c) if is_whitespace(c) => self.in_body(token),
+            TreeToken::StartTag(ref tag) if tag.name == "html" => self.in_body(token),
+            TreeToken::Eof => self.stopped = true,
+            token => {
+                self.error("unexpected-token", "Unexpected token after body");
+                self.reprocess(InsertionMode::InBody, token);
+            }
+        }
+    }
+
+    fn after_after_frameset(&mut self, token: TreeToken) {
+        match token {
+            TreeToken::Comment(comment) => self.append_comment_to(DOCUMENT, comment),
+            TreeToken::Doctype(_) => self.in_body(token),
+            TreeToken::Character(c) if is_whitespace(c) => self.in_body(token),
+            TreeToken::StartTag(ref tag) if tag.name == "html" => self.in_body(token),
+            TreeToken::Eof => self.stopped = true,
+            TreeToken::StartTag(ref tag) if tag.name == "noframes" => self.in_head(token),
+            _ => self.error("unexpected-token", "Unexpected token after frameset"),
+        }
+    }
+
+    fn parse_generic_text(&mut self, tag: Tag, state: State) {
+        self.insert_html_element(tag);
+        self.tokenizer.set_state(state);
+        self.original_mode = self.mode;
+        self.mode = InsertionMode::Text;
+    }
+
+    fn check_unclosed_elements_at_end(&mut self) {
+        let unclosed = self.open_elements.iter().any(|&node| {
+            !self.is_element_one_of(
+                node,
+                &[
+                    "dd", "dt", "li", "optgroup", "option", "p", "rb", "rp", "rt", "rtc", "tbody",
+                    "td", "tfoot", "th", "thead", "tr", "body", "html",
+                ],
+            )
+        });
+        if unclosed {
+            self.error("unclosed-elements", "Unclosed elements at end of body");
+        }
+    }
+
+    // Tree manipulation
+
+    fn new_node(&mut self, data: NodeType) -> NodeId {
+        self.nodes.push(TreeNode {
+            data,
+            parent: None,
+            children: vec![],
+            span: self.span,
+            tag: None,
+        });
+        self.nodes.len() - 1
+    }
+
+    fn create_element(&mut self, tag: Tag, namespace: Namespace) -> NodeId {
+        let mut data = element(tag.name.clone(), AttrMap::new(), vec![]).node_type;
+        if let NodeType::Element(ref mut element) = data {
+            element.namespace = namespace;
+        }
+        let node = self.new_node(data);
+        self.add_missing_attributes(node, tag.attributes.clone());
+        self.nodes[node].span = tag.span;
+        self.nodes[node].tag = Some(tag);
+        node
+    }
+
+    // Implied elements get an empty span where the current token starts.
+    fn synthetic_tag(&self, name: &str) -> Tag {
+        Tag {
+            name: String::from(name),
+            attributes: vec![],
+            self_closing: false,
+            span: Span::at(self.span.start),
+        }
+    }
+
+    fn append(&mut self, parent: NodeId, child: NodeId) {
+        self.insert_at(parent, None, child);
+    }
+
+    fn insert_at(&mut self, parent: NodeId, before: Option<NodeId>, child: NodeId) {
+        self.detach(child);
+        let index = match before {
+            Some(sibling) => self.nodes[parent]
+                .children
+                .iter()
+                .position(|&node| node == sibling)
+                .unwrap_or(self.nodes[parent].children.len()),
+            None => self.nodes[parent].children.len(),
+        };
+        self.nodes[parent].children.insert(index, child);
+        self.nodes[child].parent = Some(parent);
+    }
+
+    fn detach(&mut self, node: NodeId) {
+        if let Some(parent) = self.nodes[node].parent.take() {
+            self.nodes[parent].children.retain(|&child| child != node);
+        }
+    }
+
+    fn current_node(&self) -> NodeId {
+        *self.open_elements.last().unwrap_or(&DOCUMENT)
+    }
+
+    // The context element stands in for the root of a fragment.
+    fn adjusted_current_node(&self) -> NodeId {
+        match self.context {
+            Some(context) if self.open_elements.len() == 1 => context,
+            _ => self.current_node(),
+        }
+    }
+
+    // Returns the parent to insert into and the sibling to insert before.
+    fn appropriate_insertion_location(
+        &self,
+        override_target: Option<NodeId>,
+    ) -> (NodeId, Option<NodeId>) {
+        let target = override_target.unwrap_or_else(|| self.current_node());
+        if !self.foster_parenting
+            || !self.is_element_one_of(target, &["table", "tbody", "tfoot", "thead", "tr"])
+        {
+            return (target, None);
+        }
+        let last_template = self.last_open_element("template");
+        let last_table = self.last_open_element("table");
+        if let Some(template_index) = last_template {
+            if last_table.is_none_or(|table_index| template_index > table_index) {
+                return (self.open_elements[template_index], None);
+            }
+        }
+        match last_table {
+            None => (self.open_elements[0], None),
+            Some(table_index) => {
+                let table = self.open_elements[table_index];
+                match self.nodes[table].parent {
+                    Some(parent) => (parent, Some(table)),
+                    None => (self.open_elements[table_index - 1], None),
+                }
+            }
+        }
+    }
+
+    fn insert_html_element(&mut self, tag: Tag) -> NodeId {
+        self.insert_element(tag, Namespace::Html)
+    }
+
+    // Foreign elements may close themselves with a trailing solidus.
+    fn insert_foreign_element(&mut self, mut tag: Tag, namespace: Namespace) {
+        adjust_foreign_names(&mut tag, namespace);
+        let self_closing = tag.self_closing;
+        self.insert_element(tag, namespace);
+        if self_closing {
+            self.pop();
+            self.self_closing_acknowledged = true;
+        }
+    }
+
+    fn insert_element(&mut self, tag: Tag, namespace: Namespace) -> NodeId {
+        let (parent, before) = self.appropriate_insertion_location(None);
+        let node = self.create_element(tag, namespace);
+        self.insert_at(parent, before, node);
+        self.open_elements.push(node);
+        node
+    }
+
+    fn insert_comment(&mut self, data: Comment) {
+        let (parent, before) = self.appropriate_insertion_location(None);
+        let node = self.new_node(data.into_node_type());
+        self.insert_at(parent, before, node);
+    }
+
+    fn append_comment_to(&mut self, parent: NodeId, data: Comment) {
+        let node = self.new_node(data.into_node_type());
+        self.append(parent, node);
+    }
+
+    fn insert_character(&mut self, c: char) {
+        let (parent, before) = self.appropriate_insertion_location(None);
+        if parent == DOCUMENT {
+            return;
+        }
+        let children = &self.nodes[parent].children;
+        let previous = match before {
+            Some(sibling) => {
+                let index = children.iter().position(|&node| node == sibling).unwrap();
+                if index > 0 {
+                    Some(children[index - 1])
+                } else {
+                    None
+                }
+            }
+            None => children.last().cloned(),
+        };
+        if let Some(previous) = previous {
+            if let NodeType::Text(ref mut text) = self.nodes[previous].data {
+                text.push(c);
+                self.nodes[previous].span.end = self.span.end;
+                return;
+            }
+        }
+        let node = self.new_node(text(c.to_string()).node_type);
+        self.insert_at(parent, before, node);
+    }
+
+    fn add_missing_attributes(&mut self, node: NodeId, attributes: Vec<Attribute>) {
+        if let NodeType::Element(ref mut element) = self.nodes[node].data {
+            for attribute in attributes {
+                if element.attributes.contains_key(&attribute.name) {
+                    continue;
+                }
+                let span = AttributeSpan {
+                    name: attribute.name_span,
+                    value: attribute.value_span,
+                };
+                element
+                    .attributes
+                    .insert(attribute.name, attribute.value, span);
+            }
+        }
+    }
+
+    // Element queries
+
+    // Names of HTML elements only, as foreign elements such as the SVG title
+    // may share them.
+    fn tag_name(&self, node: NodeId) -> Option<&str> {
+        match self.nodes[node].data {
+            NodeType::Element(ref element) if element.namespace == Namespace::Html => {
+                Some(element.tag_name.as_str())
+            }
+            _ => None,
+        }
+    }
+
+    fn namespace(&self, node: NodeId) -> Namespace {
+        match self.nodes[node].data {
+            NodeType::Element(ref element) => element.namespace,
+            _ => Namespace::Html,
+        }
+    }
+
+    fn is_html_element(&self, node: NodeId) -> bool {
+        self.tag_name(node).is_some()
+    }
+
+    fn is_foreign_element(&self, node: NodeId, namespace: Namespace, names: &[&str]) -> bool {
+        match self.nodes[node].data {
+            NodeType::Element(ref element) => {
+                element.namespace == namespace && names.contains(&element.tag_name.as_str())
+            }
+            _ => false,
+        }
+    }
+
+    // End tags are lowercased by the tokenizer while foreign element names may
+    // be mixed case.
+    fn local_name_is(&self, node: NodeId, name: &str) -> bool {
+        match self.nodes[node].data {
+            NodeType::Element(ref element) => element.tag_name.eq_ignore_ascii_case(name),
+            _ => false,
+        }
+    }
+
+    fn is_mathml_text_integration_point(&self, node: NodeId) -> bool {
+        self.is_foreign_element(node, Namespace::MathMl, &["mi", "mo", "mn", "ms", "mtext"])
+    }
+
+    fn is_html_integration_point(&self, node: NodeId) -> bool {
+        match self.nodes[node].data {
+            NodeType::Element(ref element) => match element.namespace {
+                Namespace::Svg => is_one_of(&element.tag_name, &["foreignObject", "desc", "title"]),
+                Namespace::MathMl => {
+                    element.tag_name == "annotation-xml"
+                        && element.attributes.get("encoding").is_some_and(|encoding| {
+                            encoding.eq_ignore_ascii_case("text/html")
+                                || encoding.eq_ignore_ascii_case("application/xhtml+xml")
+                        })
+                }
+                Namespace::Html => false,
+            },
+            _ => false,
+        }
+    }
+
+    // Foreign elements which are special and bound the default scopes.
+    fn is_special_foreign_element(&self, node: NodeId) -> bool {
+        self.is_foreign_element(
+            node,
+            Namespace::MathMl,
+            &["mi", "mo", "mn", "ms", "mtext", "annotation-xml"],
+        ) || self.is_foreign_element(node, Namespace::Svg, &["foreignObject", "desc", "title"])
+    }
+
+    fn is_element(&self, node: NodeId, name: &str) -> bool {
+        self.tag_name(node) == Some(name)
+    }
+
+    fn is_element_one_of(&self, node: NodeId, names: &[&str]) -> bool {
+        self.tag_name(node)
+            .is_some_and(|tag_name| names.contains(&tag_name))
+    }
+
+    fn current_node_is(&self, name: &str) -> bool {
+        self.is_element(self.current_node(), name)
+    }
+
+    fn is_special(&self, node: NodeId) -> bool {
+        self.is_element_one_of(node, SPECIAL_ELEMENTS) || self.is_special_foreign_element(node)
+    }
+
+    fn has_open_element(&self, name: &str) -> bool {
+        self.last_open_element(name).is_some()
+    }
+
+    fn last_open_element(&self, name: &str) -> Option<usize> {
+        self.open_elements
+            .iter()
+            .rposition(|&node| self.is_element(node, name))
+    }
+
+    fn is_scope_boundary(&self, node: NodeId, scope: Scope) -> bool {
+        const DEFAULT_BOUNDARIES: &[&str] = &[
+            "applet", "caption", "html", "table", "td", "th", "marquee", "object", "template",
+        ];
+        let default_boundary = self.is_element_one_of(node, DEFAULT_BOUNDARIES)
+            || self.is_special_foreign_element(node);
+        match scope {
+            Scope::Default => default_boundary,
+            Scope::ListItem => default_boundary || self.is_element_one_of(node, &["ol", "ul"]),
+            Scope::Button => default_boundary || self.is_element(node, "button"),
+            Scope::Table => self.is_element_one_of(node, &["html", "table", "template"]),
+            Scope::Select => !self.is_element_one_of(node, &["optgroup", "option"]),
+        }
+    }
+
+    fn in_scope(&self, names: &[&str], scope: Scope) -> bool {
+        for &node in self.open_elements.iter().rev() {
+            if self.is_element_one_of(node, names) {
+                return true;
+            }
+            if self.is_scope_boundary(node, scope) {
+                return false;
+            }
+        }
+        false
+    }
+
+    fn node_in_scope(&self, target: NodeId) -> bool {
+        for &node in self.open_elements.iter().rev() {
+            if node == target {
+                return true;
+            }
+            if self.is_scope_boundary(node, Scope::Default) {
+                return false;
+            }
+        }
+        false
+    }
+
+    // Stack of open elements
+
+    // Elements leave the stack through these, which record them as completed,
+    // apart from the head element pushed back on for a moment after head.
+    fn pop(&mut self) -> Option<NodeId> {
+        let node = self.open_elements.pop()?;
+        self.completed.push(node);
+        Some(node)
+    }
+
+    fn pop_to(&mut self, length: usize) {
+        while self.open_elements.len() > length {
+            self.pop();
+        }
+    }
+
+    fn remove_open_element(&mut self, node: NodeId) {
+        if let Some(index) = self.open_elements.iter().position(|&open| open == node) {
+            self.open_elements.remove(index);
+            self.completed.push(node);
+        }
+    }
+
+    fn pop_until(&mut self, name: &str) {
+        while let Some(node) = self.pop() {
+            if self.is_element(node, name) {
+                break;
+            }
+        }
+    }
+
+    fn pop_until_one_of(&mut self, names: &[&str]) {
+        while let Some(node) = self.pop() {
+            if self.is_element_one_of(node, names) {
+                break;
+            }
+        }
+    }
+
+    fn clear_stack_back_to(&mut self, names: &[&str]) {
+        while !self.is_element_one_of(self.current_node(), names) {
+            self.pop();
+        }
+    }
+
+    fn generate_implied_end_tags(&mut self, except: Option<&str>) {
+        loop {
+            let node = self.current_node();
+            let implied = self.is_element_one_of(
+                node,
+                &[
+                    "dd", "dt", "li", "optgroup", "option", "p", "rb", "rp", "rt", "rtc",
+                ],
+            );
+            if !implied || except.is_some_and(|name| self.is_element(node, name)) {
+                return;
+            }
+            self.pop();
+        }
+    }
+
+    fn generate_all_implied_end_tags(&mut self) {
+        while self.is_element_one_of(
+            self.current_node(),
+            &[
+                "caption", "colgroup", "dd", "dt", "li", "optgroup", "option", "p", "rb", "rp",
+                "rt", "rtc", "tbody", "td", "tfoot", "th", "thead", "tr",
+            ],
+        ) {
+            self.pop();
+        }
+    }
+
+    fn close_p_element(&mut self) {
+        self.generate_implied_end_tags(Some("p"));
+        if !self.current_node_is("p") {
+            self.error(
+                "unclosed-elements",
+                "Unexpected open elements when closing p",
+            );
+        }
+        self.pop_until("p");
+    }
+
+    fn close_p_element_in_button_scope(&mut self) {
+        if self.in_scope(&["p"], Scope::Button) {
+            self.close_p_element();
+        }
+    }
+
+    // Shared by li and dd/dt start tags which close an open item of the same
+    // kind unless a special element sits between them.
+    fn close_list_item(&mut self, names: &[&str]) {
+        for index in (0..self.open_elements.len()).rev() {
+            let node = self.open_elements[index];
+            if self.is_element_one_of(node, names) {
+                let name = self.tag_name(node).unwrap().to_string();
+                self.generate_implied_end_tags(Some(&name));
+                if !self.current_node_is(&name) {
+                    self.error(
+                        "unclosed-elements",
+                        "Unexpected open elements when closing list item",
+                    );
+                }
+                self.pop_until(&name);
+                return;
+            }
+            if self.is_special(node) && !self.is_element_one_of(node, &["address", "div", "p"]) {
+                return;
+            }
+        }
+    }
+
+    fn reset_insertion_mode(&mut self) {
+        for index in (0..self.open_elements.len()).rev() {
+            let last = index == 0;
+            let node = match self.context {
+                Some(context) if last => context,
+                _ => self.open_elements[index],
+            };
+            let name = self.tag_name(node).unwrap_or("").to_string();
+            let mode = match name.as_str() {
+                "select" => {
+                    if !last {
+                        for &ancestor in self.open_elements[..index].iter().rev() {
+                            if self.is_element(ancestor, "template") {
+                                break;
+                            }
+                            if self.is_element(ancestor, "table") {
+                                self.mode = InsertionMode::InSelectInTable;
+                                return;
+                            }
+                        }
+                    }
+                    InsertionMode::InSelect
+                }
+                "td" | "th" if !last => InsertionMode::InCell,
+                "tr" => InsertionMode::InRow,
+                "tbody" | "thead" | "tfoot" => InsertionMode::InTableBody,
+                "caption" => InsertionMode::InCaption,
+                "colgroup" => InsertionMode::InColumnGroup,
+                "table" => InsertionMode::InTable,
+                "template" => *self
+                    .template_modes
+                    .last()
+                    .unwrap_or(&InsertionMode::InTemplate),
+                "head" if !last => InsertionMode::InHead,
+                "body" => InsertionMode::InBody,
+                "frameset" => InsertionMode::InFrameset,
+                "html" => {
+                    if self.head_element.is_none() {
+                        InsertionMode::BeforeHead
+                    } else {
+                        InsertionMode::AfterHead
+                    }
+                }
+                _ if last => InsertionMode::InBody,
+                _ => continue,
+            };
+            self.mode = mode;
+            return;
+        }
+    }
+
+    // List of active formatting elements
+
+    fn formatting_tag(&self, node: NodeId) -> Tag {
+        self.nodes[node]
+            .tag
+            .clone()
+            .expect("formatting elements keep their tag")
+    }
+
+    fn push_active_formatting(&mut self, node: NodeId) {
+        // Noah's Ark clause: at most three matching entries after the last marker.
+        let tag = self.formatting_tag(node);
+        let mut matching = vec![];
+        for (index, entry) in self.active_formatting.iter().enumerate().rev() {
+            match *entry {
+                FormattingEntry::Marker => break,
+                FormattingEntry::Element(existing) => {
+                    if same_tag(&self.formatting_tag(existing), &tag) {
+                        matching.push(index);
+                    }
+                }
+            }
+        }
+        if matching.len() >= 3 {
+            let earliest = *matching.last().unwrap();
+            self.active_formatting.remove(earliest);
+        }
+        self.active_formatting.push(FormattingEntry::Element(node));
+    }
+
+    fn active_formatting_position(&self, node: NodeId) -> Option<usize> {
+        self.active_formatting
+            .iter()
+            .position(|entry| *entry == FormattingEntry::Element(node))
+    }
+
+    fn remove_from_active_formatting(&mut self, node: NodeId) {
+        if let Some(index) = self.active_formatting_position(node) {
+            self.active_formatting.remove(index);
+        }
+    }
+
+    fn active_formatting_element_after_last_marker(&self, name: &str) -> Option<NodeId> {
+        for entry in self.active_formatting.iter().rev() {
+            match *entry {
+                FormattingEntry::Marker => return None,
+                FormattingEntry::Element(node) if self.is_element(node, name) => return Some(node),
+                _ => {}
+            }
+        }
+        None
+    }
+
+    fn clear_active_formatting_to_last_marker(&mut self) {
+        while let Some(entry) = self.active_formatting.pop() {
+            if entry == FormattingEntry::Marker {
+                break;
+            }
+        }
+    }
+
+    fn reconstruct_active_formatting(&mut self) {
+        let is_open = |builder: &Self, entry: &FormattingEntry| match *entry {
+            FormattingEntry::Marker => true,
+            FormattingEntry::Element(node) => builder.open_elements.contains(&node),
+        };
+        let length = self.active_formatting.len();
+        if length == 0 || is_open(self, &self.active_formatting[length - 1]) {
+            return;
+        }
+        let mut index = length - 1;
+        while index > 0 && !is_open(self, &self.active_formatting[index - 1]) {
+            index -= 1;
+        }
+        while index < length {
+            if let FormattingEntry::Element(node) = self.active_formatting[index] {
+                let tag = self.formatting_tag(node);
+                let new_node = self.insert_html_element(tag);
+                self.active_formatting[index] = FormattingEntry::Element(new_node);
+            }
+            index += 1;
+        }
+    }
+
+    // Returns false when the end tag should be handled like any other end tag.
+    fn adoption_agency(&mut self, subject: &str) -> bool {
+        let current = self.current_node();
+        if self.is_element(current, subject) && self.active_formatting_position(current).is_none() {
+            self.pop();
+            return true;
+        }
+
+        for _ in 0..8 {
+            let formatting_element = match self.active_formatting_element_after_last_marker(subject)
+            {
+                Some(node) => node,
+                None => return false,
+            };
+            let formatting_index = match self
+                .open_elements
+                .iter()
+                .position(|&node| node == formatting_element)
+            {
+                Some(index) => index,
+                None => {
+                    self.error("unexpected-end-tag", "Formatting element not open");
+                    self.remove_from_active_formatting(formatting_element);
+                    return true;
+                }
+            };
+            if !self.node_in_scope(formatting_element) {
+                self.error("unexpected-end-tag", "Formatting element not in scope");
+                return true;
+            }
+            if formatting_element != self.current_node() {
+                self.error(
+                    "misnested-formatting-element",
+                    "Misnested formatting element",
+                );
+            }
+
+            let furthest_block_index = (formatting_index + 1..self.open_elements.len())
+                .find(|&index| self.is_special(self.open_elements[index]));
+            let furthest_block_index = match furthest_block_index {
+                Some(index) => index,
+                None => {
+                    self.pop_to(formatting_index);
+                    self.remove_from_active_formatting(formatting_element);
+                    return true;
+                }
+            };
+            let furthest_block = self.open_elements[furthest_block_index];
+            let common_ancestor = self.open_elements[formatting_index - 1];
+            let mut bookmark = self
+                .active_formatting_position(formatting_element)
+                .expect("formatting element is in the list");
+
+            let mut node_index = furthest_block_index;
+            let mut last_node = furthest_block;
+            let mut inner_loop_counter = 0;
+            loop {
+                inner_loop_counter += 1;
+                node_index -= 1;
+                let node = self.open_elements[node_index];
+                if node == formatting_element {
+                    break;
+                }
+                let mut position = self.active_formatting_position(node);
+                if inner_loop_counter > 3 {
+                    if let Some(index) = position {
+                        self.active_formatting.remove(index);
+                        if index < bookmark {
+                            bookmark -= 1;
+                        }
+                        position = None;
+                    }
+                }
+                let position = match position {
+                    Some(position) => position,
+                    None => {
+                        self.open_elements.remove(node_index);
+                        self.completed.push(node);
+                        continue;
+                    }
+                };
+                let tag = self.formatting_tag(node);
+                let new_node = self.create_element(tag, Namespace::Html);
+                self.active_formatting[position] = FormattingEntry::Element(new_node);
+                self.open_elements[node_index] = new_node;
+                self.completed.push(node);
+                if last_node == furthest_block {
+                    bookmark = position + 1;
+                }
+                self.append(new_node, last_node);
+                last_node = new_node;
+            }
+
+            self.detach(last_node);
+            let (parent, before) = self.appropriate_insertion_location(Some(common_ancestor));
+            self.insert_at(parent, before, last_node);
+
+            let tag = self.formatting_tag(formatting_element);
+            let new_node = self.create_element(tag, Namespace::Html);
+            let children = mem::take(&mut self.nodes[furthest_block].children);
+            for child in children {
+                self.nodes[child].parent = None;
+                self.append(new_node, child);
+            }
+            self.append(furthest_block, new_node);
+
+            self.active_formatting
+                .insert(bookmark, FormattingEntry::Element(new_node));
+            self.remove_from_active_formatting(formatting_element);
+            self.remove_open_element(formatting_element);
+            let furthest_block_index = self
+                .open_elements
+                .iter()
+                .position(|&node| node == furthest_block)
+                .expect("furthest block is open");
+            self.open_elements
+                .insert(furthest_block_index + 1, new_node);
+        }
+        true
+    }
+
+    // Output
+
+    /// Finishes the document, moving any diagnostics into `warnings`.
+    pub fn into_document(mut self, warnings: &mut Vec<Diagnostic>) -> Node {
+        self.nodes[DOCUMENT].span = Span {
+            start: Default::default(),
+            end: self.tokenizer.position(),
+        };
+        self.nodes[DOCUMENT].data = document(self.quirks_mode, vec![]).node_type;
+        warnings.append(&mut self.warnings);
+        self.take_node(DOCUMENT)
+    }
+
+    /// Finishes a fragment, returning the nodes parsed into the context
+    /// element.
+    pub fn into_fragment(mut self, warnings: &mut Vec<Diagnostic>) -> Vec<Node> {
+        warnings.append(&mut self.warnings);
+        let root = self.nodes[DOCUMENT].children[0];
+        let children = mem::take(&mut self.nodes[root].children);
+        children
+            .into_iter()
+            .map(|child| self.take_node(child))
+            .collect()
+    }
+
+    fn take_node(&mut self, id: NodeId) -> Node {
+        let children = mem::take(&mut self.nodes[id].children);
+        let children = children
+            .into_iter()
+            .map(|child| self.take_node(child))
+            .collect();
+        let node_type = mem::replace(&mut self.nodes[id].data, NodeType::Text(String::new()));
+        self.output_node(id, node_type, children)
+    }
+
+    fn output_node(&self, id: NodeId, node_type: NodeType, children: Vec<Node>) -> Node {
+        // Elements closed implicitly extend to the end of their contents.
+        let mut span = self.nodes[id].span;
+        for child in &children {
+            if child.span().end.offset() > span.end.offset() {
+                span.end = child.span().end;
+            }
+        }
+        let mut output = node(node_type, children);
+        output.set_span(span);
+        output
+    }
+}
+
+const HEADINGS: &[&str] = &["h1", "h2", "h3", "h4", "h5", "h6"];
+
+const FORMATTING_ELEMENTS: &[&str] = &[
+    "b", "big", "code", "em", "font", "i", "s", "small", "strike", "strong", "tt", "u",
+];
+
+const SPECIAL_ELEMENTS: &[&str] = &[
+    "address",
+    "applet",
+    "area",
+    "article",
+    "aside",
+    "base",
+    "basefont",
+    "bgsound",
+    "blockquote",
+    "body",
+    "br",
+    "button",
+    "caption",
+    "center",
+    "col",
+    "colgroup",
+    "dd",
+    "details",
+    "dir",
+    "div",
+    "dl",
+    "dt",
+    "embed",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "frame",
+    "frameset",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "head",
+    "header",
+    "hgroup",
+    "hr",
+    "html",
+    "iframe",
+    "img",
+    "input",
+    "keygen",
+    "li",
+    "link",
+    "listing",
+    "main",
+    "marquee",
+    "menu",
+    "meta",
+    "nav",
+    "noembed",
+    "noframes",
+    "noscript",
+    "object",
+    "ol",
+    "p",
+    "param",
+    "plaintext",
+    "pre",
+    "script",
+    "search",
+    "section",
+    "select",
+    "source",
+    "style",
+    "summary",
+    "table",
+    "tbody",
+    "td",
+    "template",
+    "textarea",
+    "tfoot",
+    "th",
+    "thead",
+    "title",
+    "tr",
+    "track",
+    "ul",
+    "wbr",
+    "xmp",
+];
+
+// HTML start tags which close any foreign elements they appear in.
+const BREAKOUT_ELEMENTS: &[&str] = &[
+    "b",
+    "big",
+    "blockquote",
+    "body",
+    "br",
+    "center",
+    "code",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "em",
+    "embed",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "head",
+    "hr",
+    "i",
+    "img",
+    "li",
+    "listing",
+    "menu",
+    "meta",
+    "nobr",
+    "ol",
+    "p",
+    "pre",
+    "ruby",
+    "s",
+    "small",
+    "span",
+    "strong",
+    "strike",
+    "sub",
+    "sup",
+    "table",
+    "tt",
+    "u",
+    "ul",
+    "var",
+];
+
+const SVG_TAG_NAMES: &[(&str, &str)] = &[
+    ("altglyph", "altGlyph"),
+    ("altglyphdef", "altGlyphDef"),
+    ("altglyphitem", "altGlyphItem"),
+    ("animatecolor", "animateColor"),
+    ("animatemotion", "animateMotion"),
+    ("animatetransform", "animateTransform"),
+    ("clippath", "clipPath"),
+    ("feblend", "feBlend"),
+    ("fecolormatrix", "feColorMatrix"),
+    ("fecomponenttransfer", "feComponentTransfer"),
+    ("fecomposite", "feComposite"),
+    ("feconvolvematrix", "feConvolveMatrix"),
+    ("fediffuselighting", "feDiffuseLighting"),
+    ("fedisplacementmap", "feDisplacementMap"),
+    ("fedistantlight", "feDistantLight"),
+    ("fedropshadow", "feDropShadow"),
+    ("feflood", "feFlood"),
+    ("fefunca", "feFuncA"),
+    ("fefuncb", "feFuncB"),
+    ("fefuncg", "feFuncG"),
+    ("fefuncr", "feFuncR"),
+    ("fegaussianblur", "feGaussianBlur"),
+    ("feimage", "feImage"),
+    ("femerge", "feMerge"),
+    ("femergenode", "feMergeNode"),
+    ("femorphology", "feMorphology"),
+    ("feoffset", "feOffset"),
+    ("fepointlight", "fePointLight"),
+    ("fespecularlighting", "feSpecularLighting"),
+    ("fespotlight", "feSpotLight"),
+    ("fetile", "feTile"),
+    ("feturbulence", "feTurbulence"),
+    ("foreignobject", "foreignObject"),
+    ("glyphref", "glyphRef"),
+    ("lineargradient", "linearGradient"),
+    ("radialgradient", "radialGradient"),
+    ("textpath", "textPath"),
+];
+
+const SVG_ATTRIBUTES: &[(&str, &str)] = &[
+    ("attributename", "attributeName"),
+    ("attributetype", "attributeType"),
+    ("basefrequency", "baseFrequency"),
+    ("baseprofile", "baseProfile"),
+    ("calcmode", "calcMode"),
+    ("clippathunits", "clipPathUnits"),
+    ("diffuseconstant", "diffuseConstant"),
+    ("edgemode", "edgeMode"),
+    ("filterunits", "filterUnits"),
+    ("glyphref", "glyphRef"),
+    ("gradienttransform", "gradientTransform"),
+    ("gradientunits", "gradientUnits"),
+    ("kernelmatrix", "kernelMatrix"),
+    ("kernelunitlength", "kernelUnitLength"),
+    ("keypoints", "keyPoints"),
+    ("keysplines", "keySplines"),
+    ("keytimes", "keyTimes"),
+    ("lengthadjust", "lengthAdjust"),
+    ("limitingconeangle", "limitingConeAngle"),
+    ("markerheight", "markerHeight"),
+    ("markerunits", "markerUnits"),
+    ("markerwidth", "markerWidth"),
+    ("maskcontentunits", "maskContentUnits"),
+    ("maskunits", "maskUnits"),
+    ("numoctaves", "numOctaves"),
+    ("pathlength", "pathLength"),
+    ("patterncontentunits", "patternContentUnits"),
+    ("patterntransform", "patternTransform"),
+    ("patternunits", "patternUnits"),
+    ("pointsatx", "pointsAtX"),
+    ("pointsaty", "pointsAtY"),
+    ("pointsatz", "pointsAtZ"),
+    ("preservealpha", "preserveAlpha"),
+    ("preserveaspectratio", "preserveAspectRatio"),
+    ("primitiveunits", "primitiveUnits"),
+    ("refx", "refX"),
+    ("refy", "refY"),
+    ("repeatcount", "repeatCount"),
+    ("repeatdur", "repeatDur"),
+    ("requiredextensions", "requiredExtensions"),
+    ("requiredfeatures", "requiredFeatures"),
+    ("specularconstant", "specularConstant"),
+    ("specularexponent", "specularExponent"),
+    ("spreadmethod", "spreadMethod"),
+    ("startoffset", "startOffset"),
+    ("stddeviation", "stdDeviation"),
+    ("stitchtiles", "stitchTiles"),
+    ("surfacescale", "surfaceScale"),
+    ("systemlanguage", "systemLanguage"),
+    ("tablevalues", "tableValues"),
+    ("targetx", "targetX"),
+    ("targety", "targetY"),
+    ("textlength", "textLength"),
+    ("viewbox", "viewBox"),
+    ("viewtarget", "viewTarget"),
+    ("xchannelselector", "xChannelSelector"),
+    ("ychannelselector", "yChannelSelector"),
+    ("zoomandpan", "zoomAndPan"),
+];
+
+const MATHML_ATTRIBUTES: &[(&str, &str)] = &[("definitionurl", "definitionURL")];
+
+// The tokenizer lowercases every name, so the mixed case names of SVG and
+// MathML are put back. Prefixed attributes such as `xlink:href` need no
+// adjustment as they keep their prefix in the name.
+fn adjust_foreign_names(tag: &mut Tag, namespace: Namespace) {
+    let (tag_names, attribute_names) = match namespace {
+        Namespace::Svg => (SVG_TAG_NAMES, SVG_ATTRIBUTES),
+        Namespace::MathMl => (&[][..], MATHML_ATTRIBUTES),
+        Namespace::Html => return,
+    };
+    let adjust = |name: &mut String, names: &[(&str, &str)]| {
+        if let Some(&(_, adjusted)) = names.iter().find(|&&(lower, _)| lower == name) {
+            *name = String::from(adjusted);
+        }
+    };
+    adjust(&mut tag.name, tag_names);
+    for attribute in &mut tag.attributes {
+        adjust(&mut attribute.name, attribute_names);
+    }
+}
+
+fn is_one_of(name: &str, names: &[&str]) -> bool {
+    names.contains(&name)
+}
+
+fn is_heading(name: &str) -> bool {
+    is_one_of(name, HEADINGS)
+}
+
+fn is_hidden_input(tag: &Tag) -> bool {
+    tag.attribute("type")
+        .is_some_and(|value| value.eq_ignore_ascii_case("hidden"))
+}
+
+fn same_tag(a: &Tag, b: &Tag) -> bool {
+    if a.name != b.name || a.attributes.len() != b.attributes.len() {
+        return false;
+    }
+    a.attributes
+        .iter()
+        .all(|attribute| b.attribute(&attribute.name) == Some(attribute.value.as_str()))
+}
+
+fn quirks_mode_for(doctype: &Doctype) -> QuirksMode {
+    const QUIRKY_PUBLIC_PREFIXES: &[&str] = &[
+        "+//silmaril//dtd html pro v0r11 19970101//",
+        "-//as//dtd html 3.0 aswedit + extensions//",
+        "-//advasoft ltd//dtd html 3.0 aswedit + extensions//",
+        "-//ietf//dtd html 2.0 level 1//",
+        "-//ietf//dtd html 2.0 level 2//",
+        "-//ietf//dtd html 2.0 strict level 1//",
+        "-//ietf//dtd html 2.0 strict level 2//",
+        "-//ietf//dtd html 2.0 strict//",
+        "-//ietf//dtd html 2.0//",
+        "-//ietf//dtd html 2.1e//",
+        "-//ietf//dtd html 3.0//",
+        "-//ietf//dtd html 3.2 final//",
+        "-//ietf//dtd html 3.2//",
+        "-//ietf//dtd html 3//",
+        "-//ietf//dtd html level 0//",
+        "-//ietf//dtd html level 1//",
+        "-//ietf//dtd html level 2//",
+        "-//ietf//dtd html level 3//",
+        "-//ietf//dtd html strict level 0//",
+        "-//ietf//dtd html strict level 1//",
+        "-//ietf//dtd html strict level 2//",
+        "-//ietf//dtd html strict level 3//",
+        "-//ietf//dtd html strict//",
+        "-//ietf//dtd html//",
+        "-//metrius//dtd metrius presentational//",
+        "-//microsoft//dtd internet explorer 2.0 html strict//",
+        "-//microsoft//dtd internet explorer 2.0 html//",
+        "-//microsoft//dtd internet explorer 2.0 tables//",
+        "-//microsoft//dtd internet explorer 3.0 html strict//",
+        "-//microsoft//dtd internet explorer 3.0 html//",
+        "-//microsoft//dtd internet explorer 3.0 tables//",
+        "-//netscape comm. corp.//dtd html//",
+        "-//netscape comm. corp.//dtd strict html//",
+        "-//o'reilly and associates//dtd html 2.0//",
+        "-//o'reilly and associates//dtd html extended 1.0//",
+        "-//o'reilly and associates//dtd html extended relaxed 1.0//",
+        "-//sq//dtd html 2.0 hotmetal + extensions//",
+        "-//softquad software//dtd hotmetal pro 6.0::19990601::extensions to html 4.0//",
+        "-//softquad//dtd hotmetal pro 4.0::19971010::extensions to html 4.0//",
+        "-//spyglass//dtd html 2.0 extended//",
+        "-//sun microsystems corp.//dtd hotjava html//",
+        "-//sun microsystems corp.//dtd hotjava strict html//",
+        "-//w3c//dtd html 3 1995-03-24//",
+        "-//w3c//dtd html 3.2 draft//",
+        "-//w3c//dtd html 3.2 final//",
+        "-//w3c//dtd html 3.2//",
+        "-//w3c//dtd html 3.2s draft//",
+        "-//w3c//dtd html 4.0 frameset//",
+        "-//w3c//dtd html 4.0 transitional//",
+        "-//w3c//dtd html experimental 19960712//",
+        "-//w3c//dtd html experimental 970421//",
+        "-//w3c//dtd w3 html//",
+        "-//w3o//dtd w3 html 3.0//",
+        "-//webtechs//dtd mozilla html 2.0//",
+        "-//webtechs//dtd mozilla html//",
+    ];
+    const FRAMESET_OR_TRANSITIONAL: &[&str] = &[
+        "-//w3c//dtd html 4.01 frameset//",
+        "-//w3c//dtd html 4.01 transitional//",
+    ];
+    const LIMITED_QUIRKS_PREFIXES: &[&str] = &[
+        "-//w3c//dtd xhtml 1.0 frameset//",
+        "-//w3c//dtd xhtml 1.0 transitional//",
+    ];
+
+    let public_id = doctype
+        .public_id
+        .as_ref()
+        .map(|public_id| public_id.to_ascii_lowercase());
+    let system_id = doctype
+        .system_id
+        .as_ref()
+        .map(|system_id| system_id.to_ascii_lowercase());
+    let public_starts_with = |prefixes: &[&str]| {
+        public_id
+            .as_ref()
+            .is_some_and(|public_id| prefixes.iter().any(|prefix| public_id.starts_with(prefix)))
+    };
+
+    if doctype.force_quirks
+        || doctype.name.as_ref().is_none_or(|name| name != "html")
+        || public_id.as_ref().is_some_and(|public_id| {
+            public_id == "-//w3o//dtd w3 html strict 3.0//en//"
+                || public_id == "-/w3c/dtd html 4.0 transitional/en"
+                || public_id == "html"
+        })
+        || system_id.as_ref().is_some_and(|system_id| {
+            system_id == "http://www.ibm.com/data/dtd/v11/ibmxhtml1-transitional.dtd"
+        })
+        || public_starts_with(QUIRKY_PUBLIC_PREFIXES)
+        || (system_id.is_none() && public_starts_with(FRAMESET_OR_TRANSITIONAL))
+    {
+        return QuirksMode::Quirks;
+    }
+    if public_starts_with(LIMITED_QUIRKS_PREFIXES)
+        || (system_id.is_some() && public_starts_with(FRAMESET_OR_TRANSITIONAL))
+    {
+        return QuirksMode::LimitedQuirks;
+    }
+    QuirksMode::NoQuirks
+}
@@ -1,9 +1,27 @@
+mod css_color;
 pub mod css_parser;
 #[cfg(test)]
 mod css_parser_test;
+mod css_tokenizer;
+#[cfg(test)]
+mod css_tokenizer_test;
+pub mod diagnostic;
+#[cfg(test)]
+mod diagnostic_test;
+pub mod encoding;
+#[cfg(test)]
+mod encoding_test;
 
+mod entities;
+#[cfg(test)]
+mod html5lib_test;
 pub mod html_parser;
 #[cfg(test)]
 mod html_parser_test;
+mod html_tokenizer;
+mod html_tree_builder;
 
+#[allow(clippy::module_inception)]
 mod parser;
+
+pub use self::parser::Span;
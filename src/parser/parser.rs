@@ -1,33 +1,116 @@
 use std::fmt;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct ParserPosition {
     overall: usize,
     x: usize,
     y: usize,
 }
 
+impl Default for ParserPosition {
+    fn default() -> ParserPosition {
+        ParserPosition {
+            overall: 0,
+            x: 0,
+            y: 1,
+        }
+    }
+}
+
+impl ParserPosition {
+    /// Byte offset from the start of the source.
+    pub fn offset(&self) -> usize {
+        self.overall
+    }
+
+    /// Line number, starting at 1.
+    pub fn line(&self) -> usize {
+        self.y
+    }
+
+    /// Byte offset from the start of the line, starting at 0.
+    pub fn column(&self) -> usize {
+        self.x
+    }
+
+    pub fn advance(self, c: char) -> ParserPosition {
+        if c == '\n' {
+            ParserPosition {
+                overall: self.overall + 1,
+                x: 0,
+                y: self.y + 1,
+            }
+        } else {
+            ParserPosition {
+                overall: self.overall + c.len_utf8(),
+                x: self.x + c.len_utf8(),
+                y: self.y,
+            }
+        }
+    }
+}
+
 impl fmt::Display for ParserPosition {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}:{}  ({})", self.y, self.x, self.overall)
     }
 }
 
+/// The source range a node, attribute or token was parsed from. `end` is
+/// exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Span {
+    pub start: ParserPosition,
+    pub end: ParserPosition,
+}
+
+impl Span {
+    pub fn at(position: ParserPosition) -> Span {
+        Span {
+            start: position,
+            end: position,
+        }
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}-{}:{}",
+            self.start.line(),
+            self.start.column(),
+            self.end.line(),
+            self.end.column()
+        )
+    }
+}
+
+/// A cursor over source text. Streaming parsers append text as it arrives
+/// and discard what they no longer need, so `text` starts at byte offset
+/// `discarded` of the source.
 pub struct Parser {
     position: ParserPosition,
     text: String,
-    save_point: Option<ParserPosition>,
+    discarded: usize,
+    complete: bool,
 }
 
 pub fn create(text: String) -> Parser {
     Parser {
-        position: ParserPosition {
-            overall: 0,
-            x: 0,
-            y: 1,
-        },
+        position: ParserPosition::default(),
         text,
-        save_point: None,
+        discarded: 0,
+        complete: true,
+    }
+}
+
+pub fn create_streaming() -> Parser {
+    Parser {
+        position: ParserPosition::default(),
+        text: String::new(),
+        discarded: 0,
+        complete: false,
     }
 }
 
@@ -36,88 +119,60 @@ impl Parser {
         self.position
     }
 
-    pub fn starts_with(&self, text: &str) -> bool {
-        self.text[self.position.overall..].starts_with(text)
+    pub fn push_str(&mut self, text: &str) {
+        self.text.push_str(text);
     }
 
-    pub fn next_char(&self) -> char {
-        self.text[self.position.overall..].chars().next().unwrap()
+    /// Marks the end of the input, after which `eof` can become true.
+    pub fn finish(&mut self) {
+        self.complete = true;
     }
 
-    pub fn consume_expected_text(&mut self, text: &str) -> Result<(), String> {
-        if !self.starts_with(text) {
-            let value = self.consume_next_n_chars(text.len());
-            Err(format!("Expected: {} Found: {}", text, value))
-        } else {
-            let length = text.len();
-            for _ in 0..length {
-                self.consume_char();
-            }
-            Ok(())
-        }
+    pub fn is_complete(&self) -> bool {
+        self.complete
     }
 
-    pub fn consume_next_n_chars(&mut self, len: usize) -> String {
-        let mut value = String::new();
-        for _ in 0..len {
-            if self.eof() {
-                break;
-            }
-            value.push(self.consume_char());
-        }
-        return value;
+    /// Drops the text before `position`, which must not be looked at again.
+    pub fn discard_before(&mut self, position: ParserPosition) {
+        let length = position.overall - self.discarded;
+        self.text.drain(..length);
+        self.discarded = position.overall;
     }
 
-    pub fn consume_alphanumeric_word(&mut self) -> String {
-        self.consume_while(|c| match c {
-            'a'..='z' | 'A'..='Z' | '0'..='9' => true,
-            _ => false,
-        })
+    pub fn starts_with(&self, text: &str) -> bool {
+        self.remaining().starts_with(text)
     }
 
-    pub fn consume_whitespace(&mut self) {
-        self.consume_while(char::is_whitespace);
+    pub fn remaining(&self) -> &str {
+        self.text_from(self.position)
     }
 
-    pub fn consume_while<F>(&mut self, test: F) -> String
-    where
-        F: Fn(char) -> bool,
-    {
-        let mut result = String::new();
-        while !self.eof() && test(self.next_char()) {
-            result.push(self.consume_char());
-        }
-        return result;
+    pub fn text_from(&self, position: ParserPosition) -> &str {
+        &self.text[position.overall - self.discarded..]
     }
 
-    pub fn consume_until_including(&mut self, breaker: char) {
-        self.consume_while(|c| c != breaker);
-        self.consume_char();
+    pub fn next_char(&self) -> char {
+        self.remaining().chars().next().unwrap()
     }
 
-    pub fn consume_char(&mut self) -> char {
-        let mut iter = self.text[self.position.overall..].char_indices();
-        let (_, cur_char) = iter.next().unwrap();
-        let (char_len, _) = iter.next().unwrap_or((1, ' '));
-        self.position.overall += char_len;
-        self.position.x += char_len;
-        if cur_char == '\n' {
-            self.position.y += 1;
-            self.position.x = 0;
+    pub fn consume_next_n_chars(&mut self, len: usize) -> String {
+        let mut value = String::new();
+        for _ in 0..len {
+            if self.eof() {
+                break;
+            }
+            value.push(self.consume_char());
         }
-        return cur_char;
-    }
-
-    pub fn eof(&self) -> bool {
-        self.position.overall >= self.text.len()
+        value
     }
 
-    pub fn set_save_point(&mut self) {
-        self.save_point = Some(self.position.clone());
+    pub fn consume_char(&mut self) -> char {
+        let cur_char = self.next_char();
+        self.position = self.position.advance(cur_char);
+        cur_char
     }
 
-    pub fn restore_from_save_point(&mut self) {
-        self.position = self.save_point.unwrap();
-        self.save_point = None;
+    pub fn eof(&self) -> bool {
+        self.remaining().is_empty()
     }
 }
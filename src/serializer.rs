@@ -0,0 +1,272 @@
+use dom::*;
+
+/// How attributes are ordered in serialized start tags.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AttributeOrder {
+    /// The order the attributes were added in, which is the order they were
+    /// written in for parsed elements.
+    Source,
+    /// Sorted by name, for output which does not depend on the source.
+    Alphabetical,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SerializeOptions {
+    /// Puts each child on its own indented line inside elements which hold
+    /// only block-level elements, comments and whitespace. Text and inline
+    /// elements are left as they are, as line breaks between them would be
+    /// rendered as spaces.
+    pub pretty: bool,
+    pub attribute_order: AttributeOrder,
+}
+
+impl Default for SerializeOptions {
+    fn default() -> SerializeOptions {
+        SerializeOptions {
+            pretty: false,
+            attribute_order: AttributeOrder::Source,
+        }
+    }
+}
+
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "basefont", "bgsound", "br", "col", "embed", "frame", "hr", "img", "input",
+    "keygen", "link", "meta", "param", "source", "track", "wbr",
+];
+
+// Text in these elements is written out as is.
+const RAW_TEXT_ELEMENTS: &[&str] = &[
+    "style",
+    "script",
+    "xmp",
+    "iframe",
+    "noembed",
+    "noframes",
+    "plaintext",
+];
+
+// Whitespace is significant in these elements, so they are never indented.
+const PREFORMATTED_ELEMENTS: &[&str] = &["pre", "textarea", "listing"];
+
+// Elements which are not rendered inline, so whitespace around them does not
+// show. Anything else, custom and foreign elements included, is taken to be
+// inline.
+const BLOCK_ELEMENTS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "base",
+    "blockquote",
+    "body",
+    "caption",
+    "col",
+    "colgroup",
+    "dd",
+    "details",
+    "dialog",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "frame",
+    "frameset",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "head",
+    "header",
+    "hgroup",
+    "hr",
+    "html",
+    "legend",
+    "li",
+    "link",
+    "listing",
+    "main",
+    "menu",
+    "meta",
+    "nav",
+    "noscript",
+    "ol",
+    "optgroup",
+    "option",
+    "p",
+    "pre",
+    "script",
+    "section",
+    "style",
+    "summary",
+    "table",
+    "tbody",
+    "td",
+    "template",
+    "tfoot",
+    "th",
+    "thead",
+    "title",
+    "tr",
+    "ul",
+];
+
+/// Writes a node and its descendants out as HTML, following the HTML
+/// fragment serialization algorithm. A document is written as its children.
+pub fn serialize(node: &Node, options: &SerializeOptions) -> String {
+    let mut output = String::new();
+    match node.node_type {
+        NodeType::Document(_) => serialize_children(node, options, 0, &mut output),
+        _ => serialize_node(node, None, options, 0, &mut output),
+    }
+    if options.pretty && !output.ends_with('\n') {
+        output.push('\n');
+    }
+    output
+}
+
+fn serialize_node(
+    node: &Node,
+    parent: Option<&ElementData>,
+    options: &SerializeOptions,
+    depth: usize,
+    output: &mut String,
+) {
+    match node.node_type {
+        NodeType::Document(_) => serialize_children(node, options, depth, output),
+        NodeType::DocType(ref doctype) => {
+            output.push_str(&format!("<!DOCTYPE {}>", doctype.name));
+        }
+        NodeType::Comment(ref comment) => {
+            output.push_str(&format!("<!--{}-->", comment));
+        }
+        NodeType::ProcessingInstruction(ref instruction) => {
+            // Written the XML way, as only the XML compatible parser keeps
+            // them, and it ends them at `?>`.
+            if instruction.data.is_empty() {
+                output.push_str(&format!("<?{}?>", instruction.target));
+            } else {
+                output.push_str(&format!("<?{} {}?>", instruction.target, instruction.data));
+            }
+        }
+        NodeType::Text(ref text) => {
+            if parent.is_some_and(|parent| is_html_element_one_of(parent, RAW_TEXT_ELEMENTS)) {
+                output.push_str(text);
+            } else {
+                output.push_str(&escape(text, false));
+            }
+        }
+        NodeType::Element(ref element) => {
+            output.push('<');
+            output.push_str(&element.tag_name);
+            for (name, value) in ordered_attributes(element, options.attribute_order) {
+                output.push_str(&format!(" {}=\"{}\"", name, escape(value, true)));
+            }
+            output.push('>');
+            if is_html_element_one_of(element, VOID_ELEMENTS) {
+                return;
+            }
+            // The parser drops a newline straight after these start tags, so
+            // one which belongs to the text is written twice.
+            if is_html_element_one_of(element, PREFORMATTED_ELEMENTS) {
+                if let Some(NodeType::Text(ref text)) =
+                    node.children.first().map(|child| &child.node_type)
+                {
+                    if text.starts_with('\n') {
+                        output.push('\n');
+                    }
+                }
+            }
+            serialize_children(node, options, depth + 1, output);
+            output.push_str(&format!("</{}>", element.tag_name));
+        }
+    }
+}
+
+fn serialize_children(node: &Node, options: &SerializeOptions, depth: usize, output: &mut String) {
+    let element = match node.node_type {
+        NodeType::Element(ref element) => Some(element),
+        _ => None,
+    };
+    if !options.pretty || !can_indent(node, element) {
+        for child in &node.children {
+            serialize_node(child, element, options, depth, output);
+        }
+        return;
+    }
+    // The document's children start on the first line, not indented.
+    let indent_depth = if element.is_some() { depth } else { 0 };
+    let mut first = true;
+    for child in &node.children {
+        if is_whitespace_text(child) {
+            continue;
+        }
+        if element.is_some() || !first {
+            output.push('\n');
+        }
+        output.push_str(&"  ".repeat(indent_depth));
+        serialize_node(child, element, options, depth, output);
+        first = false;
+    }
+    if element.is_some() && !first {
+        output.push('\n');
+        output.push_str(&"  ".repeat(depth - 1));
+    }
+}
+
+// Only whitespace between block-level elements may be replaced by
+// indentation.
+fn can_indent(node: &Node, element: Option<&ElementData>) -> bool {
+    if let Some(element) = element {
+        if is_html_element_one_of(element, RAW_TEXT_ELEMENTS)
+            || is_html_element_one_of(element, PREFORMATTED_ELEMENTS)
+        {
+            return false;
+        }
+    }
+    node.children.iter().all(|child| match child.node_type {
+        NodeType::Text(_) => is_whitespace_text(child),
+        NodeType::Element(ref child) => is_html_element_one_of(child, BLOCK_ELEMENTS),
+        _ => true,
+    })
+}
+
+fn is_whitespace_text(node: &Node) -> bool {
+    match node.node_type {
+        NodeType::Text(ref text) => text
+            .chars()
+            .all(|c| matches!(c, ' ' | '\t' | '\n' | '\x0C' | '\r')),
+        _ => false,
+    }
+}
+
+fn is_html_element_one_of(element: &ElementData, names: &[&str]) -> bool {
+    element.namespace == Namespace::Html && names.contains(&element.tag_name.as_str())
+}
+
+fn ordered_attributes(element: &ElementData, order: AttributeOrder) -> Vec<(&String, &String)> {
+    let mut attributes: Vec<(&String, &String)> = element.attributes.iter().collect();
+    if order == AttributeOrder::Alphabetical {
+        attributes.sort();
+    }
+    attributes
+}
+
+fn escape(text: &str, attribute_mode: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '\u{A0}' => escaped.push_str("&nbsp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' if attribute_mode => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
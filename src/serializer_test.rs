@@ -0,0 +1,137 @@
+use parser::html_parser;
+use serializer::*;
+
+#[test]
+fn it_round_trips_a_document() {
+    let source = "<!DOCTYPE html><html><head><title>a &amp; b</title></head>\
+                  <body><p class=\"x\">1 &lt; 2</p><!--note--></body></html>";
+    assert_eq!(serialize_html(source, &SerializeOptions::default()), source);
+}
+
+#[test]
+fn it_escapes_text_and_attributes() {
+    let output = serialize_html(
+        "<p title='say \"hi\" & <go>'>a\u{A0}&amp;<b>",
+        &SerializeOptions::default(),
+    );
+    assert!(
+        output.contains("<p title=\"say &quot;hi&quot; &amp; &lt;go&gt;\">a&nbsp;&amp;<b></b></p>")
+    );
+}
+
+#[test]
+fn it_writes_void_and_raw_text_elements() {
+    let output = serialize_html(
+        "<br><img src=x><script>if (a < b) {}</script><style>p > a {}</style>",
+        &SerializeOptions::default(),
+    );
+    assert!(output.contains("<script>if (a < b) {}</script><style>p > a {}</style>"));
+    assert!(output.contains("<br><img src=\"x\">"));
+    assert!(!output.contains("</br>"));
+}
+
+#[test]
+fn it_keeps_a_leading_newline_in_preformatted_text() {
+    let source = "<pre>\n\nx</pre><textarea>\n y</textarea>";
+    let output = serialize_html(source, &SerializeOptions::default());
+    assert!(output.contains("<pre>\n\nx</pre><textarea> y</textarea>"));
+}
+
+#[test]
+fn it_orders_attributes() {
+    let source = "<div id=a class=b data-c=c></div>";
+    let output = serialize_html(source, &SerializeOptions::default());
+    assert!(output.contains("<div id=\"a\" class=\"b\" data-c=\"c\">"));
+    let options = SerializeOptions {
+        attribute_order: AttributeOrder::Alphabetical,
+        ..SerializeOptions::default()
+    };
+    let output = serialize_html(source, &options);
+    assert!(output.contains("<div class=\"b\" data-c=\"c\" id=\"a\">"));
+}
+
+#[test]
+fn it_pretty_prints() {
+    let options = SerializeOptions {
+        pretty: true,
+        ..SerializeOptions::default()
+    };
+    let output = serialize_html(
+        "<!DOCTYPE html><ul>\n<li>one <b>two</b></li><li></li></ul><pre> <i>x</i>\n</pre>",
+        &options,
+    );
+    assert_eq!(
+        output,
+        "<!DOCTYPE html>\n\
+         <html>\n  \
+         <head></head>\n  \
+         <body>\n    \
+         <ul>\n      \
+         <li>one <b>two</b></li>\n      \
+         <li></li>\n    \
+         </ul>\n    \
+         <pre> <i>x</i>\n</pre>\n  \
+         </body>\n\
+         </html>\n"
+    );
+}
+
+#[test]
+fn it_leaves_inline_content_on_one_line_when_pretty_printing() {
+    let options = SerializeOptions {
+        pretty: true,
+        ..SerializeOptions::default()
+    };
+    let output = serialize_html("<div><p><b>a</b><i>b</i></p><span>c</span></div>", &options);
+    assert!(
+        output.contains("<div><p><b>a</b><i>b</i></p><span>c</span></div>"),
+        "{}",
+        output
+    );
+    let output = serialize_html("<div><p><b>a</b> <i>b</i></p><p></p></div>", &options);
+    assert!(
+        output.contains("<div>\n      <p><b>a</b> <i>b</i></p>\n      <p></p>\n    </div>"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn it_keeps_foreign_element_names() {
+    let output = serialize_html(
+        "<svg viewbox='0 0 1 1'><foreignobject></foreignobject></svg>",
+        &SerializeOptions::default(),
+    );
+    assert!(output.contains("<svg viewBox=\"0 0 1 1\"><foreignObject></foreignObject></svg>"));
+}
+
+#[test]
+fn it_writes_processing_instructions() {
+    let document = html_parser::parse_xml_compatible(
+        String::from("<?xml version=\"1.0\"?><p>x</p>"),
+        &mut vec![],
+    );
+    let output = serialize(&document, &SerializeOptions::default());
+    assert!(output.starts_with("<?xml version=\"1.0\"?><html>"));
+}
+
+#[test]
+fn it_round_trips_processing_instructions() {
+    let source = "<?xml version=\"1.0\"?><!DOCTYPE html><html><head></head>\
+                  <body><?php echo 1; ?><?empty?><p>x</p></body></html>";
+    let document = html_parser::parse_xml_compatible(String::from(source), &mut vec![]);
+    let output = serialize(&document, &SerializeOptions::default());
+    let reparsed = html_parser::parse_xml_compatible(output.clone(), &mut vec![]);
+    assert_eq!(serialize(&reparsed, &SerializeOptions::default()), output);
+    assert_eq!(output.matches("<html>").count(), 1, "{}", output);
+    assert!(
+        output.contains("<?php echo 1; ?><?empty?><p>x</p>"),
+        "{}",
+        output
+    );
+}
+
+fn serialize_html(source: &str, options: &SerializeOptions) -> String {
+    let document = html_parser::parse(String::from(source), &mut vec![]);
+    serialize(&document, options)
+}
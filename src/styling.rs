@@ -2,25 +2,48 @@ use cssom::*;
 use dom::*;
 
 pub fn apply_styling(dom: &Node, style_sheet: &StyleSheet) -> Node {
-    let rules = &style_sheet.rules;
-    style_node(dom, rules)
+    let mut dom = dom.clone();
+    style_node(&mut dom, &style_sheet.rules, BLACK);
+    dom
 }
 
-fn style_node(node: &Node, rules: &Vec<Rule>) -> Node {
-    match &node.node_type {
-        NodeType::Element(element_data) => Node {
-            node_type: NodeType::Element(ElementData {
-                tag_name: element_data.tag_name.clone(),
-                attributes: element_data.attributes.clone(),
-                style_values: build_style(&element_data, &rules),
-            }),
-            children: node
-                .children
-                .iter()
-                .map(|child| style_node(child, &rules))
-                .collect(),
-        },
-        _ => node.clone(),
+// `color` is inherited, so each element's color is passed to its children
+// for resolving `currentColor`.
+fn style_node(node: &mut Node, rules: &[Rule], parent_color: Color) {
+    let mut color = parent_color;
+    if let NodeType::Element(ref mut element_data) = node.node_type {
+        element_data.style_values = build_style(element_data, rules);
+        color = resolve_current_color(&mut element_data.style_values, parent_color);
+    }
+    for child in &mut node.children {
+        style_node(child, rules, color);
+    }
+}
+
+// Replaces `currentColor` with the element's color, which is returned. In
+// `color` itself it means the inherited color.
+fn resolve_current_color(styles: &mut PropertyMap, parent_color: Color) -> Color {
+    let color = match styles.get("color") {
+        Some(&Value::Color(color)) => color,
+        Some(Value::Keyword(keyword)) if keyword.eq_ignore_ascii_case("initial") => BLACK,
+        _ => parent_color,
+    };
+    for value in styles.values_mut() {
+        replace_current_color(value, color);
+    }
+    color
+}
+
+// Lists such as `border-color: red currentColor` are searched too.
+fn replace_current_color(value: &mut Value, color: Color) {
+    match *value {
+        Value::CurrentColor => *value = Value::Color(color),
+        Value::SpaceSeparated(ref mut values) | Value::CommaSeparated(ref mut values) => {
+            for value in values {
+                replace_current_color(value, color);
+            }
+        }
+        _ => {}
     }
 }
 
@@ -33,10 +56,7 @@ fn match_rule<'a>(element: &ElementData, rule: &'a Rule) -> Option<MatchedRule<'
         .map(|selector| (selector.specificity(), rule))
 }
 
-fn determine_matching_rules<'a>(
-    element: &ElementData,
-    rules: &'a Vec<Rule>,
-) -> Vec<MatchedRule<'a>> {
+fn determine_matching_rules<'a>(element: &ElementData, rules: &'a [Rule]) -> Vec<MatchedRule<'a>> {
     let res = rules
         .iter()
         .filter_map(|rule| match_rule(element, rule))
@@ -44,18 +64,24 @@ fn determine_matching_rules<'a>(
     res
 }
 
-fn build_style(element: &ElementData, rules: &Vec<Rule>) -> PropertyMap {
+fn build_style(element: &ElementData, rules: &[Rule]) -> PropertyMap {
     let mut styles = PropertyMap::new();
 
-    let mut matching_rules = determine_matching_rules(element, &rules);
+    let mut matching_rules = determine_matching_rules(element, rules);
 
-    matching_rules.sort_by(|&(a, _), &(b, _)| a.cmp(&b));
+    matching_rules.sort_by_key(|&(specificity, _)| specificity);
 
-    for (_, rule) in matching_rules {
-        for declaration in &rule.declarations {
-            let name = declaration.name.clone();
-            let value = declaration.value.clone();
-            styles.insert(name, value);
+    // Important declarations override normal ones whatever their
+    // specificity.
+    for important in &[false, true] {
+        for &(_, rule) in &matching_rules {
+            for declaration in &rule.declarations {
+                if declaration.important == *important {
+                    let name = declaration.name.clone();
+                    let value = declaration.value.clone();
+                    styles.insert(name, value);
+                }
+            }
         }
     }
 
@@ -0,0 +1,83 @@
+use cssom::*;
+use dom::*;
+use parser::css_parser;
+use parser::html_parser;
+use styling::*;
+
+#[test]
+fn it_resolves_current_color_against_the_inherited_color() {
+    let dom = html_parser::parse(
+        String::from("<div><p><b></b></p></div><i></i>"),
+        &mut vec![],
+    );
+    let style_sheet = css_parser::parse(
+        String::from(
+            "div { color: red } p { background-color: currentColor } \
+             b { color: currentColor; border-color: currentcolor } \
+             i { outline-color: currentColor }",
+        ),
+        &mut vec![],
+    );
+    let styled = apply_styling(&dom, &style_sheet);
+    let body = &styled.children[0].children[1];
+    let paragraph = &body.children[0].children[0];
+    let red = Color {
+        r: 255,
+        g: 0,
+        b: 0,
+        a: 255,
+    };
+    assert_eq!(style_color(paragraph, "background-color"), Some(red));
+    assert_eq!(style_color(&paragraph.children[0], "color"), Some(red));
+    assert_eq!(
+        style_color(&paragraph.children[0], "border-color"),
+        Some(red)
+    );
+    assert_eq!(style_color(&body.children[1], "outline-color"), Some(BLACK));
+}
+
+#[test]
+fn it_resolves_current_color_in_lists_and_after_inherit() {
+    let dom = html_parser::parse(String::from("<div><p></p></div>"), &mut vec![]);
+    let style_sheet = css_parser::parse(
+        String::from("div { color: red } p { color: inherit; border-color: blue currentColor }"),
+        &mut vec![],
+    );
+    let styled = apply_styling(&dom, &style_sheet);
+    let paragraph = &styled.children[0].children[1].children[0].children[0];
+    match paragraph.node_type {
+        NodeType::Element(ref element) => assert_eq!(
+            element.style_values["border-color"].to_string(),
+            "rgb(0, 0, 255) rgb(255, 0, 0)"
+        ),
+        _ => panic!("Wrong node type"),
+    }
+}
+
+#[test]
+fn it_applies_important_declarations_over_more_specific_ones() {
+    let dom = html_parser::parse(String::from("<p id=a class=b></p>"), &mut vec![]);
+    let style_sheet = css_parser::parse(
+        String::from("p { color: red !important } #a { color: blue } .b { color: lime }"),
+        &mut vec![],
+    );
+    let styled = apply_styling(&dom, &style_sheet);
+    let paragraph = &styled.children[0].children[1].children[0];
+    let red = Color {
+        r: 255,
+        g: 0,
+        b: 0,
+        a: 255,
+    };
+    assert_eq!(style_color(paragraph, "color"), Some(red));
+}
+
+fn style_color(node: &Node, property: &str) -> Option<Color> {
+    match node.node_type {
+        NodeType::Element(ref element) => match element.style_values.get(property) {
+            Some(&Value::Color(color)) => Some(color),
+            _ => None,
+        },
+        _ => None,
+    }
+}